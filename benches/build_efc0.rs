@@ -0,0 +1,93 @@
+//! build_efc0.rs — build time and recall@10 with a single `ef_construction`
+//! versus a small upper-layer beam plus a full layer-0 beam.
+//!
+//! ```bash
+//! cargo bench --bench build_efc0
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 64;
+const NUM_VECS: usize = 3_000;
+const NUM_QUERIES: usize = 200;
+const M: usize = 16;
+const K: usize = 10;
+const EFC: usize = 200;
+
+fn dataset(n: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| (0..DIMS).map(|_| rng.random::<f32>() - 0.5).collect())
+        .collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut na, mut nb) = (0.0_f32, 0.0_f32, 0.0_f32);
+    for i in 0..a.len() {
+        dot += a[i] * b[i];
+        na += a[i] * a[i];
+        nb += b[i] * b[i];
+    }
+    1.0 - dot / (na.sqrt() * nb.sqrt()).max(1e-12)
+}
+
+fn ground_truth(data: &[Vec<f32>], q: &[f32]) -> Vec<u64> {
+    let mut all: Vec<(u64, f32)> = data
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i as u64, cosine(v, q)))
+        .collect();
+    all.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    all.into_iter().take(K).map(|(id, _)| id).collect()
+}
+
+/// `upper` is the beam on layers >= 1; layer 0 always uses `EFC`.
+fn build(data: &[Vec<f32>], upper: usize) -> vcal_core::Hnsw<Cosine> {
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .m(M)
+        .ef_construction(upper)
+        .ef_construction_layer0(EFC)
+        .ef_search(64)
+        .seed(7)
+        .build()
+        .unwrap();
+    for (i, v) in data.iter().enumerate() {
+        h.insert(v.clone(), i as u64).unwrap();
+    }
+    h
+}
+
+fn bench_build(c: &mut Criterion) {
+    let data = dataset(NUM_VECS, 1);
+    let queries = dataset(NUM_QUERIES, 2);
+    let truth: Vec<Vec<u64>> = queries.iter().map(|q| ground_truth(&data, q)).collect();
+
+    let mut group = c.benchmark_group("build_efc0");
+    group.sample_size(10);
+    for &upper in &[EFC, 32] {
+        let h = build(&data, upper);
+        let mut found = 0usize;
+        for (q, gt) in queries.iter().zip(&truth) {
+            let hits = h.search(q, K).unwrap();
+            found += hits.iter().filter(|(id, _)| gt.contains(id)).count();
+        }
+        println!(
+            "\n[Console] efc(upper)={} efc(layer0)={} recall@{}: {:.4}",
+            upper,
+            EFC,
+            K,
+            found as f64 / (NUM_QUERIES * K) as f64
+        );
+
+        group.bench_with_input(BenchmarkId::new("build", upper), &upper, |b, &u| {
+            b.iter(|| build(&data, u))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_build);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+//! distance.rs — raw metric throughput on 768-dim vectors.
+//!
+//! Run with
+//! ```bash
+//! cargo bench --bench distance
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use vcal_core::{Cosine, Dot, Metric};
+
+const DIMS: usize = 768;
+
+fn bench_distance(c: &mut Criterion) {
+    let a: Vec<f32> = (0..DIMS).map(|i| (i as f32 * 0.37).sin()).collect();
+    let b: Vec<f32> = (0..DIMS).map(|i| (i as f32 * 0.11).cos()).collect();
+
+    let mut group = c.benchmark_group("distance_768");
+    group.throughput(Throughput::Elements(DIMS as u64));
+    group.bench_function("cosine", |bn| {
+        bn.iter(|| Cosine.distance(black_box(&a), black_box(&b)))
+    });
+    group.bench_function("dot", |bn| {
+        bn.iter(|| Dot.distance(black_box(&a), black_box(&b)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_distance);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+//! insert_alloc.rs — heap allocations per insert, plus insert throughput.
+//!
+//! A counting global allocator wraps the system one; the console line
+//! reports allocations per inserted vector for a seeded build.
+//!
+//! ```bash
+//! cargo bench --bench insert_alloc
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 64;
+const NUM_VECS: usize = 20_000;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn dataset(n: usize) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..n)
+        .map(|_| (0..DIMS).map(|_| rng.random::<f32>() - 0.5).collect())
+        .collect()
+}
+
+fn build(data: &[Vec<f32>]) -> vcal_core::Hnsw<Cosine> {
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .ef_construction(100)
+        .seed(7)
+        .build()
+        .unwrap();
+    h.reserve(data.len());
+    for (i, v) in data.iter().enumerate() {
+        h.insert(v.clone(), i as u64).unwrap();
+    }
+    h
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let data = dataset(NUM_VECS);
+
+    let before = ALLOCS.load(Ordering::Relaxed);
+    let h = build(&data);
+    let allocs = ALLOCS.load(Ordering::Relaxed) - before;
+    println!(
+        "\n[Console] {} inserts: {} allocations ({:.1} per insert, incl. vector clone)",
+        h.len(),
+        allocs,
+        allocs as f64 / NUM_VECS as f64
+    );
+
+    let mut group = c.benchmark_group("insert_alloc");
+    group.sample_size(10);
+    group.bench_function("build", |b| b.iter(|| build(&data)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert);
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+//! ivf.rs — recall@10 and latency of plain `search` versus `search_ivf`
+//! at several `nprobe` values, on 10k vectors split into 32 buckets.
+//!
+//! ```bash
+//! cargo bench --features ivf --bench ivf
+//! ```
+//!
+//! On this data recall@10 is 1.0 throughout. `search_ivf` takes about
+//! 67.5/70.9/74.6 µs at nprobe 2/4/8 against 75.4 µs for plain `search`:
+//! both start from the same upper-layer descent, and the bucket restriction
+//! skips scoring neighbours outside the probed clusters. Seeding the beam
+//! from each bucket's centroid node instead was slower than plain search
+//! (about 80/87/97 µs), since those seeds start farther from the query.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 64;
+const NUM_VECS: usize = 10_000;
+const NUM_QUERIES: usize = 200;
+const NLIST: usize = 32;
+const K: usize = 10;
+
+/// Clustered data (64 random centres plus noise), where IVF buckets are meaningful.
+fn dataset(n: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut centre_rng = StdRng::seed_from_u64(0);
+    let centres: Vec<Vec<f32>> = (0..64)
+        .map(|_| {
+            (0..DIMS)
+                .map(|_| centre_rng.random::<f32>() - 0.5)
+                .collect()
+        })
+        .collect();
+    (0..n)
+        .map(|_| {
+            let c = &centres[rng.random_range(0..centres.len())];
+            c.iter()
+                .map(|x| x + 0.15 * (rng.random::<f32>() - 0.5))
+                .collect()
+        })
+        .collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut na, mut nb) = (0.0_f32, 0.0_f32, 0.0_f32);
+    for i in 0..a.len() {
+        dot += a[i] * b[i];
+        na += a[i] * a[i];
+        nb += b[i] * b[i];
+    }
+    1.0 - dot / (na.sqrt() * nb.sqrt()).max(1e-12)
+}
+
+fn ground_truth(data: &[Vec<f32>], q: &[f32]) -> Vec<u64> {
+    let mut all: Vec<(u64, f32)> = data
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i as u64, cosine(v, q)))
+        .collect();
+    all.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    all.into_iter().take(K).map(|(id, _)| id).collect()
+}
+
+fn bench_ivf(c: &mut Criterion) {
+    let data = dataset(NUM_VECS, 1);
+    let queries = dataset(NUM_QUERIES, 2);
+    let truth: Vec<Vec<u64>> = queries.iter().map(|q| ground_truth(&data, q)).collect();
+
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .m(16)
+        .ef_construction(100)
+        .ef_search(64)
+        .track_access(false)
+        .seed(7)
+        .build()
+        .unwrap();
+    for (i, v) in data.iter().enumerate() {
+        h.insert(v.clone(), i as u64).unwrap();
+    }
+    h.train_ivf(NLIST, 2_000, 7).unwrap();
+
+    let mut group = c.benchmark_group("ivf");
+    // `None` is the plain `search` baseline.
+    for &nprobe in &[None, Some(8), Some(4), Some(2)] {
+        let run = |q: &[f32]| match nprobe {
+            None => h.search(q, K).unwrap(),
+            Some(p) => h.search_ivf(q, K, p).unwrap(),
+        };
+        let mut found = 0usize;
+        for (q, gt) in queries.iter().zip(&truth) {
+            found += run(q).iter().filter(|(id, _)| gt.contains(id)).count();
+        }
+        let label = nprobe.map_or_else(|| "hnsw".to_string(), |p| format!("nprobe={}", p));
+        println!(
+            "\n[Console] {} recall@{}: {:.4}",
+            label,
+            K,
+            found as f64 / (NUM_QUERIES * K) as f64
+        );
+
+        group.bench_function(BenchmarkId::new("search", &label), |b| {
+            let mut i = 0usize;
+            b.iter(|| {
+                i = (i + 1) % NUM_QUERIES;
+                run(&queries[i])
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_ivf);
+criterion_main!(benches);
@@ -0,0 +1,92 @@
+//! recall_m_max0.rs — recall@10 with and without layer-0 degree doubling.
+//!
+//! Uses a seeded uniform dataset so runs are reproducible; swap `dataset()`
+//! for SIFT/GloVe vectors to reproduce on a standard benchmark set.
+//!
+//! ```bash
+//! cargo bench --bench recall_m_max0
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 64;
+const NUM_VECS: usize = 5_000;
+const NUM_QUERIES: usize = 200;
+const M: usize = 8;
+const K: usize = 10;
+
+fn dataset(n: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| (0..DIMS).map(|_| rng.random::<f32>() - 0.5).collect())
+        .collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut na, mut nb) = (0.0_f32, 0.0_f32, 0.0_f32);
+    for i in 0..a.len() {
+        dot += a[i] * b[i];
+        na += a[i] * a[i];
+        nb += b[i] * b[i];
+    }
+    1.0 - dot / (na.sqrt() * nb.sqrt()).max(1e-12)
+}
+
+fn ground_truth(data: &[Vec<f32>], q: &[f32]) -> Vec<u64> {
+    let mut all: Vec<(u64, f32)> = data
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i as u64, cosine(v, q)))
+        .collect();
+    all.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    all.into_iter().take(K).map(|(id, _)| id).collect()
+}
+
+fn build(data: &[Vec<f32>], m_max0: usize) -> vcal_core::Hnsw<Cosine> {
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .m(M)
+        .m_max0(m_max0)
+        .ef_construction(100)
+        .ef_search(32)
+        .build()
+        .unwrap();
+    for (i, v) in data.iter().enumerate() {
+        h.insert(v.clone(), i as u64).unwrap();
+    }
+    h
+}
+
+fn bench_recall(c: &mut Criterion) {
+    let data = dataset(NUM_VECS, 1);
+    let queries = dataset(NUM_QUERIES, 2);
+    let truth: Vec<Vec<u64>> = queries.iter().map(|q| ground_truth(&data, q)).collect();
+
+    let mut group = c.benchmark_group("recall_m_max0");
+    for &m0 in &[M, 2 * M] {
+        let h = build(&data, m0);
+
+        let mut found = 0usize;
+        for (q, gt) in queries.iter().zip(&truth) {
+            let hits = h.search(q, K).unwrap();
+            found += hits.iter().filter(|(id, _)| gt.contains(id)).count();
+        }
+        println!(
+            "\n[Console] m={} m_max0={} recall@{}: {:.4}",
+            M,
+            m0,
+            K,
+            found as f64 / (NUM_QUERIES * K) as f64
+        );
+
+        group.bench_with_input(BenchmarkId::new("search", m0), &queries[0], |b, q| {
+            b.iter(|| h.search(q, K).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_recall);
+criterion_main!(benches);
@@ -0,0 +1,94 @@
+//! search_shallow.rs — latency and recall@10 of `search` versus
+//! `search_shallow` with a few layer-0 visit caps, on 10k vectors.
+//!
+//! ```bash
+//! cargo bench --bench search_shallow
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 64;
+const NUM_VECS: usize = 10_000;
+const NUM_QUERIES: usize = 200;
+const K: usize = 10;
+const EF: usize = 128;
+
+fn dataset(n: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| (0..DIMS).map(|_| rng.random::<f32>() - 0.5).collect())
+        .collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut na, mut nb) = (0.0_f32, 0.0_f32, 0.0_f32);
+    for i in 0..a.len() {
+        dot += a[i] * b[i];
+        na += a[i] * a[i];
+        nb += b[i] * b[i];
+    }
+    1.0 - dot / (na.sqrt() * nb.sqrt()).max(1e-12)
+}
+
+fn ground_truth(data: &[Vec<f32>], q: &[f32]) -> Vec<u64> {
+    let mut all: Vec<(u64, f32)> = data
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i as u64, cosine(v, q)))
+        .collect();
+    all.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    all.into_iter().take(K).map(|(id, _)| id).collect()
+}
+
+fn bench_search_shallow(c: &mut Criterion) {
+    let data = dataset(NUM_VECS, 1);
+    let queries = dataset(NUM_QUERIES, 2);
+    let truth: Vec<Vec<u64>> = queries.iter().map(|q| ground_truth(&data, q)).collect();
+
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .m(16)
+        .ef_construction(100)
+        .ef_search(EF)
+        .track_access(false)
+        .seed(7)
+        .build()
+        .unwrap();
+    for (i, v) in data.iter().enumerate() {
+        h.insert(v.clone(), i as u64).unwrap();
+    }
+
+    let mut group = c.benchmark_group("search_shallow");
+    // `None` is the full `search` baseline.
+    for &cap in &[None, Some(512), Some(128), Some(32)] {
+        let run = |q: &[f32]| match cap {
+            None => h.search(q, K).unwrap(),
+            Some(v) => h.search_shallow(q, K, v).unwrap(),
+        };
+        let mut found = 0usize;
+        for (q, gt) in queries.iter().zip(&truth) {
+            found += run(q).iter().filter(|(id, _)| gt.contains(id)).count();
+        }
+        let label = cap.map_or_else(|| "full".to_string(), |v| v.to_string());
+        println!(
+            "\n[Console] layer0 cap={} recall@{}: {:.4}",
+            label,
+            K,
+            found as f64 / (NUM_QUERIES * K) as f64
+        );
+
+        group.bench_function(BenchmarkId::new("cap", &label), |b| {
+            let mut i = 0usize;
+            b.iter(|| {
+                i = (i + 1) % NUM_QUERIES;
+                run(&queries[i])
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_search_shallow);
+criterion_main!(benches);
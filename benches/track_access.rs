@@ -0,0 +1,68 @@
+//! track_access.rs — multi-threaded search throughput with and without
+//! `last_hit` tracking (atomic stores on every hit).
+//!
+//! Run with
+//! ```bash
+//! cargo bench --bench track_access
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 64;
+const NUM_VECS: usize = 5_000;
+const K: usize = 10;
+const THREADS: usize = 8;
+
+fn build(track: bool) -> vcal_core::Hnsw<Cosine> {
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .track_access(track)
+        .seed(7)
+        .build()
+        .unwrap();
+    for i in 0..NUM_VECS {
+        let v: Vec<f32> = (0..DIMS).map(|d| ((i * DIMS + d) as f32).sin()).collect();
+        h.insert(v, i as u64).unwrap();
+    }
+    h
+}
+
+fn bench_track_access(c: &mut Criterion) {
+    let query: Arc<Vec<f32>> = Arc::new((0..DIMS).map(|d| (d as f32).cos()).collect());
+
+    let mut group = c.benchmark_group("search_track_access");
+    for &track in &[true, false] {
+        let h = Arc::new(build(track));
+        group.bench_function(
+            BenchmarkId::new("threads", format!("{}/track={}", THREADS, track)),
+            |b| {
+                b.iter_custom(|iters| {
+                    let start = Instant::now();
+                    let handles: Vec<_> = (0..THREADS)
+                        .map(|_| {
+                            let (h, q) = (Arc::clone(&h), Arc::clone(&query));
+                            thread::spawn(move || {
+                                for _ in 0..iters {
+                                    h.search(&q[..], K).unwrap();
+                                }
+                            })
+                        })
+                        .collect();
+                    for t in handles {
+                        t.join().unwrap();
+                    }
+                    let elapsed: Duration = start.elapsed();
+                    elapsed / THREADS as u32
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_track_access);
+criterion_main!(benches);
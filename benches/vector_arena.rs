@@ -0,0 +1,58 @@
+//! vector_arena.rs — search latency with per-node vector allocations
+//! versus the same graph after `pack_vectors`, on 100k × 128 vectors.
+//!
+//! ```bash
+//! cargo bench --bench vector_arena
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vcal_core::{Cosine, HnswBuilder};
+
+const DIMS: usize = 128;
+const NUM_VECS: usize = 100_000;
+const NUM_QUERIES: usize = 500;
+const K: usize = 10;
+
+fn dataset(n: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| (0..DIMS).map(|_| rng.random::<f32>() - 0.5).collect())
+        .collect()
+}
+
+fn bench_vector_arena(c: &mut Criterion) {
+    let queries = dataset(NUM_QUERIES, 2);
+    let mut h = HnswBuilder::<Cosine>::default()
+        .dims(DIMS)
+        .m(16)
+        .ef_construction(100)
+        .ef_search(64)
+        .track_access(false)
+        .seed(7)
+        .build()
+        .unwrap();
+    for (i, v) in dataset(NUM_VECS, 1).into_iter().enumerate() {
+        h.insert(v, i as u64).unwrap();
+    }
+
+    let mut group = c.benchmark_group("vector_arena");
+    // Same graph both times; only where the vectors live differs.
+    for packed in [false, true] {
+        if packed {
+            h.pack_vectors();
+        }
+        let label = if packed { "arena" } else { "per_node" };
+        group.bench_function(label, |b| {
+            let mut i = 0usize;
+            b.iter(|| {
+                i = (i + 1) % NUM_QUERIES;
+                h.search(&queries[i], K).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_vector_arena);
+criterion_main!(benches);
@@ -0,0 +1,173 @@
+//! binary.rs — HNSW over packed binary codes with integer Hamming distances.
+//!
+//! Reuses the `f32` graph unchanged: each `u64` word is stored as two `f32`
+//! lanes holding its raw bits, and [`HammingBits`] counts differing bits with
+//! `count_ones`. Distances come back as exact `u32` popcounts.
+//!
+//! `Metric` keeps its `f32` distance: popcounts are exact in `f32` up to
+//! 2^24 differing bits, so a generic distance type isn't worth threading
+//! through the graph. There is no snapshot support: JSON cannot carry the
+//! NaN bit patterns some lanes hold.
+
+use crate::{math::HammingBits, ExternalId, Hnsw, HnswBuilder, IdType, Result, VcalError};
+
+/// Index over fixed-width binary codes of `words` `u64`s each.
+pub struct BinaryHnsw<Id: IdType = ExternalId> {
+    inner: Hnsw<HammingBits, Id>,
+    words: usize,
+}
+
+/// Two `f32` lanes per word, low half first.
+fn to_lanes(code: &[u64]) -> Vec<f32> {
+    code.iter()
+        .flat_map(|&w| [f32::from_bits(w as u32), f32::from_bits((w >> 32) as u32)])
+        .collect()
+}
+
+impl BinaryHnsw<ExternalId> {
+    /// Index for codes of `words` `u64`s with default graph parameters.
+    pub fn new(words: usize) -> Result<Self> {
+        Self::with_builder(words, HnswBuilder::new(HammingBits))
+    }
+}
+
+impl<Id: IdType> BinaryHnsw<Id> {
+    /// Index for codes of `words` `u64`s using `builder`'s graph parameters
+    /// (`m`, `ef_*`, `seed`, ...). Its `dims` is overridden, and
+    /// `normalize_query` and `flush_subnormals` are forced off: both rewrite
+    /// the `f32` lanes, which here are raw code bits.
+    pub fn with_builder(words: usize, builder: HnswBuilder<HammingBits>) -> Result<Self> {
+        if words == 0 {
+            return Err(VcalError::InvalidDimensions { found: 0 });
+        }
+        Ok(Self {
+            inner: builder
+                .dims(words * 2)
+                .normalize_query(false)
+                .flush_subnormals(false)
+                .build_with_ids()?,
+            words,
+        })
+    }
+
+    /// Code width in `u64` words.
+    #[inline]
+    pub fn words(&self) -> usize {
+        self.words
+    }
+
+    fn check(&self, code: &[u64]) -> Result<()> {
+        if code.len() != self.words {
+            return Err(VcalError::DimensionMismatch {
+                expected: self.words,
+                found: code.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Insert (or upsert) `code` under `ext_id`.
+    pub fn insert(&mut self, code: &[u64], ext_id: Id) -> Result<()> {
+        self.check(code)?;
+        self.inner.insert(to_lanes(code), ext_id)
+    }
+
+    /// Up to `k` nearest codes as `(id, differing bits)`, nearest first.
+    pub fn search(&self, code: &[u64], k: usize) -> Result<Vec<(Id, u32)>> {
+        self.check(code)?;
+        Ok(self
+            .inner
+            .search(to_lanes(code), k)?
+            .into_iter()
+            .map(|(id, d)| (id, d as u32))
+            .collect())
+    }
+
+    /// Idempotent delete; true if something was removed.
+    pub fn delete(&mut self, ext_id: Id) -> bool {
+        self.inner.delete(ext_id)
+    }
+
+    #[inline]
+    pub fn contains(&self, ext_id: Id) -> bool {
+        self.inner.contains(ext_id)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// The underlying index, for stats and tuning (`set_ef`, eviction, ...).
+    /// Its vectors are bit lanes, so don't query it with real-valued data.
+    pub fn inner_mut(&mut self) -> &mut Hnsw<HammingBits, Id> {
+        &mut self.inner
+    }
+
+    /// Read-only view of the underlying index.
+    pub fn inner(&self) -> &Hnsw<HammingBits, Id> {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn popcount_dist(a: &[u64], b: &[u64]) -> u32 {
+        a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+    }
+
+    #[test]
+    fn binary_search_returns_exact_popcounts() {
+        let mut rng = StdRng::seed_from_u64(149);
+        let codes: Vec<Vec<u64>> = (0..500)
+            .map(|_| (0..4).map(|_| rng.random::<u64>()).collect())
+            .collect();
+        let mut h = BinaryHnsw::with_builder(4, HnswBuilder::new(HammingBits).seed(149)).unwrap();
+        for (i, c) in codes.iter().enumerate() {
+            h.insert(c, i as u64).unwrap();
+        }
+        assert_eq!(h.len(), 500);
+
+        let mut q = codes[42].clone();
+        q[1] ^= 0b111;
+        let hits = h.search(&q, 5).unwrap();
+        assert_eq!(hits[0], (42, 3));
+        for &(id, d) in &hits {
+            assert_eq!(d, popcount_dist(&codes[id as usize], &q));
+        }
+        assert!(hits.windows(2).all(|w| w[0].1 <= w[1].1));
+
+        assert!(matches!(
+            h.search(&[0; 3], 1),
+            Err(VcalError::DimensionMismatch {
+                expected: 4,
+                found: 3
+            })
+        ));
+        assert!(h.delete(42));
+        assert_ne!(h.search(&q, 1).unwrap()[0].0, 42);
+        assert!(BinaryHnsw::new(0).is_err());
+    }
+
+    #[test]
+    fn lane_rewriting_flags_are_ignored() {
+        for builder in [
+            HnswBuilder::new(HammingBits).flush_subnormals(true),
+            HnswBuilder::new(HammingBits).normalize_query(true),
+        ] {
+            // Each half of the first code is a subnormal `f32` bit pattern.
+            let mut h = BinaryHnsw::with_builder(1, builder).unwrap();
+            h.insert(&[0x0000_0001_0000_0001], 1).unwrap();
+            h.insert(&[u64::MAX], 2).unwrap();
+            assert_eq!(h.search(&[0u64], 2).unwrap(), vec![(1, 2), (2, 64)]);
+        }
+    }
+}
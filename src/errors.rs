@@ -12,15 +12,37 @@ pub enum VcalError {
         expected: usize,
         found: usize,
     },
+    /// `DimensionMismatch` raised by a batch insert or a snapshot load;
+    /// `index` is the 0-based position of the offending record in the input
+    /// (for snapshots, of the node in `graph.nodes`).
+    DimensionMismatchAt {
+        expected: usize,
+        found: usize,
+        index: usize,
+    },
     InvalidDimensions {
         found: usize,
     },
     InvalidParameter(&'static str),
+    /// The index itself is misconfigured (e.g. it has no dimensionality).
+    InvalidConfig(&'static str),
     EmptyIndex,
-    #[cfg(feature = "serde")]
+    /// The caller's cancel flag was raised before the operation finished.
+    Cancelled,
+    /// A transactional insert failed midway and was rolled back; carries the
+    /// panic message.
+    InsertFailed(String),
+    /// No live vector is stored under this external id.
+    UnknownId(u64),
+    /// `UnknownId` for an index with a custom id type (`Hnsw<M, Id>` with
+    /// `Id` other than `u64`); carries the id's `Debug` rendering.
+    UnknownCustomId(String),
+    #[cfg(any(feature = "serde", feature = "hnswlib_compat"))]
     CorruptSnapshot(String),
     #[cfg(feature = "serde")]
     Serialize(String),
+    #[cfg(feature = "serde")]
+    Io(String),
 }
 
 impl fmt::Display for VcalError {
@@ -33,19 +55,64 @@ impl fmt::Display for VcalError {
                     expected, found
                 )
             }
+            VcalError::DimensionMismatchAt {
+                expected,
+                found,
+                index,
+            } => {
+                write!(
+                    f,
+                    "dimension mismatch at record {}: expected {}, got {}",
+                    index, expected, found
+                )
+            }
             VcalError::InvalidDimensions { found } => {
                 write!(f, "invalid index dimensions: expected > 0, got {}", found)
             }
             VcalError::InvalidParameter(msg) => {
                 write!(f, "invalid parameter: {}", msg)
             }
+            VcalError::InvalidConfig(msg) => {
+                write!(f, "invalid index configuration: {}", msg)
+            }
             VcalError::EmptyIndex => write!(f, "index is empty"),
-            #[cfg(feature = "serde")]
+            VcalError::Cancelled => write!(f, "operation cancelled"),
+            VcalError::InsertFailed(msg) => write!(f, "insert rolled back: {}", msg),
+            VcalError::UnknownId(id) => write!(f, "unknown id: {}", id),
+            VcalError::UnknownCustomId(id) => write!(f, "unknown id: {}", id),
+            #[cfg(any(feature = "serde", feature = "hnswlib_compat"))]
             VcalError::CorruptSnapshot(msg) => write!(f, "corrupt snapshot: {}", msg),
             #[cfg(feature = "serde")]
             VcalError::Serialize(msg) => write!(f, "serialization error: {}", msg),
+            #[cfg(feature = "serde")]
+            VcalError::Io(msg) => write!(f, "i/o error: {}", msg),
+        }
+    }
+}
+
+impl VcalError {
+    /// Tag a `DimensionMismatch` with the batch position it came from;
+    /// other variants pass through unchanged.
+    pub(crate) fn at(self, index: usize) -> Self {
+        match self {
+            VcalError::DimensionMismatch { expected, found } => VcalError::DimensionMismatchAt {
+                expected,
+                found,
+                index,
+            },
+            other => other,
         }
     }
 }
 
 impl std::error::Error for VcalError {}
+
+impl VcalError {
+    /// `UnknownId` for `u64` ids, `UnknownCustomId` for any other id type.
+    pub(crate) fn unknown_id<Id: crate::IdType>(id: Id) -> Self {
+        match (&id as &dyn std::any::Any).downcast_ref::<u64>() {
+            Some(&id) => VcalError::UnknownId(id),
+            None => VcalError::UnknownCustomId(format!("{:?}", id)),
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! frozen.rs — immutable, compact read-only form of an index.
+//!
+//! [`Hnsw::freeze`] drops tombstones and renumbers live nodes densely (in
+//! NodeId order), then lays everything out contiguously: vectors in one
+//! `Vec<f32>`, layer 0 as CSR (`offsets` + flat `u32` neighbours), each
+//! upper layer as CSR over its sorted member list, and ids sorted for
+//! binary-search lookup. Search runs the same descent and beam as the
+//! mutable graph, so a frozen index returns what the source index would
+//! have (without `recent_ties`). There is no `last_hit` tracking and no way
+//! back to a mutable `Hnsw`.
+
+use crate::{
+    math::{self, Metric},
+    Cosine, ExternalId, Hnsw, IdType, Result, SearchHit, VcalError,
+};
+use ordered_float::OrderedFloat;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+/// Adjacency of one layer: `edges[offsets[i]..offsets[i + 1]]` are the
+/// neighbours of the `i`-th member.
+struct Csr {
+    /// Dense node indexes present on this layer, ascending. Empty on
+    /// layer 0, where every node is a member and `i` is the node itself.
+    members: Vec<u32>,
+    offsets: Vec<u32>,
+    edges: Vec<u32>,
+}
+
+impl Csr {
+    fn neighbors(&self, node: u32) -> &[u32] {
+        let i = if self.members.is_empty() {
+            node as usize
+        } else {
+            match self.members.binary_search(&node) {
+                Ok(i) => i,
+                Err(_) => return &[],
+            }
+        };
+        &self.edges[self.offsets[i] as usize..self.offsets[i + 1] as usize]
+    }
+
+    fn heap_bytes(&self) -> usize {
+        4 * (self.members.capacity() + self.offsets.capacity() + self.edges.capacity())
+    }
+}
+
+/// Read-only index produced by [`Hnsw::freeze`]; supports search and
+/// vector lookup only.
+pub struct FrozenHnsw<M: Metric = Cosine, Id: IdType = ExternalId> {
+    dims: usize,
+    ef: usize,
+    normalize_query: bool,
+    flush_subnormals: bool,
+    empty_returns_ok: bool,
+    metric: M,
+    /// Row `i` is node `i`'s vector.
+    vecs: Vec<f32>,
+    ids: Vec<Id>,
+    /// Node indexes sorted by id.
+    by_id: Vec<u32>,
+    /// `layers[l]` is layer `l`; layer 0 is dense.
+    layers: Vec<Csr>,
+    entry: Option<u32>,
+}
+
+impl<M: Metric, Id: IdType + Ord> Hnsw<M, Id> {
+    /// Convert into a compact immutable [`FrozenHnsw`] for read-only
+    /// serving. Tombstones and edges to them are dropped; `ef`, `dims`,
+    /// `normalize_query`, `flush_subnormals` and `empty_returns_ok` carry
+    /// over. O(n·(d + m)).
+    /// Requires `Id: Ord` for the sorted id table.
+    ///
+    /// # Panics
+    /// If the index holds `u32::MAX` or more live nodes.
+    pub fn freeze(self) -> FrozenHnsw<M, Id> {
+        let g = &self.graph;
+        let live: Vec<usize> = (0..g.nodes.len())
+            .filter(|&nid| !g.nodes[nid].is_deleted())
+            .collect();
+        assert!(
+            live.len() < u32::MAX as usize,
+            "FrozenHnsw holds fewer than u32::MAX nodes"
+        );
+        let mut dense = vec![u32::MAX; g.nodes.len()];
+        for (i, &nid) in live.iter().enumerate() {
+            dense[nid] = i as u32;
+        }
+
+        let mut vecs = Vec::with_capacity(live.len() * self.dims);
+        for &nid in &live {
+            vecs.extend_from_slice(&g.nodes[nid].vec);
+        }
+        let ids: Vec<Id> = live.iter().map(|&nid| g.nodes[nid].ext_id).collect();
+        let mut by_id: Vec<u32> = (0..live.len() as u32).collect();
+        by_id.sort_unstable_by_key(|&i| ids[i as usize]);
+
+        let top = live
+            .iter()
+            .map(|&nid| g.nodes[nid].links.len().saturating_sub(1))
+            .max()
+            .unwrap_or(0);
+        let layers = (0..=top)
+            .map(|l| {
+                let mut csr = Csr {
+                    members: Vec::new(),
+                    offsets: vec![0],
+                    edges: Vec::new(),
+                };
+                for (i, &nid) in live.iter().enumerate() {
+                    if g.nodes[nid].links.len() <= l {
+                        continue;
+                    }
+                    if l > 0 {
+                        csr.members.push(i as u32);
+                    }
+                    // Keep link order so the beam visits neighbours exactly
+                    // as the mutable graph does.
+                    csr.edges.extend(
+                        g.neighbors(nid, l)
+                            .iter()
+                            .map(|&nb| dense.get(nb).copied().unwrap_or(u32::MAX))
+                            .filter(|&nb| nb != u32::MAX),
+                    );
+                    csr.offsets.push(csr.edges.len() as u32);
+                }
+                csr.members.shrink_to_fit();
+                csr.edges.shrink_to_fit();
+                csr
+            })
+            .collect();
+
+        // Same entry the mutable search would start from.
+        let entry = match g.entry {
+            Some(e) if e < dense.len() && dense[e] != u32::MAX => Some(dense[e]),
+            _ => g.search_entry().map(|e| dense[e]),
+        };
+
+        FrozenHnsw {
+            dims: self.dims,
+            ef: self.ef,
+            normalize_query: self.normalize_query,
+            flush_subnormals: self.flush_subnormals,
+            empty_returns_ok: self.empty_returns_ok,
+            metric: self.metric,
+            vecs,
+            ids,
+            by_id,
+            layers,
+            entry,
+        }
+    }
+}
+
+impl<M: Metric, Id: IdType + Ord> FrozenHnsw<M, Id> {
+    /// k-NN search using the `ef` the index was frozen with.
+    #[inline]
+    pub fn search(&self, query: impl AsRef<[f32]>, k: usize) -> Result<Vec<SearchHit<Id>>> {
+        self.search_with_ef(query, k, self.ef)
+    }
+
+    /// k-NN search with a per-request `ef` (clamped to at least `k` and 1).
+    pub fn search_with_ef(
+        &self,
+        query: impl AsRef<[f32]>,
+        k: usize,
+        ef: usize,
+    ) -> Result<Vec<SearchHit<Id>>> {
+        let query = match self.prepare_query(query.as_ref())? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        let Some(mut ep) = self.entry else {
+            return Ok(Vec::new());
+        };
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut d_ep = self.metric.distance(&query, self.vector(ep));
+        for l in (1..self.layers.len()).rev() {
+            let next = self.greedy(ep, d_ep, &query, l);
+            ep = next.0;
+            d_ep = next.1;
+        }
+        let mut cand = self.beam(ep, d_ep, &query, ef.max(k));
+        cand.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        cand.truncate(k);
+        Ok(cand
+            .into_iter()
+            .map(|(i, d)| (self.ids[i as usize], d))
+            .collect())
+    }
+
+    /// The stored vector of `ext_id`, if present.
+    pub fn get_vector(&self, ext_id: Id) -> Option<&[f32]> {
+        let pos = self
+            .by_id
+            .binary_search_by_key(&ext_id, |&i| self.ids[i as usize])
+            .ok()?;
+        Some(self.vector(self.by_id[pos]))
+    }
+
+    /// Whether `ext_id` is stored.
+    #[inline]
+    pub fn contains(&self, ext_id: Id) -> bool {
+        self.get_vector(ext_id).is_some()
+    }
+}
+
+impl<M: Metric, Id: IdType> FrozenHnsw<M, Id> {
+    /// Number of stored vectors.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// True when no vectors are stored.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Embedding dimensionality.
+    #[inline]
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    /// Heap memory held by vectors, ids and adjacency, in bytes.
+    pub fn heap_bytes(&self) -> usize {
+        4 * (self.vecs.capacity() + self.by_id.capacity())
+            + std::mem::size_of::<Id>() * self.ids.capacity()
+            + self.layers.iter().map(Csr::heap_bytes).sum::<usize>()
+    }
+
+    #[inline]
+    fn vector(&self, i: u32) -> &[f32] {
+        let start = i as usize * self.dims;
+        &self.vecs[start..start + self.dims]
+    }
+
+    fn prepare_query<'q>(&self, query: &'q [f32]) -> Result<Option<Cow<'q, [f32]>>> {
+        if self.ids.is_empty() {
+            if self.empty_returns_ok {
+                return Ok(None);
+            }
+            return Err(VcalError::EmptyIndex);
+        }
+        if query.len() != self.dims {
+            return Err(VcalError::DimensionMismatch {
+                expected: self.dims,
+                found: query.len(),
+            });
+        }
+        let flushed = if self.flush_subnormals {
+            math::flushed_subnormals(query)
+        } else {
+            None
+        };
+        let query = flushed.map_or(Cow::Borrowed(query), Cow::Owned);
+        if self.normalize_query {
+            return Ok(Some(Cow::Owned(math::l2_normalized(&query))));
+        }
+        Ok(Some(query))
+    }
+
+    fn greedy(&self, mut curr: u32, mut dc: f32, q: &[f32], layer: usize) -> (u32, f32) {
+        loop {
+            let mut improved = false;
+            for &nb in self.layers[layer].neighbors(curr) {
+                let d = self.metric.distance(q, self.vector(nb));
+                if d < dc {
+                    curr = nb;
+                    dc = d;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return (curr, dc);
+            }
+        }
+    }
+
+    /// Layer-0 beam, mirroring `Graph::ef_search_idx` without its options.
+    fn beam(&self, entry: u32, d_entry: f32, q: &[f32], ef: usize) -> Vec<(u32, f32)> {
+        let layer0 = &self.layers[0];
+        let mut visited = HashSet::with_capacity(ef * 2);
+        visited.insert(entry);
+        let mut top: BinaryHeap<(OrderedFloat<f32>, u32)> = BinaryHeap::new();
+        let mut to_visit: BinaryHeap<(Reverse<OrderedFloat<f32>>, u32)> = BinaryHeap::new();
+        top.push((OrderedFloat(d_entry), entry));
+        to_visit.push((Reverse(OrderedFloat(d_entry)), entry));
+
+        while let Some((_, curr)) = to_visit.pop() {
+            let worst = top.peek().map_or(f32::INFINITY, |x| x.0.into_inner());
+            for &nb in layer0.neighbors(curr) {
+                if !visited.insert(nb) {
+                    continue;
+                }
+                let d = self.metric.distance(q, self.vector(nb));
+                if top.len() < ef || d < worst {
+                    to_visit.push((Reverse(OrderedFloat(d)), nb));
+                    top.push((OrderedFloat(d), nb));
+                    if top.len() > ef {
+                        top.pop();
+                    }
+                }
+            }
+        }
+        top.into_iter().map(|(d, i)| (i, d.into_inner())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rand_vecs;
+    use crate::HnswBuilder;
+
+    #[test]
+    fn frozen_search_matches_the_mutable_index() {
+        let data = rand_vecs(800, 16, 170);
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(16)
+            .m(8)
+            .ef_search(48)
+            .seed(170)
+            .track_access(false)
+            .build()
+            .unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        for id in (0..800).step_by(9) {
+            h.delete(id);
+        }
+        let queries = rand_vecs(40, 16, 171);
+        let want: Vec<_> = queries.iter().map(|q| h.search(q, 10).unwrap()).collect();
+
+        let f = h.freeze();
+        assert_eq!(f.len(), 800 - 89);
+        for (q, w) in queries.iter().zip(&want) {
+            assert_eq!(&f.search(q, 10).unwrap(), w);
+        }
+        assert_eq!(f.get_vector(10), Some(&data[10][..]));
+        assert_eq!(f.get_vector(9), None);
+        assert!(matches!(
+            f.search([0.0; 3], 1),
+            Err(VcalError::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn freezing_an_empty_index() {
+        let h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        let f = h.freeze();
+        assert!(f.is_empty());
+        assert!(matches!(f.search([1.0; 4], 1), Err(VcalError::EmptyIndex)));
+    }
+}
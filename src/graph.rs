@@ -2,31 +2,177 @@
 
 use crate::{
     math::Metric,
-    node::{Node, NodeId},
-    rand_level::draw_level,
+    node::{Adj, Links, Node, NodeId, VecSlot},
+    params::DegreeBudget,
+    rand_level::Sampler,
+    IdType,
 };
 
 use crate::node::MAX_LINKS_PER_LVL;
+use ordered_float::OrderedFloat;
+use rand::{rngs::StdRng, SeedableRng};
 use smallvec::SmallVec;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
 use std::collections::HashMap;
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::Arc;
+
+/// How many ef-search expansions run between polls of a cancel flag.
+const CANCEL_POLL: usize = 64;
+
+/// Sink for `Graph::warm` reads (`std::hint::black_box` is past our MSRV).
+static WARM_SINK: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+#[cfg(test)]
+thread_local! {
+    /// Repair passes run on this thread, so tests can count them.
+    pub(crate) static MASS_REPAIRS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+/// Query→node distances memoised for the duration of one query (greedy
+/// descent plus ef-search), so no node's distance is computed twice. Entries
+/// are keyed by NodeId and only valid while node vectors are unchanged.
+#[derive(Default)]
+struct DistanceCache {
+    map: HashMap<NodeId, f32>,
+}
+
+impl DistanceCache {
+    #[inline]
+    fn get<M: Metric, Id>(&mut self, g: &Graph<Id>, nid: NodeId, q: &[f32], metric: &M) -> f32 {
+        *self
+            .map
+            .entry(nid)
+            .or_insert_with(|| metric.distance(&g.nodes[nid].vec, q))
+    }
+
+    /// `get` through `Metric::distance_bounded`. Only exact results (those
+    /// `<= bound`) are memoised; a cut-off one would be wrong under a later,
+    /// looser bound.
+    #[inline]
+    fn get_bounded<M: Metric, Id>(
+        &mut self,
+        g: &Graph<Id>,
+        nid: NodeId,
+        q: &[f32],
+        metric: &M,
+        bound: f32,
+    ) -> f32 {
+        if let Some(&d) = self.map.get(&nid) {
+            return d;
+        }
+        let d = metric.distance_bounded(&g.nodes[nid].vec, q, bound);
+        if d <= bound {
+            self.map.insert(nid, d);
+        }
+        d
+    }
+}
+
+/// Working sets of one beam search, reusable across queries.
+#[derive(Default)]
+struct BeamBufs {
+    visited: std::collections::HashSet<NodeId>,
+    top: BinaryHeap<(OrderedFloat<f32>, NodeId)>,
+    to_visit: BinaryHeap<(Reverse<OrderedFloat<f32>>, NodeId)>,
+}
+
+/// Everything a query allocates (distance memo, beam working sets, the
+/// beam itself), kept between queries so they stop allocating once warm.
+#[derive(Default)]
+pub(crate) struct Scratch {
+    cache: DistanceCache,
+    bufs: BeamBufs,
+    beam: Vec<(NodeId, f32)>,
+}
+
+/// Per-query knobs for the layer-0 beam search.
+#[derive(Clone, Copy, Default)]
+pub struct SearchOpts<'a> {
+    /// Polled between layers and every `CANCEL_POLL` expansions.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Nodes for which this returns `true` are traversed but never returned.
+    pub skip: Option<&'a dyn Fn(NodeId) -> bool>,
+    /// Stop expanding once this many nodes (entry included) have been scored.
+    pub max_visits: Option<usize>,
+    /// Nodes for which this returns `false` are neither scored nor expanded,
+    /// confining the beam to a region of the graph. Entries are exempt.
+    pub within: Option<&'a dyn Fn(NodeId) -> bool>,
+}
 
 /// In-memory HNSW graph.
-pub struct Graph {
-    pub nodes: Vec<Node>,
+pub struct Graph<Id = u64> {
+    pub nodes: Vec<Node<Id>>,
     pub(crate) levels: Vec<Vec<NodeId>>,
     pub(crate) max_level: usize,
     pub(crate) entry: Option<NodeId>,
     /// Mapping from external ids to internal NodeId for fast lookups/touch/delete.
-    pub(crate) by_ext: HashMap<u64, NodeId>,
+    pub(crate) by_ext: HashMap<Id, NodeId>,
     /// Active (non-deleted) vectors count.
     pub(crate) active: usize,
     /// Approximate total bytes across active nodes (guides eviction).
     pub(crate) total_bytes: usize,
+    /// Next insertion sequence number handed to `Node::seq`.
+    pub(crate) next_seq: u64,
+    /// Level sampler RNG (seeded for reproducible builds, OS-seeded otherwise).
+    pub(crate) rng: StdRng,
+    /// Level distribution implementation used by `add`.
+    pub(crate) sampler: Sampler,
+    /// Per-layer degree budget overriding `m`/`m0`; see [`Graph::budget`].
+    pub(crate) degree_budget: Option<DegreeBudget>,
+    /// Pre-mutation copies of touched nodes while a `try_add` is running.
+    journal: Option<HashMap<NodeId, SavedNode>>,
+    /// Live node found by the last search-time scan while `entry` was stale
+    /// (`NO_HINT` if none); revalidated on every use.
+    pub(crate) entry_hint: AtomicUsize,
+    /// Number of full `pick_entry` scans so far.
+    pub(crate) entry_scans: AtomicUsize,
+}
+
+const NO_HINT: usize = usize::MAX;
+
+/// A node's mutable state as it was before a journaled insert touched it.
+struct SavedNode {
+    links: Links,
+    vec: VecSlot,
+    deleted: bool,
+    bytes: usize,
+}
+
+/// Graph-wide state captured before a journaled insert.
+struct Checkpoint<Id> {
+    nodes_len: usize,
+    level_lens: Vec<usize>,
+    /// Full registry copy, only taken for upserts (delete uses `swap_remove`).
+    levels: Option<Vec<Vec<NodeId>>>,
+    max_level: usize,
+    entry: Option<NodeId>,
+    ext_id: Id,
+    old_nid: Option<NodeId>,
+    active: usize,
+    total_bytes: usize,
+    next_seq: u64,
+    rng: StdRng,
 }
 
-impl Graph {
+impl<Id: IdType> Graph<Id> {
     pub fn new() -> Self {
+        Self::with_rng(StdRng::from_os_rng())
+    }
+
+    /// Graph whose level draws are reproducible from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Restart the level draws from `seed`, as `with_seed` would.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
         Self {
             nodes: Vec::new(),
             levels: vec![Vec::new()], // ensure level-0 exists
@@ -35,12 +181,19 @@ impl Graph {
             by_ext: HashMap::new(),
             active: 0,
             total_bytes: 0,
+            next_seq: 0,
+            rng,
+            sampler: Sampler::default(),
+            degree_budget: None,
+            journal: None,
+            entry_hint: AtomicUsize::new(NO_HINT),
+            entry_scans: AtomicUsize::new(0),
         }
     }
 
     /// Safe accessor: neighbors of `nid` on `layer` (empty slice if absent).
     #[inline]
-    fn neighbors(&self, nid: NodeId, layer: usize) -> &[NodeId] {
+    pub(crate) fn neighbors(&self, nid: NodeId, layer: usize) -> &[NodeId] {
         if nid >= self.nodes.len() {
             return &[];
         }
@@ -51,8 +204,53 @@ impl Graph {
         &links[layer]
     }
 
-    /// Insert a vector + external id.
-    pub fn add<M: Metric>(&mut self, vec: Vec<f32>, ext_id: u64, metric: &M, m: usize, ef: usize) {
+    /// Max degree on `layer`: the custom `degree_budget` if one is set
+    /// (floored at 2, like `m`), otherwise `m0` on layer 0 and `m` above.
+    #[inline]
+    pub(crate) fn budget(&self, layer: usize, m: usize, m0: usize) -> usize {
+        match &self.degree_budget {
+            Some(f) => f(layer).max(2),
+            None if layer == 0 => m0,
+            None => m,
+        }
+    }
+
+    /// Insert a vector + external id; returns the level the node was drawn at.
+    ///
+    /// `m` is the degree budget on upper layers, `m0` the budget on layer 0
+    /// (both overridden by `degree_budget`);
+    /// likewise `ef` is the construction beam on upper layers, `ef0` on layer 0.
+    /// Beams below `m` are widened to `m`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add<M: Metric>(
+        &mut self,
+        vec: Arc<[f32]>,
+        ext_id: Id,
+        metric: &M,
+        m: usize,
+        m0: usize,
+        ef: usize,
+        ef0: usize,
+    ) -> usize {
+        let lvl = self.sampler.draw(m, &mut self.rng);
+        self.add_at_level(vec, ext_id, lvl, metric, m, m0, ef, ef0);
+        lvl
+    }
+
+    /// `add` with the node's top layer given instead of drawn; the RNG is
+    /// not advanced.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn add_at_level<M: Metric>(
+        &mut self,
+        vec: Arc<[f32]>,
+        ext_id: Id,
+        lvl: usize,
+        metric: &M,
+        m: usize,
+        m0: usize,
+        ef: usize,
+        ef0: usize,
+    ) {
         debug_assert!(m >= 2, "M must be ≥ 2");
         // If the external id already exists, treat as upsert: delete old node first.
         if let Some(_old) = self.by_ext.get(&ext_id).copied() {
@@ -60,7 +258,6 @@ impl Graph {
             let _ = self.delete(ext_id);
         }
 
-        let lvl = draw_level(m);
         let node_id = self.nodes.len() as NodeId;
 
         // Use the existing tower for wiring; update top only after linking.
@@ -73,23 +270,37 @@ impl Graph {
             }
         }
 
-        let node = Node::new(ext_id, lvl, vec);
+        let mut node = Node::new(ext_id, lvl, vec);
+        node.seq = self.next_seq;
+        self.next_seq += 1;
         self.total_bytes += node.bytes;
         self.active += 1;
         self.nodes.push(node);
         self.by_ext.insert(ext_id, node_id);
 
         let mut entry = old_entry.unwrap_or(node_id);
+        // Node vectors never change while linking, so one cache serves every layer.
+        let mut cache = DistanceCache::default();
 
         if node_id != entry && old_max > lvl {
             for l in (lvl + 1..=old_max).rev() {
-                entry = self.greedy(entry, node_id, l, metric);
+                entry = self.greedy(entry, node_id, l, metric, &mut cache);
             }
         }
 
         for l in (0..=lvl).rev() {
-            let ef_eff = ef.max(m.max(1));
-            let mut neigh = self.ef_search_idx(entry, &self.nodes[node_id].vec, ef_eff, l, metric);
+            let ef_eff = if l == 0 { ef0 } else { ef }.max(m.max(1));
+            let mut neigh = self
+                .ef_search_idx(
+                    &[entry],
+                    &self.nodes[node_id].vec,
+                    ef_eff,
+                    l,
+                    metric,
+                    SearchOpts::default(),
+                    &mut cache,
+                )
+                .unwrap_or_default();
 
             neigh.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
 
@@ -103,7 +314,8 @@ impl Graph {
                 ids.push(entry);
             }
             ids.retain(|&x| x < self.nodes.len() && !self.nodes[x].is_deleted() && x != node_id);
-            self.connect(node_id, &ids, m, l, metric);
+            let budget = self.budget(l, m, m0);
+            self.connect(node_id, &ids, budget, l, metric);
         }
 
         // Ensure entry is set for the very first node.
@@ -121,70 +333,372 @@ impl Graph {
         self.levels[lvl].push(node_id);
     }
 
+    /// `add` as a transaction: if it panics midway (e.g. inside the metric),
+    /// every node it touched, the registries, counters and RNG are restored
+    /// and the panic payload is returned. Only unwinding panics are caught.
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_add<M: Metric>(
+        &mut self,
+        vec: Arc<[f32]>,
+        ext_id: Id,
+        metric: &M,
+        m: usize,
+        m0: usize,
+        ef: usize,
+        ef0: usize,
+    ) -> std::thread::Result<()> {
+        let old_nid = self.by_ext.get(&ext_id).copied();
+        let cp = Checkpoint {
+            nodes_len: self.nodes.len(),
+            level_lens: self.levels.iter().map(Vec::len).collect(),
+            levels: old_nid.map(|_| self.levels.clone()),
+            max_level: self.max_level,
+            entry: self.entry,
+            ext_id,
+            old_nid,
+            active: self.active,
+            total_bytes: self.total_bytes,
+            next_seq: self.next_seq,
+            rng: self.rng.clone(),
+        };
+        self.journal = Some(HashMap::new());
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            self.add(vec, ext_id, metric, m, m0, ef, ef0);
+        }));
+        let saved = self.journal.take().unwrap_or_default();
+        if res.is_err() {
+            self.rollback(cp, saved);
+        }
+        res
+    }
+
+    fn rollback(&mut self, cp: Checkpoint<Id>, saved: HashMap<NodeId, SavedNode>) {
+        self.nodes.truncate(cp.nodes_len);
+        for (nid, sn) in saved {
+            if let Some(n) = self.nodes.get_mut(nid) {
+                n.links = sn.links;
+                n.vec = sn.vec;
+                n.deleted
+                    .store(sn.deleted, std::sync::atomic::Ordering::Relaxed);
+                n.bytes = sn.bytes;
+            }
+        }
+        match cp.levels {
+            Some(levels) => self.levels = levels,
+            None => {
+                self.levels.truncate(cp.level_lens.len());
+                for (lvl, &len) in self.levels.iter_mut().zip(&cp.level_lens) {
+                    lvl.truncate(len);
+                }
+            }
+        }
+        self.by_ext.remove(&cp.ext_id);
+        if let Some(old) = cp.old_nid {
+            self.by_ext.insert(cp.ext_id, old);
+        }
+        self.max_level = cp.max_level;
+        self.entry = cp.entry;
+        self.active = cp.active;
+        self.total_bytes = cp.total_bytes;
+        self.next_seq = cp.next_seq;
+        self.rng = cp.rng;
+    }
+
+    /// Record `nid`'s pre-mutation state once per journaled insert.
+    #[inline]
+    fn journal_save(&mut self, nid: NodeId) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        if let Some(n) = self.nodes.get(nid) {
+            journal.entry(nid).or_insert_with(|| SavedNode {
+                links: n.links.clone(),
+                vec: n.vec.clone(),
+                deleted: n.is_deleted(),
+                bytes: n.bytes,
+            });
+        }
+    }
+
     /// Public k-NN search (returns `(ext_id, dist)`).
+    ///
+    /// With `recent_ties`, hits at exactly equal distance are ordered by
+    /// descending insertion sequence (freshest first).
     pub fn knn<M: Metric>(
         &self,
         query: &[f32],
         k: usize,
         metric: &M,
         ef: usize,
-    ) -> Vec<(u64, f32)> {
+        recent_ties: bool,
+    ) -> Vec<(Id, f32)> {
         if self.nodes.is_empty() || k == 0 {
             return Vec::new();
         }
 
+        let cand = self.candidates(query, ef.max(k), metric);
+        self.rank(cand, k, recent_ties)
+    }
+
+    /// Sort a raw beam by distance (freshest first on ties under
+    /// `recent_ties`), keep `k`, and map to `(ext_id, dist)`.
+    pub fn rank(&self, cand: Vec<(NodeId, f32)>, k: usize, recent_ties: bool) -> Vec<(Id, f32)> {
+        let mut out = Vec::with_capacity(k.min(cand.len()));
+        self.rank_into(cand, k, recent_ties, &mut out);
+        out
+    }
+
+    /// `rank`, appending to a caller-owned buffer.
+    pub fn rank_into(
+        &self,
+        mut cand: Vec<(NodeId, f32)>,
+        k: usize,
+        recent_ties: bool,
+        out: &mut Vec<(Id, f32)>,
+    ) {
+        self.sort_beam(&mut cand, recent_ties);
+        cand.truncate(k);
+        out.extend(
+            cand.into_iter()
+                .map(|(nid, dist)| (self.nodes[nid].ext_id, dist)),
+        );
+    }
+
+    /// `rank(cand, 1, recent_ties)` without sorting or allocating: the
+    /// first minimum under the same ordering, so ties resolve identically.
+    pub(crate) fn best(&self, cand: &[(NodeId, f32)], recent_ties: bool) -> Option<(Id, f32)> {
+        let best = if recent_ties {
+            cand.iter().min_by(|a, b| {
+                a.1.partial_cmp(&b.1)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| self.nodes[b.0].seq.cmp(&self.nodes[a.0].seq))
+            })
+        } else {
+            cand.iter()
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+        };
+        best.map(|&(nid, dist)| (self.nodes[nid].ext_id, dist))
+    }
+
+    /// Nearest live node to `query`: the usual upper-layer descent, then a
+    /// layer-0 beam of width `ef` that keeps only what k = 1 needs. The
+    /// beam's admission bound is a heap of bare distances, the answer a
+    /// single running minimum (ties broken as in `best`), distances are not
+    /// memoised, and the walk stops once the closest unexpanded candidate
+    /// is farther than the `ef`-th best distance seen.
+    pub(crate) fn nearest<M: Metric>(
+        &self,
+        query: &[f32],
+        ef: usize,
+        metric: &M,
+        recent_ties: bool,
+    ) -> Option<(NodeId, f32)> {
+        let entry = self.layer0_entry(query, metric)?;
+        let ef = ef.max(1);
+        let better = |(nid, d): (NodeId, f32), (bid, bd): (NodeId, f32)| {
+            d < bd || (recent_ties && d == bd && self.nodes[nid].seq > self.nodes[bid].seq)
+        };
+
+        let d0 = metric.distance(&self.nodes[entry].vec, query);
+        let mut best = (entry, d0);
+        let mut visited = std::collections::HashSet::with_capacity(ef * 2);
+        visited.insert(entry);
+        let mut bound: BinaryHeap<OrderedFloat<f32>> = BinaryHeap::with_capacity(ef + 1);
+        bound.push(OrderedFloat(d0));
+        let mut to_visit = BinaryHeap::new();
+        to_visit.push((Reverse(OrderedFloat(d0)), entry));
+
+        while let Some((Reverse(OrderedFloat(dc)), curr)) = to_visit.pop() {
+            let worst = bound.peek().map_or(f32::INFINITY, |w| w.into_inner());
+            if bound.len() >= ef && dc > worst {
+                break;
+            }
+            for &nb in self.neighbors(curr, 0) {
+                if !self.is_valid_nid(nb) || !visited.insert(nb) {
+                    continue;
+                }
+                let full = bound.len() >= ef;
+                let worst = bound.peek().map_or(f32::INFINITY, |w| w.into_inner());
+                let d = if full {
+                    metric.distance_bounded(&self.nodes[nb].vec, query, worst)
+                } else {
+                    metric.distance(&self.nodes[nb].vec, query)
+                };
+                if full && d >= worst {
+                    continue;
+                }
+                to_visit.push((Reverse(OrderedFloat(d)), nb));
+                bound.push(OrderedFloat(d));
+                if bound.len() > ef {
+                    bound.pop();
+                }
+                if better((nb, d), best) {
+                    best = (nb, d);
+                }
+            }
+        }
+        Some(best)
+    }
+
+    fn sort_beam(&self, cand: &mut [(NodeId, f32)], recent_ties: bool) {
+        if recent_ties {
+            cand.sort_by(|a, b| {
+                a.1.partial_cmp(&b.1)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| self.nodes[b.0].seq.cmp(&self.nodes[a.0].seq))
+            });
+        } else {
+            cand.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        }
+    }
+
+    /// `knn` appending to `out` and running entirely in `scratch`, so a
+    /// warm scratch and an `out` with room for `k` make it allocation-free.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn knn_scratch<M: Metric>(
+        &self,
+        query: &[f32],
+        k: usize,
+        metric: &M,
+        ef: usize,
+        recent_ties: bool,
+        scratch: &mut Scratch,
+        out: &mut Vec<(Id, f32)>,
+    ) {
+        if k == 0 {
+            return;
+        }
+        let mut ep = match self.entry {
+            Some(e) if self.is_valid_nid(e) => e,
+            _ => match self.search_entry() {
+                Some(e) => e,
+                None => return,
+            },
+        };
+        scratch.cache.map.clear();
+        for l in (1..=self.max_level).rev() {
+            ep = self.greedy_idx(ep, query, l, metric, &mut scratch.cache);
+        }
+        self.beam_into(
+            &[ep],
+            query,
+            ef.max(k),
+            0,
+            metric,
+            SearchOpts::default(),
+            &mut scratch.cache,
+            &mut scratch.bufs,
+            &mut scratch.beam,
+        );
+        self.sort_beam(&mut scratch.beam, recent_ties);
+        out.extend(
+            scratch.beam[..k.min(scratch.beam.len())]
+                .iter()
+                .map(|&(nid, dist)| (self.nodes[nid].ext_id, dist)),
+        );
+    }
+
+    /// Greedy descent to layer 0 followed by an ef-search there; returns the
+    /// unsorted `(NodeId, distance)` beam.
+    pub fn candidates<M: Metric>(
+        &self,
+        query: &[f32],
+        ef: usize,
+        metric: &M,
+    ) -> Vec<(NodeId, f32)> {
+        self.candidates_with(query, ef, metric, SearchOpts::default())
+            .unwrap_or_default()
+    }
+
+    /// Like `candidates`, honouring `opts` (cancel flag, skipped nodes);
+    /// `None` means it was cancelled.
+    pub fn candidates_with<M: Metric>(
+        &self,
+        query: &[f32],
+        ef: usize,
+        metric: &M,
+        opts: SearchOpts<'_>,
+    ) -> Option<Vec<(NodeId, f32)>> {
+        let cancelled = || {
+            opts.cancel
+                .map_or(false, |c| c.load(std::sync::atomic::Ordering::Relaxed))
+        };
+
         // Don’t trust self.entry blindly.
         let mut ep = match self.entry {
             Some(e) if self.is_valid_nid(e) => e,
-            _ => match self.pick_entry() {
+            _ => match self.search_entry() {
                 Some(e) => e,
-                None => return Vec::new(),
+                None => return Some(Vec::new()),
             },
         };
 
+        let mut cache = DistanceCache::default();
         for l in (1..=self.max_level).rev() {
-            ep = self.greedy_idx(ep, query, l, metric);
+            if cancelled() {
+                return None;
+            }
+            ep = self.greedy_idx(ep, query, l, metric, &mut cache);
         }
 
-        let mut cand = self.ef_search_idx(ep, query, ef.max(k), 0, metric);
-        cand.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-        cand.truncate(k);
-        cand.into_iter()
-            .map(|(nid, dist)| (self.nodes[nid].ext_id, dist))
-            .collect()
+        self.ef_search_idx(&[ep], query, ef, 0, metric, opts, &mut cache)
+    }
+
+    /// The layer-0 node the greedy upper-layer descent reaches for `query`,
+    /// i.e. where `candidates` would start its beam. `None` if no node is live.
+    pub fn layer0_entry<M: Metric>(&self, query: &[f32], metric: &M) -> Option<NodeId> {
+        let mut ep = match self.entry {
+            Some(e) if self.is_valid_nid(e) => e,
+            _ => self.search_entry()?,
+        };
+        let mut cache = DistanceCache::default();
+        for l in (1..=self.max_level).rev() {
+            ep = self.greedy_idx(ep, query, l, metric, &mut cache);
+        }
+        Some(ep)
+    }
+
+    /// Layer-0 beam seeded from several `entries` at once, skipping the
+    /// upper-layer descent. Dead entries are dropped; with none left the
+    /// result is empty. `None` means it was cancelled.
+    pub fn candidates_from<M: Metric>(
+        &self,
+        entries: &[NodeId],
+        query: &[f32],
+        ef: usize,
+        metric: &M,
+        opts: SearchOpts<'_>,
+    ) -> Option<Vec<(NodeId, f32)>> {
+        let mut cache = DistanceCache::default();
+        self.ef_search_idx(entries, query, ef, 0, metric, opts, &mut cache)
     }
 
     /* ---------------- internal helpers ----------------------------------- */
 
+    /// Fallback entry for searches when `entry` is stale. Searches can't
+    /// repair `entry` through `&self`, so the result of one `pick_entry` scan
+    /// is kept in `entry_hint` and reused while that node stays live; without
+    /// it every query after a mass delete would rescan the level registry.
+    pub(crate) fn search_entry(&self) -> Option<NodeId> {
+        let hint = self.entry_hint.load(std::sync::atomic::Ordering::Relaxed);
+        if hint != NO_HINT && self.is_valid_nid(hint) {
+            return Some(hint);
+        }
+        let e = self.pick_entry()?;
+        self.entry_hint
+            .store(e, std::sync::atomic::Ordering::Relaxed);
+        Some(e)
+    }
+
     fn greedy<M: Metric>(
         &self,
-        mut curr: NodeId,
+        curr: NodeId,
         target: NodeId,
         layer: usize,
         metric: &M,
+        cache: &mut DistanceCache,
     ) -> NodeId {
-        if !self.is_valid_nid(curr) || self.neighbors(curr, layer).is_empty() {
-            return curr;
-        }
-        let tv = &self.nodes[target].vec;
-        loop {
-            let mut improved = false;
-            for &nb in self.neighbors(curr, layer) {
-                if !self.is_valid_nid(nb) {
-                    continue;
-                }
-                if metric.distance(&self.nodes[nb].vec, tv)
-                    < metric.distance(&self.nodes[curr].vec, tv)
-                {
-                    curr = nb;
-                    improved = true;
-                }
-            }
-            if !improved {
-                break;
-            }
-        }
-        curr
+        self.greedy_idx(curr, &self.nodes[target].vec, layer, metric, cache)
     }
 
     fn greedy_idx<M: Metric>(
@@ -193,20 +707,22 @@ impl Graph {
         q: &[f32],
         layer: usize,
         metric: &M,
+        cache: &mut DistanceCache,
     ) -> NodeId {
         if !self.is_valid_nid(curr) || self.neighbors(curr, layer).is_empty() {
             return curr;
         }
+        let mut dc = cache.get(self, curr, q, metric);
         loop {
             let mut improved = false;
             for &nb in self.neighbors(curr, layer) {
                 if !self.is_valid_nid(nb) {
                     continue;
                 }
-                if metric.distance(&self.nodes[nb].vec, q)
-                    < metric.distance(&self.nodes[curr].vec, q)
-                {
+                let d = cache.get(self, nb, q, metric);
+                if d < dc {
                     curr = nb;
+                    dc = d;
                     improved = true;
                 }
             }
@@ -217,34 +733,86 @@ impl Graph {
         curr
     }
 
-    /// ef-search core — returns Vec of (NodeId, distance).
+    /// ef-search core — returns Vec of (NodeId, distance), or `None` if
+    /// `opts.cancel` was raised. Distances go through the caller's `cache`.
+    #[allow(clippy::too_many_arguments)]
     fn ef_search_idx<M: Metric>(
         &self,
-        entry: NodeId,
+        entries: &[NodeId],
         query: &[f32],
         ef: usize,
         layer: usize,
         metric: &M,
-    ) -> Vec<(NodeId, f32)> {
-        // Bail out early if entry is invalid/deleted.
-        if !self.is_valid_nid(entry) {
-            return Vec::new();
+        opts: SearchOpts<'_>,
+        cache: &mut DistanceCache,
+    ) -> Option<Vec<(NodeId, f32)>> {
+        let mut bufs = BeamBufs {
+            visited: std::collections::HashSet::with_capacity(ef * 2),
+            ..BeamBufs::default()
+        };
+        let mut out = Vec::new();
+        if self.beam_into(
+            entries, query, ef, layer, metric, opts, cache, &mut bufs, &mut out,
+        ) {
+            Some(out)
+        } else {
+            None
         }
+    }
 
-        let mut visited = std::collections::HashSet::with_capacity(ef * 2);
-        use ordered_float::OrderedFloat;
-        use std::cmp::Reverse;
-        let mut top: std::collections::BinaryHeap<(OrderedFloat<f32>, NodeId)> =
-            std::collections::BinaryHeap::new();
-        let mut to_visit: std::collections::BinaryHeap<(Reverse<OrderedFloat<f32>>, NodeId)> =
-            std::collections::BinaryHeap::new();
-
-        let d0 = metric.distance(&self.nodes[entry].vec, query);
-        visited.insert(entry);
-        top.push((OrderedFloat(d0), entry));
-        to_visit.push((Reverse(OrderedFloat(d0)), entry));
+    /// `ef_search_idx` core on caller-owned buffers: clears `bufs` and
+    /// `out`, then fills `out` with the unsorted beam. Returns `false` if
+    /// `opts.cancel` was raised (`out` is then left empty).
+    #[allow(clippy::too_many_arguments)]
+    fn beam_into<M: Metric>(
+        &self,
+        entries: &[NodeId],
+        query: &[f32],
+        ef: usize,
+        layer: usize,
+        metric: &M,
+        opts: SearchOpts<'_>,
+        cache: &mut DistanceCache,
+        bufs: &mut BeamBufs,
+        out: &mut Vec<(NodeId, f32)>,
+    ) -> bool {
+        let skip = |nid: NodeId| opts.skip.map_or(false, |f| f(nid));
+        let within = |nid: NodeId| opts.within.map_or(true, |f| f(nid));
+
+        out.clear();
+        let BeamBufs {
+            visited,
+            top,
+            to_visit,
+        } = bufs;
+        visited.clear();
+        top.clear();
+        to_visit.clear();
+
+        // Invalid/deleted entries are dropped; with none left there is nothing to search.
+        for &entry in entries {
+            if !self.is_valid_nid(entry) || !visited.insert(entry) {
+                continue;
+            }
+            let d0 = cache.get(self, entry, query, metric);
+            if !skip(entry) {
+                top.push((OrderedFloat(d0), entry));
+                if top.len() > ef {
+                    top.pop();
+                }
+            }
+            to_visit.push((Reverse(OrderedFloat(d0)), entry));
+        }
 
-        while let Some((Reverse(_), curr)) = to_visit.pop() {
+        let max_visits = opts.max_visits.unwrap_or(usize::MAX);
+        let mut expanded = 0usize;
+        'beam: while let Some((Reverse(_), curr)) = to_visit.pop() {
+            expanded += 1;
+            if let Some(c) = opts.cancel {
+                if expanded % CANCEL_POLL == 0 && c.load(std::sync::atomic::Ordering::Relaxed) {
+                    return false;
+                }
+            }
             let neighs = self.neighbors(curr, layer);
             if neighs.is_empty() {
                 continue;
@@ -258,12 +826,26 @@ impl Graph {
                 if !self.is_valid_nid(nb) {
                     continue;
                 } // <==== extra guard
-                if !visited.insert(nb) {
+                if visited.len() >= max_visits {
+                    break 'beam;
+                }
+                if !visited.insert(nb) || !within(nb) {
                     continue;
                 }
-                let d = metric.distance(&self.nodes[nb].vec, query);
+                // With a full beam only `d < worst` can matter, so the metric
+                // may stop as soon as it is past `worst`.
+                let d = if top.len() < ef {
+                    cache.get(self, nb, query, metric)
+                } else {
+                    cache.get_bounded(self, nb, query, metric, worst)
+                };
                 if top.len() < ef || d < worst {
+                    // Skipped nodes still route the search, they just never
+                    // take a result slot.
                     to_visit.push((Reverse(OrderedFloat(d)), nb));
+                    if skip(nb) {
+                        continue;
+                    }
                     top.push((OrderedFloat(d), nb));
                     if top.len() > ef {
                         top.pop();
@@ -271,16 +853,66 @@ impl Graph {
                 }
             }
         }
-        top.into_iter()
-            .map(|(od, nid)| (nid, od.into_inner()))
-            .collect()
+        out.extend(top.drain().map(|(od, nid)| (nid, od.into_inner())));
+        true
+    }
+
+    /// Copy every live vector into one contiguous arena (a new one every
+    /// `u32::MAX` floats), in NodeId order, and point the nodes at it;
+    /// returns the total arena length in floats. Vectors of later inserts
+    /// and updates get their own allocation, and arena space of deleted or
+    /// replaced vectors stays allocated, until the next pack.
+    pub fn pack_vectors(&mut self) -> usize {
+        let len = |n: &Node<Id>| if n.is_deleted() { 0 } else { n.vec.len() };
+        let (mut first, mut packed) = (0, 0);
+        while first < self.nodes.len() {
+            let (mut end, mut total) = (first, 0);
+            while end < self.nodes.len() && total + len(&self.nodes[end]) <= u32::MAX as usize {
+                total += len(&self.nodes[end]);
+                end += 1;
+            }
+            self.pack_run(first..end, total);
+            packed += total;
+            first = end;
+        }
+        packed
+    }
+
+    /// Pack the live vectors of `nodes` (`total` floats) into one arena.
+    fn pack_run(&mut self, nodes: std::ops::Range<NodeId>, total: usize) {
+        let run = &mut self.nodes[nodes];
+        // `collect` on an exact-size iterator allocates the `Arc` once;
+        // fill it in place while this is its only handle.
+        let mut arena: Arc<[f32]> = std::iter::repeat(0.0).take(total).collect();
+        let buf = Arc::get_mut(&mut arena).expect("fresh arena is unshared");
+        let mut at = 0;
+        for n in run.iter().filter(|n| !n.is_deleted()) {
+            buf[at..at + n.vec.len()].copy_from_slice(&n.vec);
+            at += n.vec.len();
+        }
+        let mut at = 0;
+        for n in run.iter_mut().filter(|n| !n.is_deleted()) {
+            let len = n.vec.len();
+            n.vec = VecSlot::in_arena(&arena, at, len);
+            at += len;
+        }
+    }
+
+    /// Pre-size node storage, the id map and the layer-0 registry.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+        self.by_ext.reserve(additional);
+        if let Some(l0) = self.levels.first_mut() {
+            l0.reserve(additional);
+        }
     }
 
     /// Check whether an ext_id exists.
-    pub fn contains_ext(&self, ext_id: u64) -> bool {
+    pub fn contains_ext(&self, ext_id: Id) -> bool {
         self.by_ext.contains_key(&ext_id)
     }
 
+    /// Link `nid` to `neigh` on `layer`; `m` is the degree budget for that layer.
     fn connect<M: Metric>(
         &mut self,
         nid: NodeId,
@@ -298,9 +930,14 @@ impl Graph {
             if selected.len() >= m {
                 break;
             }
+            // Ties keep the candidate: with a strict `<`, an exact duplicate
+            // of `nid` would shadow every other candidate and leave the two
+            // linked only to each other. Without duplicates, ties are exact
+            // float equalities and recall is unchanged; see
+            // `benches/recall_duplicates.rs` for both cases.
             let ok = selected.iter().all(|&s| {
                 metric.distance(&self.nodes[c].vec, &self.nodes[nid].vec)
-                    < metric.distance(&self.nodes[c].vec, &self.nodes[s].vec)
+                    <= metric.distance(&self.nodes[c].vec, &self.nodes[s].vec)
             });
             if ok {
                 selected.push(c);
@@ -314,62 +951,82 @@ impl Graph {
                 selected_valid.push(s);
             }
         }
+        self.journal_save(nid);
+        for &s in &selected_valid {
+            self.journal_save(s);
+        }
 
         // Ensure layer exists on nid and each valid neighbor
         if layer >= self.nodes[nid].links.len() {
-            self.nodes[nid].links.resize(layer + 1, Vec::new());
+            self.nodes[nid].links.resize(layer + 1, Adj::new());
         }
         for &s in &selected_valid {
             if layer >= self.nodes[s].links.len() {
-                self.nodes[s].links.resize(layer + 1, Vec::new());
+                self.nodes[s].links.resize(layer + 1, Adj::new());
             }
         }
 
-        // Byte accounting
-        let mut bytes_delta: isize = 0;
-
         // Forward (nid -> selected_valid)
-        let nid_before = self.nodes[nid].recompute_bytes();
         {
+            // Lists are short (<= 2m) and we only ever append, so a linear
+            // `contains` keeps them duplicate-free without sort + dedup.
             let mut adj = std::mem::take(&mut self.nodes[nid].links[layer]);
-            adj.extend(selected_valid.iter().copied());
-            adj.retain(|&x| x != nid && x < self.nodes.len() && !self.nodes[x].is_deleted());
-            adj.sort_unstable();
-            adj.dedup();
+            adj.retain(|x| *x != nid && *x < self.nodes.len() && !self.nodes[*x].is_deleted());
+            for &s in &selected_valid {
+                if !adj.contains(&s) {
+                    adj.push(s);
+                }
+            }
             self.nodes[nid].links[layer] = adj;
         }
-        let nid_after = self.nodes[nid].recompute_bytes();
-        bytes_delta += (nid_after as isize) - (nid_before as isize);
 
         // Back-edges (selected_valid -> nid)
         for &s in &selected_valid {
-            let nb_before = self.nodes[s].recompute_bytes();
             {
                 let mut adj = std::mem::take(&mut self.nodes[s].links[layer]);
-                adj.push(nid);
-                adj.retain(|&x| x != s && x < self.nodes.len() && !self.nodes[x].is_deleted());
-                adj.sort_unstable();
-                adj.dedup();
+                adj.retain(|x| *x != s && *x < self.nodes.len() && !self.nodes[*x].is_deleted());
+                if !adj.contains(&nid) {
+                    adj.push(nid);
+                }
                 self.nodes[s].links[layer] = adj;
             }
             self.prune_degree_hnsw(s, layer, m, metric);
-            let nb_after = self.nodes[s].recompute_bytes();
-            bytes_delta += (nb_after as isize) - (nb_before as isize);
+            self.refresh_bytes(s);
         }
 
         // Prune nid as well
         self.prune_degree_hnsw(nid, layer, m, metric);
+        self.refresh_bytes(nid);
+    }
 
-        // Apply accumulated delta
-        if bytes_delta >= 0 {
-            self.total_bytes = self.total_bytes.saturating_add(bytes_delta as usize);
-        } else {
-            self.total_bytes = self.total_bytes.saturating_sub((-bytes_delta) as usize);
+    /// Re-measure `nid` after its vector or neighbour lists changed and move
+    /// `total_bytes` by the difference from its cached `bytes`. Every
+    /// mutation goes through here, so cached values stay exact and
+    /// `stats()` stays O(1).
+    #[inline]
+    fn refresh_bytes(&mut self, nid: NodeId) {
+        let before = self.nodes[nid].bytes;
+        let after = self.nodes[nid].recompute_bytes();
+        self.total_bytes = (self.total_bytes + after).saturating_sub(before);
+    }
+
+    /// Whether `total_bytes` and every cached node `bytes` match a full
+    /// recount. O(n); for debug assertions and tests.
+    pub(crate) fn bytes_consistent(&self) -> bool {
+        let mut total = 0usize;
+        for n in &self.nodes {
+            let b = n.compute_bytes();
+            if b != n.bytes {
+                return false;
+            }
+            total += b;
         }
+        total == self.total_bytes
     }
 
     /// Greedy HNSW degree pruning: keep up to m neighbors on `layer`.
     fn prune_degree_hnsw<M: Metric>(&mut self, nid: NodeId, layer: usize, m: usize, metric: &M) {
+        self.journal_save(nid);
         let adj_taken = std::mem::take(&mut self.nodes[nid].links[layer]);
         if adj_taken.len() <= m {
             self.nodes[nid].links[layer] = adj_taken;
@@ -377,23 +1034,27 @@ impl Graph {
         }
 
         // Rank by distance to nid (ascending)
-        let mut cand: Vec<(NodeId, f32)> = adj_taken
+        let ids: Vec<NodeId> = adj_taken
             .iter()
             .copied()
             .filter(|&c| c < self.nodes.len() && !self.nodes[c].is_deleted() && c != nid)
-            .map(|c| (c, metric.distance(&self.nodes[c].vec, &self.nodes[nid].vec)))
             .collect();
+        let vecs: Vec<&[f32]> = ids.iter().map(|&c| &self.nodes[c].vec[..]).collect();
+        let mut dists = vec![0.0_f32; ids.len()];
+        metric.distance_batch(&self.nodes[nid].vec, &vecs, &mut dists);
+        let mut cand: Vec<(NodeId, f32)> = ids.into_iter().zip(dists).collect();
         cand.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
 
         // Greedy keep ≤ m
-        let mut keep: Vec<NodeId> = Vec::with_capacity(m);
+        let mut keep = Adj::with_capacity(m);
         for (c, _) in cand {
             if keep.len() >= m {
                 break;
             }
+            // `<=` for the same reason as in `connect`.
             let ok = keep.iter().all(|&s| {
                 metric.distance(&self.nodes[c].vec, &self.nodes[nid].vec)
-                    < metric.distance(&self.nodes[c].vec, &self.nodes[s].vec)
+                    <= metric.distance(&self.nodes[c].vec, &self.nodes[s].vec)
             });
             if ok {
                 keep.push(c);
@@ -404,8 +1065,128 @@ impl Graph {
     }
 }
 
-impl Graph {
-    #[allow(dead_code)]
+/// Outcome of [`Graph::finalize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FinalizeReport {
+    /// Dangling/duplicate edges dropped plus edges pruned from over-degree nodes.
+    pub edges_pruned: usize,
+    /// Live nodes with no incoming layer-0 edge that were re-linked.
+    pub orphans_fixed: usize,
+}
+
+/// Outcome of [`Graph::audit`]: damage found, nothing changed.
+/// `sanitize` would drop `edges_dropped()` edges and fix
+/// `layerless_nodes` nodes; the remaining counts are repaired by
+/// `finalize` or not at all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Edges to a missing or deleted node.
+    pub dangling_edges: usize,
+    /// Edges from a node to itself.
+    pub self_edges: usize,
+    /// Repeated entries within one neighbour list.
+    pub duplicate_edges: usize,
+    /// Live nodes without even a layer-0 list.
+    pub layerless_nodes: usize,
+    /// Edges on layer `l` to a node whose tower stops below `l`.
+    pub cross_layer_edges: usize,
+    /// Live nodes with no incoming layer-0 edge (only counted when there
+    /// are at least two live nodes).
+    pub orphans: usize,
+    /// Live nodes whose ext_id repeats an earlier live node's.
+    pub duplicate_ext_ids: usize,
+}
+
+impl AuditReport {
+    /// Edges `sanitize` would remove: dangling + self + duplicate.
+    pub fn edges_dropped(&self) -> usize {
+        self.dangling_edges + self.self_edges + self.duplicate_edges
+    }
+
+    /// True when no problem of any kind was found.
+    pub fn is_clean(&self) -> bool {
+        *self == AuditReport::default()
+    }
+}
+
+impl<Id: IdType> Graph<Id> {
+    /// Number of connected components among live nodes, treating layer-0
+    /// edges as undirected and ignoring edges to tombstones or out-of-range
+    /// ids. Union-find, O(n·m). 0 for an empty graph.
+    pub fn connected_components(&self) -> usize {
+        fn find(parent: &mut [NodeId], mut x: NodeId) -> NodeId {
+            while parent[x] != x {
+                parent[x] = parent[parent[x]];
+                x = parent[x];
+            }
+            x
+        }
+
+        let n = self.nodes.len();
+        let mut parent: Vec<NodeId> = (0..n).collect();
+        let mut components = self.active;
+        for nid in 0..n {
+            if self.nodes[nid].is_deleted() {
+                continue;
+            }
+            for &nb in self.neighbors(nid, 0) {
+                if nb >= n || self.nodes[nb].is_deleted() {
+                    continue;
+                }
+                let (a, b) = (find(&mut parent, nid), find(&mut parent, nb));
+                if a != b {
+                    parent[a] = b;
+                    components -= 1;
+                }
+            }
+        }
+        components
+    }
+
+    /// Read-only health check: counts what `sanitize` and `finalize` would
+    /// repair, plus problems neither fixes. O(n·m).
+    pub fn audit(&self) -> AuditReport {
+        let mut r = AuditReport::default();
+        let nlen = self.nodes.len();
+        let mut has_incoming = vec![false; nlen];
+        let mut seen = std::collections::HashSet::with_capacity(self.active);
+
+        for (nid, n) in self.nodes.iter().enumerate() {
+            if !n.is_deleted() {
+                if !seen.insert(n.ext_id) {
+                    r.duplicate_ext_ids += 1;
+                }
+                if n.links.is_empty() {
+                    r.layerless_nodes += 1;
+                }
+            }
+            for (l, adj) in n.links.iter().enumerate() {
+                for (i, &x) in adj.iter().enumerate() {
+                    if x >= nlen || self.nodes[x].is_deleted() {
+                        r.dangling_edges += 1;
+                    } else if x == nid {
+                        r.self_edges += 1;
+                    } else if adj[..i].contains(&x) {
+                        r.duplicate_edges += 1;
+                    } else {
+                        if self.nodes[x].links.len() <= l {
+                            r.cross_layer_edges += 1;
+                        }
+                        if l == 0 && !n.is_deleted() {
+                            has_incoming[x] = true;
+                        }
+                    }
+                }
+            }
+        }
+        if self.active > 1 {
+            r.orphans = (0..nlen)
+                .filter(|&nid| !self.nodes[nid].is_deleted() && !has_incoming[nid])
+                .count();
+        }
+        r
+    }
+
     pub fn sanitize(&mut self) -> (usize, usize) {
         let mut edges_dropped = 0usize;
         let mut nodes_fixed = 0usize;
@@ -416,7 +1197,7 @@ impl Graph {
 
         for (nid, n) in self.nodes.iter_mut().enumerate() {
             if n.links.is_empty() {
-                n.links.resize(1, Vec::new());
+                n.links.resize(1, Adj::new());
                 nodes_fixed += 1;
             }
             for l in 0..n.links.len() {
@@ -424,7 +1205,7 @@ impl Graph {
                 let before = adj.len();
 
                 // rebuild instead of retain() that closes over &self.nodes
-                let mut new_adj = Vec::with_capacity(adj.len());
+                let mut new_adj = Adj::with_capacity(adj.len());
                 for &x in adj.iter() {
                     if x < nlen && x != nid && !deleted[x] {
                         new_adj.push(x);
@@ -469,9 +1250,220 @@ impl Graph {
 
         (edges_dropped, nodes_fixed)
     }
+
+    /// Post-bulk-load maintenance: sanitize, enforce per-layer degree budgets,
+    /// re-link layer-0 orphans, and recompute counters and the entry point.
+    pub fn finalize<M: Metric>(
+        &mut self,
+        metric: &M,
+        m: usize,
+        m0: usize,
+        ef: usize,
+    ) -> FinalizeReport {
+        let mut report = FinalizeReport {
+            edges_pruned: self.sanitize().0,
+            orphans_fixed: 0,
+        };
+
+        // Enforce degree budgets.
+        for nid in 0..self.nodes.len() {
+            if self.nodes[nid].is_deleted() {
+                continue;
+            }
+            for l in 0..self.nodes[nid].links.len() {
+                let budget = self.budget(l, m, m0);
+                let before = self.nodes[nid].links[l].len();
+                if before > budget {
+                    self.prune_degree_hnsw(nid, l, budget, metric);
+                    report.edges_pruned += before - self.nodes[nid].links[l].len();
+                }
+            }
+        }
+
+        // Re-link nodes no live node points to on layer 0 (unreachable by
+        // search; tombstones keep their out-links but are never expanded).
+        if self.active > 1 {
+            let mut indeg = vec![0usize; self.nodes.len()];
+            for n in self.nodes.iter().filter(|n| !n.is_deleted()) {
+                if let Some(adj) = n.links.first() {
+                    for &x in adj {
+                        indeg[x] += 1;
+                    }
+                }
+            }
+            let orphans: Vec<NodeId> = indeg
+                .iter()
+                .enumerate()
+                .filter(|&(nid, &d)| d == 0 && self.is_valid_nid(nid) && self.entry != Some(nid))
+                .map(|(nid, _)| nid)
+                .collect();
+            for nid in orphans {
+                if self.relink_orphan(nid, metric, m, m0, ef) {
+                    report.orphans_fixed += 1;
+                }
+            }
+        }
+
+        self.total_bytes = self.nodes.iter_mut().map(|n| n.recompute_bytes()).sum();
+        self.entry = self.pick_entry();
+        report
+    }
+
+    /// Drop every edge of live node `nid` (both directions, on each of its
+    /// layers) and re-run neighbour selection from its current vector, as
+    /// `add` would. The node keeps its NodeId and level. Returns false if
+    /// `nid` is not live.
+    #[allow(clippy::too_many_arguments)]
+    pub fn relink<M: Metric>(
+        &mut self,
+        nid: NodeId,
+        metric: &M,
+        m: usize,
+        m0: usize,
+        ef: usize,
+        ef0: usize,
+    ) -> bool {
+        if !self.is_valid_nid(nid) {
+            return false;
+        }
+        let lvl = self.nodes[nid].links.len() - 1;
+
+        // Strip old edges, remembering one ex-neighbour per layer as a
+        // fallback entry in case `nid` is the graph's entry point.
+        let mut fallback = vec![None; lvl + 1];
+        for (l, slot) in fallback.iter_mut().enumerate() {
+            let neigh = std::mem::take(&mut self.nodes[nid].links[l]);
+            for &nb in &neigh {
+                if !self.is_valid_nid(nb) || l >= self.nodes[nb].links.len() {
+                    continue;
+                }
+                slot.get_or_insert(nb);
+                let adj = &mut self.nodes[nb].links[l];
+                if let Some(pos) = adj.iter().position(|&x| x == nid) {
+                    adj.swap_remove(pos);
+                }
+                self.refresh_bytes(nb);
+            }
+        }
+        self.refresh_bytes(nid);
+
+        let Some(mut entry) = self.entry.filter(|&e| e != nid).or(fallback[lvl]) else {
+            return true;
+        };
+        let mut cache = DistanceCache::default();
+        for l in (lvl + 1..=self.max_level).rev() {
+            entry = self.greedy(entry, nid, l, metric, &mut cache);
+        }
+        for l in (0..=lvl).rev() {
+            if entry == nid || l >= self.nodes[entry].links.len() {
+                match fallback[l] {
+                    Some(f) => entry = f,
+                    None => continue,
+                }
+            }
+            let ef_eff = if l == 0 { ef0 } else { ef }.max(m.max(1));
+            let mut neigh = self
+                .ef_search_idx(
+                    &[entry],
+                    &self.nodes[nid].vec,
+                    ef_eff,
+                    l,
+                    metric,
+                    SearchOpts::default(),
+                    &mut cache,
+                )
+                .unwrap_or_default();
+            neigh.retain(|&(c, _)| c != nid);
+            neigh.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+            let ids: Vec<NodeId> = neigh.iter().map(|&(c, _)| c).collect();
+            if let Some(&best) = ids.first() {
+                entry = best;
+            }
+            let budget = self.budget(l, m, m0);
+            self.connect(nid, &ids, budget, l, metric);
+        }
+        true
+    }
+
+    /// Connect `nid` to its layer-0 neighbourhood and make sure at least one
+    /// live node links back to it.
+    fn relink_orphan<M: Metric>(
+        &mut self,
+        nid: NodeId,
+        metric: &M,
+        m: usize,
+        m0: usize,
+        ef: usize,
+    ) -> bool {
+        let Some(mut ep) = self.entry else {
+            return false;
+        };
+        let mut cache = DistanceCache::default();
+        for l in (1..=self.max_level).rev() {
+            ep = self.greedy(ep, nid, l, metric, &mut cache);
+        }
+        let mut cand = self
+            .ef_search_idx(
+                &[ep],
+                &self.nodes[nid].vec,
+                ef.max(m),
+                0,
+                metric,
+                SearchOpts::default(),
+                &mut cache,
+            )
+            .unwrap_or_default();
+        cand.retain(|&(c, _)| c != nid);
+        if cand.is_empty() {
+            return false;
+        }
+        cand.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        let ids: Vec<NodeId> = cand.iter().map(|&(c, _)| c).collect();
+        let m0 = self.budget(0, m, m0);
+        self.connect(nid, &ids, m0, 0, metric);
+
+        if ids.iter().any(|&c| self.nodes[c].links[0].contains(&nid)) {
+            return true;
+        }
+        // Pruning dropped every back-edge: force one into the nearest
+        // neighbour, evicting its farthest link if it's at budget.
+        let near = ids[0];
+        let mut adj = std::mem::take(&mut self.nodes[near].links[0]);
+        if adj.len() >= m0 {
+            let far = adj
+                .iter()
+                .enumerate()
+                .max_by(|a, b| {
+                    let da = metric.distance(&self.nodes[*a.1].vec, &self.nodes[near].vec);
+                    let db = metric.distance(&self.nodes[*b.1].vec, &self.nodes[near].vec);
+                    da.partial_cmp(&db).unwrap_or(Ordering::Equal)
+                })
+                .map(|(i, _)| i);
+            if let Some(i) = far {
+                adj.swap_remove(i);
+            }
+        }
+        adj.push(nid);
+        self.nodes[near].links[0] = adj;
+        true
+    }
 }
 
-impl Default for Graph {
+/// Summary only (counters, no vectors or links); O(1).
+impl<Id> std::fmt::Debug for Graph<Id> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Graph")
+            .field("nodes", &self.nodes.len())
+            .field("active", &self.active)
+            .field("tombstones", &self.nodes.len().saturating_sub(self.active))
+            .field("max_level", &self.max_level)
+            .field("entry", &self.entry)
+            .field("total_bytes", &self.total_bytes)
+            .finish()
+    }
+}
+
+impl<Id: IdType> Default for Graph<Id> {
     fn default() -> Self {
         Self::new()
     }
@@ -481,51 +1473,173 @@ impl Default for Graph {
 // Maintenance / Management API
 // ----------------------------
 
-impl Graph {
+impl<Id: IdType> Graph<Id> {
     #[inline]
-    fn is_valid_nid(&self, nid: NodeId) -> bool {
+    /// Same live nodes by ext id, with equal vectors, layer counts and live
+    /// neighbour sets (as ext ids). NodeIds, tombstones, `last_hit`, `seq`
+    /// and neighbour order are not compared.
+    pub(crate) fn same_live_graph(&self, other: &Self) -> bool {
+        if self.active != other.active {
+            return false;
+        }
+        let live_neighbors = |g: &Self, nid: NodeId, l: usize| -> Vec<Id> {
+            g.neighbors(nid, l)
+                .iter()
+                .filter(|&&nb| g.is_valid_nid(nb))
+                .map(|&nb| g.nodes[nb].ext_id)
+                .collect()
+        };
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.is_deleted())
+            .all(|(nid, n)| {
+                let Some(&onid) = other.by_ext.get(&n.ext_id) else {
+                    return false;
+                };
+                let o = &other.nodes[onid];
+                !o.is_deleted()
+                    && *o.vec == *n.vec
+                    && o.links.len() == n.links.len()
+                    && (0..n.links.len()).all(|l| {
+                        // Adjacency is duplicate-free, so equal length plus
+                        // containment is set equality.
+                        let (a, b) = (live_neighbors(self, nid, l), live_neighbors(other, onid, l));
+                        a.len() == b.len() && a.iter().all(|x| b.contains(x))
+                    })
+            })
+    }
+
+    /// Order-independent hash of the live content compared by
+    /// `same_live_graph`: per node the ext id, vector bits and each layer's
+    /// set of live neighbour ids. NodeIds, tombstones and `last_hit` don't
+    /// contribute.
+    pub(crate) fn live_checksum(&self) -> u64 {
+        let id_hash = |id: Id| {
+            let mut h = Fnv64::default();
+            id.hash(&mut h);
+            h.finish()
+        };
+        let mut per_node: Vec<u64> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.is_deleted())
+            .map(|(nid, n)| {
+                let mut h = Fnv64::default();
+                h.write_u64(id_hash(n.ext_id));
+                for &x in n.vec.iter() {
+                    // -0.0 == 0.0 under `PartialEq`, so hash them alike.
+                    h.write_u32(if x == 0.0 { 0 } else { x.to_bits() });
+                }
+                for l in 0..n.links.len() {
+                    let mut nbrs: Vec<u64> = self
+                        .neighbors(nid, l)
+                        .iter()
+                        .filter(|&&nb| self.is_valid_nid(nb))
+                        .map(|&nb| id_hash(self.nodes[nb].ext_id))
+                        .collect();
+                    nbrs.sort_unstable();
+                    h.write_usize(nbrs.len());
+                    for nb in nbrs {
+                        h.write_u64(nb);
+                    }
+                }
+                h.finish()
+            })
+            .collect();
+        per_node.sort_unstable();
+        let mut h = Fnv64::default();
+        h.write_usize(per_node.len());
+        for x in per_node {
+            h.write_u64(x);
+        }
+        h.finish()
+    }
+
+    pub(crate) fn is_valid_nid(&self, nid: NodeId) -> bool {
         nid < self.nodes.len() && !self.nodes[nid].is_deleted()
     }
 
     /// Find a non-deleted node from the highest non-empty level down.
-    fn pick_entry(&self) -> Option<NodeId> {
+    ///
+    /// Picks the smallest valid NodeId on that level so the choice does not
+    /// depend on the order `levels[l]` was left in by `swap_remove`.
+    pub(crate) fn pick_entry(&self) -> Option<NodeId> {
+        self.entry_scans
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         for lvl in (0..self.levels.len()).rev() {
-            for &nid in &self.levels[lvl] {
-                if self.is_valid_nid(nid) {
-                    return Some(nid);
-                }
+            let best = self.levels[lvl]
+                .iter()
+                .copied()
+                .filter(|&nid| self.is_valid_nid(nid))
+                .min();
+            if best.is_some() {
+                return best;
             }
         }
         None
     }
 }
 
-impl Graph {
+impl<Id: IdType> Graph<Id> {
     /// Idempotent delete by external id. Returns true if something was removed.
-    pub fn delete(&mut self, ext_id: u64) -> bool {
-        let Some(nid) = self.by_ext.remove(&ext_id) else {
+    pub fn delete(&mut self, ext_id: Id) -> bool {
+        let Some(nid) = self.unlink(ext_id) else {
             return false;
         };
-        if nid >= self.nodes.len() {
-            return false;
+
+        // Remove from level registry lists where present.
+        for l in 0..self.levels.len() {
+            if let Some(pos) = self.levels[l].iter().position(|&x| x == nid) {
+                self.levels[l].swap_remove(pos);
+            }
         }
-        if self.nodes[nid].is_deleted() {
-            return false;
+
+        // Maintain entry: if we deleted the entry, pick a fallback if any.
+        if self.entry == Some(nid) {
+            self.entry = self.pick_entry();
         }
+        true
+    }
 
-        // We'll accumulate the net byte delta for neighbors + node and then
-        // apply it once to self.total_bytes (can be negative).
-        let mut bytes_delta: isize = 0;
+    /// Delete a batch of external ids with a single level-registry rebuild
+    /// and repair pass. Returns how many ids were actually removed.
+    pub fn delete_many(&mut self, ext_ids: &[Id]) -> usize {
+        let mut removed = 0usize;
+        for &eid in ext_ids {
+            if self.unlink(eid).is_some() {
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            let deleted: Vec<bool> = self.nodes.iter().map(|n| n.is_deleted()).collect();
+            for lvl in &mut self.levels {
+                lvl.retain(|&x| x < deleted.len() && !deleted[x]);
+            }
+            self.repair_after_mass_deletes();
+        }
+        removed
+    }
 
-        // Capture node's bytes before any modifications.
-        let node_bytes_before = { self.nodes[nid].recompute_bytes() };
+    /// Tombstone a node and unlink it from its neighbors, keeping counters in
+    /// sync. Leaves the level registry and entry point to the caller.
+    fn unlink(&mut self, ext_id: Id) -> Option<NodeId> {
+        let nid = self.by_ext.remove(&ext_id)?;
+        if nid >= self.nodes.len() {
+            return None;
+        }
+        if self.nodes[nid].is_deleted() {
+            return None;
+        }
+        self.journal_save(nid);
 
         // Unlink from neighbors at each existing level, updating their byte footprints.
         let levels = { self.nodes[nid].links.len() };
         for l in 0..levels {
             // Remove nid from every neighbor's adjacency list on layer l.
             // Take this level's neighbors from the node, then drop the borrow of node.
-            let neigh: Vec<NodeId> = {
+            let neigh: Adj = {
                 let node = &mut self.nodes[nid];
                 std::mem::take(&mut node.links[l])
             };
@@ -539,68 +1653,41 @@ impl Graph {
                 if l >= self.nodes[nb].links.len() {
                     continue;
                 }
-                // neighbor bytes before
-                let nb_bytes_before = { self.nodes[nb].recompute_bytes() };
-                {
-                    let adj = &mut self.nodes[nb].links[l];
-                    if let Some(pos) = adj.iter().position(|&x| x == nid) {
-                        adj.swap_remove(pos);
-                    }
+                self.journal_save(nb);
+                let adj = &mut self.nodes[nb].links[l];
+                if let Some(pos) = adj.iter().position(|&x| x == nid) {
+                    adj.swap_remove(pos);
                 }
-                // neighbor bytes after
-                let nb_bytes_after = { self.nodes[nb].recompute_bytes() };
-                bytes_delta += (nb_bytes_after as isize) - (nb_bytes_before as isize);
+                self.refresh_bytes(nb);
             }
         }
 
-        // After removing all links from this node, recalc its bytes (links now empty).
-        let node_bytes_after_links = { self.nodes[nid].recompute_bytes() };
-        bytes_delta += (node_bytes_after_links as isize) - (node_bytes_before as isize);
-
-        // Clear vector memory (shrink) and mark deleted), and add the byte delta.
-        bytes_delta += {
-            let before = self.nodes[nid].recompute_bytes();
-            {
-                let node = &mut self.nodes[nid];
-                node.vec.clear(); // release vector contents
-                node.vec.shrink_to_fit(); // return capacity
-                node.deleted
-                    .store(true, std::sync::atomic::Ordering::Relaxed);
-            }
-            let after = self.nodes[nid].recompute_bytes();
-            (after as isize) - (before as isize)
-        };
+        // Release the vector and mark deleted; the node's links are already empty.
+        {
+            let node = &mut self.nodes[nid];
+            node.vec = VecSlot::empty();
+            node.deleted
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+        self.refresh_bytes(nid);
 
         self.active = self.active.saturating_sub(1);
+        Some(nid)
+    }
 
-        // Remove from level registry lists where present.
-        for l in 0..self.levels.len() {
-            if let Some(pos) = self.levels[l].iter().position(|&x| x == nid) {
-                self.levels[l].swap_remove(pos);
-            }
-        }
-
-        // Maintain entry: if we deleted the entry, pick a fallback if any.
-        if self.entry == Some(nid) {
-            self.entry = self
-                .levels
-                .iter()
-                .rev()
-                .find_map(|lvl| lvl.first().copied());
-        }
+    /// Touch multiple external ids with the same timestamp (from search hits).
+    pub fn touch_many(&self, ids: &[Id], now_unix: u64) {
+        self.touch_each(ids.iter().copied(), now_unix);
+    }
 
-        // Apply accumulated delta to total_bytes.
-        if bytes_delta >= 0 {
-            self.total_bytes = self.total_bytes.saturating_add(bytes_delta as usize);
-        } else {
-            self.total_bytes = self.total_bytes.saturating_sub((-bytes_delta) as usize);
-        }
-        true
+    /// `touch_many` straight from `(id, distance)` hits, without collecting
+    /// the ids first.
+    pub(crate) fn touch_hits(&self, hits: &[(Id, f32)], now_unix: u64) {
+        self.touch_each(hits.iter().map(|h| h.0), now_unix);
     }
 
-    /// Touch multiple external ids with the same timestamp (from search hits).
-    pub fn touch_many(&self, ids: &[u64], now_unix: u64) {
-        for &eid in ids {
+    fn touch_each(&self, ids: impl Iterator<Item = Id>, now_unix: u64) {
+        for eid in ids {
             if let Some(&nid) = self.by_ext.get(&eid) {
                 if nid < self.nodes.len() {
                     let node = &self.nodes[nid];
@@ -612,15 +1699,51 @@ impl Graph {
         }
     }
 
+    /// Read every live node's vector and adjacency once (one load per cache
+    /// line) to pull them into RAM/cache. Returns the number of bytes covered.
+    pub fn warm(&self) -> usize {
+        const F32_PER_LINE: usize = 64 / std::mem::size_of::<f32>();
+        const IDS_PER_LINE: usize = 64 / std::mem::size_of::<NodeId>();
+
+        let mut bytes = 0usize;
+        let mut sink = 0u64;
+        for n in &self.nodes {
+            if n.is_deleted() {
+                continue;
+            }
+            for x in n.vec.iter().step_by(F32_PER_LINE) {
+                sink ^= x.to_bits() as u64;
+            }
+            bytes += n.vec.len() * std::mem::size_of::<f32>();
+            for l in &n.links {
+                for &nb in l.iter().step_by(IDS_PER_LINE) {
+                    sink ^= nb as u64;
+                }
+                bytes += l.len() * std::mem::size_of::<NodeId>();
+            }
+        }
+        // Publish the fold so the loads can't be optimised away.
+        WARM_SINK.store(sink, std::sync::atomic::Ordering::Relaxed);
+        bytes
+    }
+
     /// Basic stats for eviction/monitoring.
     #[inline]
     pub fn stats(&self) -> (usize, usize) {
         (self.active, self.total_bytes)
     }
 
-    /// TTL sweep: evict nodes whose last_hit is older than `ttl_secs`.
-    pub fn evict_ttl(&mut self, ttl_secs: u64, now_unix: u64) -> (usize, usize) {
+    /// TTL sweep: evict nodes whose last_hit is older than `ttl_ms`
+    /// (both in Unix milliseconds).
+    pub fn evict_ttl(&mut self, ttl_ms: u64, now_ms: u64) -> (usize, usize) {
+        (self.evict_ttl_step(ttl_ms, now_ms, usize::MAX).0, 0)
+    }
+
+    /// `evict_ttl` that stops after `budget` evictions. Returns the count
+    /// evicted and whether expired nodes remain.
+    pub fn evict_ttl_step(&mut self, ttl_ms: u64, now_ms: u64, budget: usize) -> (usize, bool) {
         let mut evicted = 0usize;
+        let mut more = false;
         for nid in 0..self.nodes.len() {
             if self.nodes[nid].is_deleted() {
                 continue;
@@ -628,7 +1751,11 @@ impl Graph {
             let ts = self.nodes[nid]
                 .last_hit
                 .load(std::sync::atomic::Ordering::Relaxed);
-            if now_unix.saturating_sub(ts) > ttl_secs {
+            if now_ms.saturating_sub(ts) > ttl_ms {
+                if evicted == budget {
+                    more = true;
+                    break;
+                }
                 let ext = self.nodes[nid].ext_id;
                 if self.delete(ext) {
                     evicted += 1;
@@ -636,16 +1763,29 @@ impl Graph {
             }
         }
         self.repair_after_mass_deletes();
-        (evicted, 0)
+        (evicted, more)
     }
 
-    /// LRU eviction until caps are satisfied.
+    /// LRU eviction until caps are satisfied. Equal `last_hit` stamps are
+    /// evicted in ascending NodeId (insertion) order, so runs are repeatable.
     pub fn evict_lru_until(
         &mut self,
         max_vecs: Option<usize>,
         max_bytes: Option<usize>,
         _now_unix: u64,
     ) -> (usize, usize) {
+        (self.evict_lru_step(max_vecs, max_bytes, usize::MAX).0, 0)
+    }
+
+    /// `evict_lru_until` that stops after `budget` evictions. Returns the
+    /// count evicted and whether the caps are still exceeded. Each call
+    /// scans all live nodes once (O(n)) to pick its victims.
+    pub fn evict_lru_step(
+        &mut self,
+        max_vecs: Option<usize>,
+        max_bytes: Option<usize>,
+        budget: usize,
+    ) -> (usize, bool) {
         let need = |active: usize, bytes: usize| {
             if let Some(mv) = max_vecs {
                 if active > mv {
@@ -662,26 +1802,28 @@ impl Graph {
 
         let (mut active, mut bytes) = self.stats();
         if !need(active, bytes) {
-            return (0, 0);
+            return (0, false);
         }
 
-        let mut heap: std::collections::BinaryHeap<std::cmp::Reverse<(u64, NodeId)>> =
-            std::collections::BinaryHeap::new();
-        for (nid, n) in self.nodes.iter().enumerate() {
-            if !n.is_deleted() {
-                let ts = n.last_hit.load(std::sync::atomic::Ordering::Relaxed);
-                heap.push(std::cmp::Reverse((ts, nid)));
-            }
+        let mut victims: Vec<(u64, NodeId)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.is_deleted())
+            .map(|(nid, n)| (n.last_hit.load(std::sync::atomic::Ordering::Relaxed), nid))
+            .collect();
+        // Only the `budget` oldest can go this call; no need to sort the rest.
+        if budget < victims.len() {
+            victims.select_nth_unstable(budget);
+            victims.truncate(budget);
         }
+        victims.sort_unstable();
 
         let mut evicted = 0usize;
-        while let Some(std::cmp::Reverse((_ts, nid))) = heap.pop() {
+        for (_ts, nid) in victims {
             if !need(active, bytes) {
                 break;
             }
-            if nid >= self.nodes.len() {
-                continue;
-            }
             let ext = self.nodes[nid].ext_id;
             if self.delete(ext) {
                 (active, bytes) = self.stats();
@@ -689,12 +1831,40 @@ impl Graph {
             }
         }
         self.repair_after_mass_deletes();
-        (evicted, 0)
+        (evicted, need(active, bytes))
     }
 }
 
-impl Graph {
+impl<Id: IdType> Graph<Id> {
+    /// Rebuild the level registry from live nodes only: each `levels[l]`
+    /// lists, in ascending NodeId order, the live nodes whose top layer is
+    /// `l`, with capacity shrunk to fit. Re-derives `max_level` and
+    /// re-picks the entry point. O(nodes); the graph itself is untouched.
+    pub fn compact_levels(&mut self) {
+        let max_level = self
+            .nodes
+            .iter()
+            .filter(|n| !n.is_deleted())
+            .map(|n| n.links.len().saturating_sub(1))
+            .max()
+            .unwrap_or(0);
+        let mut levels = vec![Vec::new(); max_level + 1];
+        for (nid, n) in self.nodes.iter().enumerate() {
+            if !n.is_deleted() {
+                levels[n.links.len().saturating_sub(1)].push(nid);
+            }
+        }
+        for lvl in &mut levels {
+            lvl.shrink_to_fit();
+        }
+        self.levels = levels;
+        self.max_level = max_level;
+        self.entry = self.pick_entry();
+    }
+
     fn repair_after_mass_deletes(&mut self) {
+        #[cfg(test)]
+        MASS_REPAIRS.with(|n| n.set(n.get() + 1));
         // Drop empty tails in levels and fix max_level
         while self.max_level > 0 && self.levels[self.max_level].is_empty() {
             self.max_level -= 1;
@@ -704,5 +1874,50 @@ impl Graph {
         if self.entry.map_or(true, |e| !self.is_valid_nid(e)) {
             self.entry = self.pick_entry();
         }
+        // Every caller already did an O(n) pass, so the recount is cheap here.
+        debug_assert!(self.bytes_consistent(), "incremental total_bytes drifted");
+    }
+}
+
+/// FNV-1a with little-endian integer encoding, so checksums agree across
+/// platforms and Rust versions (unlike `DefaultHasher`).
+pub(crate) struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Fnv64(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv64 {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
     }
 }
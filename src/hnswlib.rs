@@ -0,0 +1,393 @@
+//! hnswlib.rs — read/write hnswlib's binary index format (`hnswlib_compat`).
+//!
+//! Layout of `HierarchicalNSW::saveIndex` (hnswlib 0.7/0.8), little-endian,
+//! `size_t` = 8 bytes, `tableint` = `u32`:
+//!
+//! ```text
+//! header   offsetLevel0 u64 (= 0) | max_elements u64 | cur_element_count u64
+//!          size_data_per_element u64 | label_offset u64 | offsetData u64
+//!          maxlevel i32 | enterpoint_node u32 | maxM u64 | maxM0 u64 | M u64
+//!          mult f64 | ef_construction u64
+//! level 0  cur_element_count × size_data_per_element bytes, each:
+//!            u32 header (low 16 bits: neighbour count; bit 0 of byte 2:
+//!            deleted) | maxM0 × u32 neighbour slots | dims × f32 | label u64
+//! upper    per element: u32 linkListSize (= level × (4 + 4·maxM), 0 on
+//!            layer 0 only), then for each layer 1..=level one block of
+//!            u32 count | maxM × u32 neighbour slots
+//! ```
+//!
+//! so `offsetData = 4 + 4·maxM0`, `label_offset = offsetData + 4·dims` and
+//! `size_data_per_element = label_offset + 8`. The metric is not stored:
+//! [`Cosine`](crate::Cosine) matches hnswlib's `cosine` space only for
+//! unit-length vectors (hnswlib normalises at `add_items`, the file keeps
+//! what we wrote), and [`Dot`](crate::Dot) matches `ip`. Only
+//! `Hnsw<M, u64>` converts, since hnswlib labels are `u64`.
+
+use crate::{
+    errors::{Result, VcalError},
+    graph::Graph,
+    math::Metric,
+    node::{Adj, Links, Node, NodeId},
+    ExternalId, Hnsw, HnswBuilder,
+};
+use std::sync::atomic::Ordering;
+
+/// Byte 2 of a level-0 list header carries hnswlib's delete mark.
+const DELETE_MARK: u32 = 0x01 << 16;
+
+impl<M: Metric> Hnsw<M, ExternalId> {
+    /// Serialise into hnswlib's binary format (see the module docs). Live
+    /// nodes are renumbered densely in NodeId order and tombstones dropped.
+    /// `maxM`/`maxM0` are this index's `m`/`m_max0`, raised to the largest
+    /// actual degree if a list is longer. Errors with `InvalidParameter` past
+    /// `u32::MAX` live nodes.
+    pub fn to_hnswlib_bytes(&self) -> Result<Vec<u8>> {
+        let g = &self.graph;
+        let mut dense = vec![u32::MAX; g.nodes.len()];
+        let mut live = Vec::with_capacity(g.active);
+        for (nid, slot) in dense.iter_mut().enumerate() {
+            if g.is_valid_nid(nid) {
+                *slot = u32::try_from(live.len()).map_err(|_| {
+                    VcalError::InvalidParameter("hnswlib indexes hold at most u32::MAX nodes")
+                })?;
+                live.push(nid);
+            }
+        }
+        let links = |nid: NodeId, l: usize| -> Vec<u32> {
+            g.neighbors(nid, l)
+                .iter()
+                .filter(|&&nb| g.is_valid_nid(nb))
+                .map(|&nb| dense[nb])
+                .collect()
+        };
+
+        let mut max_m = self.m;
+        let mut max_m0 = self.m_max0;
+        let mut max_level = 0usize;
+        for &nid in &live {
+            let top = g.nodes[nid].links.len().saturating_sub(1);
+            max_level = max_level.max(top);
+            max_m0 = max_m0.max(links(nid, 0).len());
+            for l in 1..=top {
+                max_m = max_m.max(links(nid, l).len());
+            }
+        }
+        if max_m0 > 0xFFFF || max_m > 0xFFFF {
+            return Err(VcalError::InvalidParameter(
+                "hnswlib neighbour counts are 16-bit",
+            ));
+        }
+
+        let n = live.len() as u64;
+        let offset_data = 4 + 4 * max_m0;
+        let label_offset = offset_data + 4 * self.dims;
+        let size_per_element = label_offset + 8;
+        let size_links_per_element = 4 + 4 * max_m;
+        let (max_level_field, entry) = match g.pick_entry() {
+            Some(e) if !live.is_empty() => (max_level as i32, dense[e]),
+            _ => (-1, u32::MAX),
+        };
+
+        let mut out = Vec::with_capacity(96 + live.len() * (size_per_element + 4));
+        for v in [
+            0,
+            n,
+            n,
+            size_per_element as u64,
+            label_offset as u64,
+            offset_data as u64,
+        ] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out.extend_from_slice(&max_level_field.to_le_bytes());
+        out.extend_from_slice(&entry.to_le_bytes());
+        for v in [max_m as u64, max_m0 as u64, self.m as u64] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out.extend_from_slice(&(1.0 / (self.m.max(2) as f64).ln()).to_le_bytes());
+        out.extend_from_slice(&(self.efc as u64).to_le_bytes());
+
+        let push_list = |out: &mut Vec<u8>, list: &[u32], slots: usize| {
+            out.extend_from_slice(&(list.len() as u32).to_le_bytes());
+            for &x in list {
+                out.extend_from_slice(&x.to_le_bytes());
+            }
+            out.resize(out.len() + 4 * (slots - list.len()), 0);
+        };
+        for &nid in &live {
+            let node = &g.nodes[nid];
+            push_list(&mut out, &links(nid, 0), max_m0);
+            for x in node.vec.iter() {
+                out.extend_from_slice(&x.to_le_bytes());
+            }
+            out.extend_from_slice(&node.ext_id.to_le_bytes());
+        }
+        for &nid in &live {
+            let top = g.nodes[nid].links.len().saturating_sub(1);
+            out.extend_from_slice(&((size_links_per_element * top) as u32).to_le_bytes());
+            for l in 1..=top {
+                push_list(&mut out, &links(nid, l), max_m);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Load an index written by hnswlib's `saveIndex` (or
+    /// [`Hnsw::to_hnswlib_bytes`]). Elements marked deleted become
+    /// tombstones; edges to them are dropped as `from_slice` would. `m`,
+    /// `m_max0` and `ef_construction` come from the file, other settings
+    /// are builder defaults. Malformed input fails with `CorruptSnapshot`.
+    pub fn from_hnswlib_bytes(bytes: &[u8]) -> Result<Self>
+    where
+        M: Default,
+    {
+        let mut r = Reader { bytes, pos: 0 };
+        let offset_level0 = r.u64()?;
+        let _max_elements = r.u64()?;
+        let count = r.usize()?;
+        let size_per_element = r.usize()?;
+        let label_offset = r.usize()?;
+        let offset_data = r.usize()?;
+        let _max_level = r.i32()?;
+        let entry = r.u32()?;
+        let max_m = r.usize()?;
+        let max_m0 = r.usize()?;
+        let m = r.usize()?;
+        let _mult = r.f64()?;
+        let efc = r.usize()?;
+
+        if max_m0 > 0xFFFF || max_m > 0xFFFF {
+            return Err(corrupt("neighbour budget exceeds 16 bits"));
+        }
+        let data_bytes = label_offset.saturating_sub(offset_data);
+        if offset_level0 != 0
+            || offset_data != 4 + 4 * max_m0
+            || data_bytes == 0
+            || data_bytes % 4 != 0
+            || size_per_element != label_offset.saturating_add(8)
+        {
+            return Err(corrupt("inconsistent header"));
+        }
+        let dims = data_bytes / 4;
+        let size_links_per_element = 4 + 4 * max_m;
+
+        let mut h = HnswBuilder::new(M::default())
+            .dims(dims)
+            .m(m)
+            .m_max0(max_m0)
+            .ef_construction(efc)
+            .build()?;
+        let mut g: Graph<ExternalId> = Graph::new();
+        if count > bytes.len() / size_per_element {
+            return Err(corrupt("truncated level-0 data"));
+        }
+        g.nodes.reserve(count);
+        for i in 0..count {
+            let (head, list) = r.list(max_m0, count)?;
+            let vec: Vec<f32> = (0..dims).map(|_| r.f32()).collect::<Result<_>>()?;
+            let label = r.u64()?;
+            let mut node = Node::new(label, 0, vec.into());
+            node.links[0] = list;
+            node.seq = i as u64;
+            if head & DELETE_MARK != 0 {
+                node.deleted.store(true, Ordering::Relaxed);
+            }
+            g.nodes.push(node);
+        }
+        for i in 0..count {
+            let size = r.u32()? as usize;
+            if size % size_links_per_element != 0 {
+                return Err(corrupt("link list size is not a whole number of layers"));
+            }
+            for _ in 0..size / size_links_per_element {
+                let (_, list) = r.list(max_m, count)?;
+                g.nodes[i].links.push(list);
+            }
+        }
+        if r.pos != bytes.len() {
+            return Err(corrupt("trailing bytes"));
+        }
+
+        for i in 0..count {
+            let node = &mut g.nodes[i];
+            if node.is_deleted() {
+                node.vec = Vec::new().into();
+                node.links = Links::new();
+            } else if g.by_ext.insert(node.ext_id, i).is_some() {
+                return Err(corrupt("duplicate live label"));
+            }
+        }
+        g.next_seq = count as u64;
+        g.sanitize();
+        let entry = entry as usize;
+        if g.is_valid_nid(entry) && g.nodes[entry].links.len() == g.max_level + 1 {
+            g.entry = Some(entry);
+        }
+        g.sampler = h.graph.sampler;
+        h.graph = g;
+        Ok(h)
+    }
+}
+
+fn corrupt(what: &str) -> VcalError {
+    VcalError::CorruptSnapshot(format!("hnswlib: {}", what))
+}
+
+/// Little-endian cursor; running off the end is `CorruptSnapshot`.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let end = self.pos.checked_add(N).filter(|&e| e <= self.bytes.len());
+        let end = end.ok_or_else(|| corrupt("truncated"))?;
+        let mut b = [0u8; N];
+        b.copy_from_slice(&self.bytes[self.pos..end]);
+        self.pos = end;
+        Ok(b)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        self.take().map(i32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        usize::try_from(self.u64()?).map_err(|_| corrupt("size exceeds usize"))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        self.take().map(f64::from_le_bytes)
+    }
+
+    /// One `u32 header | slots × u32` list; returns the raw header and the
+    /// first `header & 0xFFFF` ids, which must be below `count`.
+    fn list(&mut self, slots: usize, count: usize) -> Result<(u32, Adj)> {
+        let head = self.u32()?;
+        let len = (head & 0xFFFF) as usize;
+        if len > slots {
+            return Err(corrupt("neighbour count exceeds its list"));
+        }
+        let mut adj = Adj::with_capacity(len);
+        for s in 0..slots {
+            let x = self.u32()? as usize;
+            if s < len {
+                if x >= count {
+                    return Err(corrupt("neighbour id out of range"));
+                }
+                adj.push(x);
+            }
+        }
+        Ok((head, adj))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cosine, Dot};
+
+    fn build(n: u64) -> Hnsw<Cosine> {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(12)
+            .m(6)
+            .seed(149)
+            .build()
+            .unwrap();
+        for i in 0..n {
+            let v: Vec<f32> = (0..12)
+                .map(|d| ((i * 12 + d) as f32 * 0.29).sin())
+                .collect();
+            h.insert(v, 1_000 + i).unwrap();
+        }
+        h
+    }
+
+    fn u64_at(b: &[u8], off: usize) -> u64 {
+        u64::from_le_bytes(b[off..off + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_follows_the_hnswlib_layout() {
+        let h = build(50);
+        let b = h.to_hnswlib_bytes().unwrap();
+        let max_m0 = u64_at(&b, 64) as usize;
+        assert_eq!(u64_at(&b, 0), 0);
+        assert_eq!((u64_at(&b, 8), u64_at(&b, 16)), (50, 50));
+        assert_eq!(u64_at(&b, 40) as usize, 4 + 4 * max_m0);
+        assert_eq!(u64_at(&b, 32) as usize, 4 + 4 * max_m0 + 4 * 12);
+        assert_eq!(u64_at(&b, 24), u64_at(&b, 32) + 8);
+        assert_eq!(u64_at(&b, 72), 6);
+        assert_eq!(max_m0, 12);
+
+        // Element 0's vector and label sit at their documented offsets.
+        let header = 8 * 6 + 4 + 4 + 8 * 3 + 8 + 8;
+        let data = header + 4 + 4 * max_m0;
+        let first = f32::from_le_bytes(b[data..data + 4].try_into().unwrap());
+        assert_eq!(first, h.graph.nodes[0].vec[0]);
+        assert_eq!(u64_at(&b, data + 4 * 12), 1_000);
+    }
+
+    #[test]
+    fn hnswlib_roundtrip_keeps_the_live_graph() {
+        let mut h = build(300);
+        for id in (1_000..1_300).step_by(11) {
+            h.delete(id);
+        }
+        let bytes = h.to_hnswlib_bytes().unwrap();
+        let back = Hnsw::<Cosine>::from_hnswlib_bytes(&bytes).unwrap();
+        assert!(back.graph.same_live_graph(&h.graph));
+        assert_eq!(back.len(), h.len());
+        assert_eq!((back.m, back.m_max0), (6, 12));
+        for id in (1_001..1_300).step_by(17).filter(|&id| h.contains(id)) {
+            let q = &h.graph.nodes[h.graph.by_ext[&id]].vec;
+            assert_eq!(
+                back.search(&q[..], 5).unwrap(),
+                h.search(&q[..], 5).unwrap()
+            );
+        }
+        // Neighbour lists come back sorted, so compare from the second
+        // generation on.
+        let again = back.to_hnswlib_bytes().unwrap();
+        let third = Hnsw::<Cosine>::from_hnswlib_bytes(&again).unwrap();
+        assert_eq!(third.to_hnswlib_bytes().unwrap(), again);
+
+        let empty = HnswBuilder::<Dot>::new(Dot).dims(4).build().unwrap();
+        let back = Hnsw::<Dot>::from_hnswlib_bytes(&empty.to_hnswlib_bytes().unwrap()).unwrap();
+        assert!(back.is_empty());
+    }
+
+    #[test]
+    fn hnswlib_delete_marks_and_corruption() {
+        let h = build(20);
+        let mut bytes = h.to_hnswlib_bytes().unwrap();
+        let header = 8 * 6 + 4 + 4 + 8 * 3 + 8 + 8;
+        // Mark element 3 deleted the way hnswlib's markDelete does.
+        let per = u64_at(&bytes, 24) as usize;
+        bytes[header + 3 * per + 2] |= 1;
+        let back = Hnsw::<Cosine>::from_hnswlib_bytes(&bytes).unwrap();
+        assert_eq!(back.len(), 19);
+        assert!(!back.contains(1_003));
+        assert!(back.audit().is_clean());
+
+        for cut in [0, 40, header + 5, bytes.len() - 1] {
+            assert!(matches!(
+                Hnsw::<Cosine>::from_hnswlib_bytes(&bytes[..cut]),
+                Err(VcalError::CorruptSnapshot(_))
+            ));
+        }
+    }
+}
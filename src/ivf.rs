@@ -0,0 +1,409 @@
+//! ivf.rs — optional IVF-style coarse quantizer over the HNSW graph (`ivf` feature).
+//!
+//! `train_ivf` clusters a sample of live vectors into `nlist` buckets with a
+//! few Lloyd iterations under the index metric, then assigns every node to
+//! its nearest centroid; inserts and `update_vector` keep the assignment
+//! current. `search_ivf` ranks the centroids against the query and runs the
+//! layer-0 beam only inside the `nprobe` nearest buckets, starting where the
+//! usual upper-layer descent lands (or, if that node is outside the probed
+//! buckets, from the node closest to each probed centroid). The quantizer
+//! lives in memory only and is not part of snapshots.
+
+use crate::{
+    graph::SearchOpts, math::Metric, node::now_millis, node::NodeId, Hnsw, IdType, Result,
+    SearchHit, VcalError,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+/// Upper bound on Lloyd iterations during training.
+const KMEANS_ITERS: usize = 10;
+/// Seed candidates kept per bucket (its nodes closest to the centroid); the
+/// first live one starts the beam, the rest cover for deletes.
+const SEEDS_PER_BUCKET: usize = 4;
+/// Bucket of a deleted node.
+const NO_BUCKET: u32 = u32::MAX;
+
+pub(crate) struct Ivf {
+    centroids: Vec<Vec<f32>>,
+    /// Bucket per NodeId; nodes added after training are appended by
+    /// `sync_ivf`, updated vectors moved by `rebucket_ivf`.
+    assign: Vec<u32>,
+    /// Per bucket, `(distance to centroid, node)` ascending.
+    seeds: Vec<Vec<(f32, NodeId)>>,
+}
+
+impl Ivf {
+    /// The same centroids with no nodes assigned, for a rebuilt graph.
+    pub(crate) fn untrained_copy(&self) -> Ivf {
+        Ivf {
+            centroids: self.centroids.clone(),
+            assign: Vec::new(),
+            seeds: vec![Vec::new(); self.centroids.len()],
+        }
+    }
+
+    fn nearest<M: Metric>(&self, metric: &M, v: &[f32]) -> (u32, f32) {
+        let mut best = (0u32, f32::INFINITY);
+        for (b, c) in self.centroids.iter().enumerate() {
+            let d = metric.distance(c, v);
+            if d < best.1 {
+                best = (b as u32, d);
+            }
+        }
+        best
+    }
+
+    fn offer_seed(&mut self, bucket: u32, d: f32, nid: NodeId) {
+        let seeds = &mut self.seeds[bucket as usize];
+        if seeds.len() == SEEDS_PER_BUCKET && d >= seeds[SEEDS_PER_BUCKET - 1].0 {
+            return;
+        }
+        let pos = seeds
+            .iter()
+            .position(|&(sd, _)| d < sd)
+            .unwrap_or(seeds.len());
+        seeds.insert(pos, (d, nid));
+        seeds.truncate(SEEDS_PER_BUCKET);
+    }
+}
+
+impl<M: Metric, Id: IdType> Hnsw<M, Id> {
+    /// Train the coarse quantizer: k-means over up to `sample` live vectors
+    /// (at least `nlist`, drawn with `seed`) into `nlist` buckets, then assign
+    /// every live node. Costs `nodes * nlist` distance calls on top of the
+    /// k-means itself. Centroids are member means, which suits `Cosine` and
+    /// other metrics whose notion of "centre" is the average. Retrain after
+    /// heavy churn; vectors inserted later are bucketed as they arrive.
+    pub fn train_ivf(&mut self, nlist: usize, sample: usize, seed: u64) -> Result<()> {
+        if nlist == 0 {
+            return Err(VcalError::InvalidParameter("nlist must be > 0"));
+        }
+        let nodes = &self.graph.nodes;
+        let mut pool: Vec<NodeId> = (0..nodes.len())
+            .filter(|&nid| !nodes[nid].is_deleted())
+            .collect();
+        if pool.is_empty() {
+            return Err(VcalError::EmptyIndex);
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        pool.shuffle(&mut rng);
+        pool.truncate(sample.max(nlist));
+        let nlist = nlist.min(pool.len());
+
+        let mut centroids: Vec<Vec<f32>> = pool[..nlist]
+            .iter()
+            .map(|&nid| nodes[nid].vec.to_vec())
+            .collect();
+        let mut member = vec![u32::MAX; pool.len()];
+        for _ in 0..KMEANS_ITERS {
+            let mut changed = false;
+            for (slot, &nid) in member.iter_mut().zip(&pool) {
+                let v = &nodes[nid].vec;
+                let mut best = (0u32, f32::INFINITY);
+                for (b, c) in centroids.iter().enumerate() {
+                    let d = self.metric.distance(c, v);
+                    if d < best.1 {
+                        best = (b as u32, d);
+                    }
+                }
+                if *slot != best.0 {
+                    *slot = best.0;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+            let mut sums = vec![vec![0.0f32; self.dims]; nlist];
+            let mut counts = vec![0usize; nlist];
+            for (&b, &nid) in member.iter().zip(&pool) {
+                counts[b as usize] += 1;
+                for (s, x) in sums[b as usize].iter_mut().zip(nodes[nid].vec.iter()) {
+                    *s += x;
+                }
+            }
+            // Empty buckets keep their previous centroid.
+            for ((c, s), &n) in centroids.iter_mut().zip(sums).zip(&counts) {
+                if n > 0 {
+                    *c = s.into_iter().map(|x| x / n as f32).collect();
+                }
+            }
+        }
+
+        self.ivf = Some(Ivf {
+            centroids,
+            assign: Vec::new(),
+            seeds: vec![Vec::new(); nlist],
+        });
+        self.sync_ivf();
+        Ok(())
+    }
+
+    /// Drop the coarse quantizer; `search_ivf` errors until retrained.
+    pub fn clear_ivf(&mut self) {
+        self.ivf = None;
+    }
+
+    /// Number of IVF buckets, or `None` if `train_ivf` hasn't run.
+    pub fn ivf_nlist(&self) -> Option<usize> {
+        self.ivf.as_ref().map(|ivf| ivf.centroids.len())
+    }
+
+    /// Bucket nodes appended since the last call (no-op when untrained).
+    pub(crate) fn sync_ivf(&mut self) {
+        let Some(ivf) = self.ivf.as_mut() else {
+            return;
+        };
+        for nid in ivf.assign.len()..self.graph.nodes.len() {
+            let node = &self.graph.nodes[nid];
+            if node.is_deleted() {
+                ivf.assign.push(NO_BUCKET);
+                continue;
+            }
+            let (b, d) = ivf.nearest(&self.metric, &node.vec);
+            ivf.assign.push(b);
+            ivf.offer_seed(b, d, nid);
+        }
+    }
+
+    /// Move live node `nid` to the bucket nearest its current vector, e.g.
+    /// after `update_vector` (no-op when untrained).
+    pub(crate) fn rebucket_ivf(&mut self, nid: NodeId) {
+        let Some(ivf) = self.ivf.as_mut() else {
+            return;
+        };
+        if nid >= ivf.assign.len() {
+            self.sync_ivf();
+            return;
+        }
+        let old = ivf.assign[nid];
+        if old != NO_BUCKET {
+            ivf.seeds[old as usize].retain(|&(_, s)| s != nid);
+        }
+        let (b, d) = ivf.nearest(&self.metric, &self.graph.nodes[nid].vec);
+        ivf.assign[nid] = b;
+        ivf.offer_seed(b, d, nid);
+    }
+
+    /// Search only the `nprobe` buckets (clamped to `1..=nlist`) whose
+    /// centroids are nearest the query. Fewer probes visit fewer nodes but
+    /// miss neighbours that fell into unprobed buckets; `nprobe = nlist`
+    /// covers the whole graph. If every seed of the probed buckets has been
+    /// deleted, falls back to a full-graph search filtered to those buckets.
+    /// Errors with `InvalidConfig` if the quantizer hasn't been trained.
+    pub fn search_ivf(&self, query: &[f32], k: usize, nprobe: usize) -> Result<Vec<SearchHit<Id>>> {
+        let query = match self.prepare_query(query)? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        let ivf = self
+            .ivf
+            .as_ref()
+            .ok_or(VcalError::InvalidConfig("IVF quantizer not trained"))?;
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let nlist = ivf.centroids.len();
+        let mut order: Vec<(f32, usize)> = ivf
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(b, c)| (self.metric.distance(&query, c), b))
+            .collect();
+        order.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        let mut probe = vec![false; nlist];
+        for &(_, b) in order.iter().take(nprobe.clamp(1, nlist)) {
+            probe[b] = true;
+        }
+        let in_probe = |nid: NodeId| {
+            ivf.assign
+                .get(nid)
+                .map_or(false, |&b| b != NO_BUCKET && probe[b as usize])
+        };
+        // Start where the upper-layer descent lands if that is in a probed
+        // bucket; otherwise one seed per probed bucket (its live node closest
+        // to the centroid).
+        let descent = self
+            .graph
+            .layer0_entry(&query, &self.metric)
+            .filter(|&e| in_probe(e));
+        let entries: Vec<NodeId> = match descent {
+            Some(e) => vec![e],
+            None => (0..nlist)
+                .filter(|&b| probe[b])
+                .filter_map(|b| {
+                    ivf.seeds[b]
+                        .iter()
+                        .map(|&(_, nid)| nid)
+                        .find(|&nid| !self.graph.nodes[nid].is_deleted())
+                })
+                .collect(),
+        };
+
+        let ef = self.ef.max(k);
+        let mut cand = self
+            .graph
+            .candidates_from(
+                &entries,
+                &query,
+                ef,
+                &self.metric,
+                SearchOpts {
+                    within: Some(&in_probe),
+                    ..SearchOpts::default()
+                },
+            )
+            .unwrap_or_default();
+        if cand.is_empty() {
+            let outside = |nid: NodeId| !in_probe(nid);
+            cand = self
+                .graph
+                .candidates_with(
+                    &query,
+                    ef,
+                    &self.metric,
+                    SearchOpts {
+                        skip: Some(&outside),
+                        ..SearchOpts::default()
+                    },
+                )
+                .unwrap_or_default();
+        }
+        let hits = self.graph.rank(cand, k, self.recent_ties);
+
+        if self.track_access {
+            let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+            self.graph.touch_many(&ids, now_millis());
+        }
+        Ok(hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cosine, HnswBuilder};
+    use rand::Rng;
+
+    /// `n` points scattered around 8 well-separated centres.
+    fn clustered(n: usize, dims: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let centres: Vec<Vec<f32>> = (0..8)
+            .map(|_| (0..dims).map(|_| rng.random::<f32>() - 0.5).collect())
+            .collect();
+        (0..n)
+            .map(|i| {
+                centres[i % 8]
+                    .iter()
+                    .map(|x| x + 0.1 * (rng.random::<f32>() - 0.5))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn index(data: &[Vec<f32>]) -> Hnsw<Cosine> {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(data[0].len())
+            .seed(107)
+            .build()
+            .unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        h
+    }
+
+    #[test]
+    fn search_ivf_requires_training() {
+        let data = clustered(50, 8, 107);
+        let mut h = index(&data);
+        assert!(matches!(
+            h.search_ivf(&data[0], 3, 2),
+            Err(VcalError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            h.train_ivf(0, 50, 1),
+            Err(VcalError::InvalidParameter(_))
+        ));
+        h.train_ivf(4, 50, 1).unwrap();
+        assert_eq!(h.ivf_nlist(), Some(4));
+        h.clear_ivf();
+        assert!(h.search_ivf(&data[0], 3, 2).is_err());
+    }
+
+    #[test]
+    fn search_ivf_stays_inside_probed_buckets() {
+        let data = clustered(800, 16, 109);
+        let mut h = index(&data);
+        h.train_ivf(8, 400, 109).unwrap();
+
+        // Inserts after training are bucketed as they arrive.
+        let extra = clustered(8, 16, 113);
+        for (i, v) in extra.into_iter().enumerate() {
+            h.insert(v, 10_000 + i as u64).unwrap();
+        }
+        let ivf = h.ivf.as_ref().unwrap();
+        assert_eq!(ivf.assign.len(), h.graph.nodes.len());
+
+        let mut self_found = 0;
+        for (i, q) in data.iter().enumerate().step_by(10) {
+            let hits = h.search_ivf(q, 5, 1).unwrap();
+            let home = ivf.nearest(&h.metric, q).0;
+            assert!(hits
+                .iter()
+                .all(|&(id, _)| ivf.assign[h.graph.by_ext[&id]] == home));
+            self_found += usize::from(hits.first().map(|h| h.0) == Some(i as u64));
+        }
+        assert!(self_found >= 76, "self-match {}/80", self_found);
+
+        // Probing every bucket agrees with the plain search on the top hit.
+        for q in data.iter().step_by(25) {
+            assert_eq!(
+                h.search_ivf(q, 1, 8).unwrap()[0].0,
+                h.search(q, 1).unwrap()[0].0
+            );
+        }
+    }
+
+    #[test]
+    fn updates_and_try_inserts_are_bucketed() {
+        let data = clustered(400, 16, 127);
+        let mut h = index(&data);
+        h.train_ivf(8, 400, 127).unwrap();
+        let bucket =
+            |h: &Hnsw<Cosine>, id: u64| h.ivf.as_ref().unwrap().assign[h.graph.by_ext[&id]];
+
+        // Move id 0 onto id 1's cluster: it must follow into id 1's bucket.
+        assert_ne!(bucket(&h, 0), bucket(&h, 1));
+        h.update_vector(0, data[1].clone()).unwrap();
+        assert_eq!(bucket(&h, 0), bucket(&h, 1));
+        let hits = h.search_ivf(&data[1], 2, 1).unwrap();
+        assert!(hits.iter().any(|&(id, _)| id == 0), "{:?}", hits);
+
+        h.try_insert(data[2].clone(), 9_000).unwrap();
+        assert_eq!(bucket(&h, 9_000), bucket(&h, 2));
+        let hits = h.search_ivf(&data[2], 2, 1).unwrap();
+        assert!(hits.iter().any(|&(id, _)| id == 9_000), "{:?}", hits);
+    }
+
+    #[test]
+    fn rebuild_keeps_the_quantizer() {
+        let data = clustered(300, 16, 131);
+        let mut h = index(&data);
+        h.train_ivf(8, 300, 131).unwrap();
+        h.delete(0);
+
+        let r = h.rebuild_with(8, 64, true).unwrap();
+        assert_eq!(r.ivf_nlist(), Some(8));
+        let (ivf, rivf) = (h.ivf.as_ref().unwrap(), r.ivf.as_ref().unwrap());
+        assert_eq!(rivf.assign.len(), r.graph.nodes.len());
+        for id in 1..300u64 {
+            assert_eq!(
+                rivf.assign[r.graph.by_ext[&id]],
+                ivf.assign[h.graph.by_ext[&id]]
+            );
+        }
+        assert_eq!(r.search_ivf(&data[7], 1, 2).unwrap()[0].0, 7);
+    }
+}
@@ -16,46 +16,313 @@
 #![deny(unsafe_code)]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+mod binary;
 mod errors;
+mod frozen;
 mod graph;
 mod math;
 mod node;
 mod params;
 mod rand_level;
+mod searcher;
+mod sparse;
+mod stats;
+#[cfg(test)]
+pub(crate) mod test_util;
+
+#[cfg(feature = "ivf")]
+mod ivf;
+
+#[cfg(feature = "hnswlib_compat")]
+mod hnswlib;
 
 #[cfg(feature = "serde")]
 mod serialize;
 
 #[cfg(feature = "serde")]
-pub use serialize::{from_slice, to_bytes};
+pub use serialize::{
+    from_slice, from_slice_strict, read_snapshot, to_bytes, to_bytes_with_tombstones,
+    write_snapshot,
+};
+
+#[cfg(feature = "compress")]
+pub use serialize::{from_slice_compressed, to_bytes_compressed};
 
+pub use binary::BinaryHnsw;
 pub use errors::{Result, VcalError};
-pub use math::{Cosine, Dot};
-pub use params::HnswBuilder;
-pub use rand_level::draw_level;
+pub use frozen::FrozenHnsw;
+pub use graph::{AuditReport, FinalizeReport};
+pub use math::{
+    simd_active, Blend, Cosine, CosineEps, Dot, HammingBits, Metric, SparseCosine, COSINE_EPS,
+};
+pub use params::{DegreeBudget, HnswBuilder};
+pub use rand_level::{draw_level, Sampler};
+pub use searcher::Searcher;
+pub use sparse::SparseHnsw;
+pub use stats::{IndexStats, NormStats};
 
-/// Public identifier type attached to each vector.
+use node::now_millis;
+use std::borrow::Cow;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// Default identifier type attached to each vector.
 pub type ExternalId = u64;
 /// `(id, distance)` tuple returned by `search`.
-pub type SearchHit = (ExternalId, f32);
+pub type SearchHit<Id = ExternalId> = (Id, f32);
+
+/// Outcome of [`Hnsw::upsert`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upserted {
+    /// The id was not live; a new node was added.
+    Inserted,
+    /// The id was live; its node was replaced by the new vector.
+    Replaced,
+}
+
+/// Bound for external id types. Blanket-implemented, so any
+/// `Copy + Eq + Hash + Debug` type (e.g. `u128` UUIDs) can key an index
+/// via `Hnsw<M, Id>`; snapshots additionally need serde support on `Id`.
+pub trait IdType: Copy + Eq + std::hash::Hash + std::fmt::Debug + Send + Sync + 'static {}
+
+impl<T: Copy + Eq + std::hash::Hash + std::fmt::Debug + Send + Sync + 'static> IdType for T {}
 
 /// Main index structure.
-pub struct Hnsw<M: math::Metric = math::Cosine> {
+pub struct Hnsw<M: math::Metric = math::Cosine, Id: IdType = ExternalId> {
     pub(crate) dims: usize,
     pub(crate) m: usize,
+    pub(crate) m_max0: usize,
     pub(crate) ef: usize,
     pub(crate) efc: usize,
+    pub(crate) efc0: Option<usize>,
+    pub(crate) recent_ties: bool,
+    pub(crate) normalize_query: bool,
+    /// Flush subnormal components to zero in queries and inserted vectors.
+    pub(crate) flush_subnormals: bool,
+    pub(crate) empty_returns_ok: bool,
+    pub(crate) track_access: bool,
+    /// `search` goes brute force while `len() < exact_below` (0 = never).
+    pub(crate) exact_below: usize,
     pub(crate) metric: M,
-    pub(crate) graph: graph::Graph,
+    pub(crate) graph: graph::Graph<Id>,
+    /// Coarse quantizer for `search_ivf`; `None` until `train_ivf`.
+    #[cfg(feature = "ivf")]
+    pub(crate) ivf: Option<ivf::Ivf>,
+}
+
+impl Hnsw<Cosine> {
+    /// A cosine index over `dims`-dimensional vectors with default
+    /// parameters: shorthand for `HnswBuilder::<Cosine>::default().dims(dims)
+    /// .build().unwrap()`. Use [`HnswBuilder`] for anything else.
+    ///
+    /// ```rust
+    /// use vcal_core::Hnsw;
+    ///
+    /// let mut h = Hnsw::new(16);
+    /// h.insert(vec![1.0; 16], 42).unwrap();
+    /// assert_eq!(h.search([1.0; 16], 1).unwrap()[0].0, 42);
+    /// ```
+    ///
+    /// # Panics
+    /// If `dims` is 0.
+    pub fn new(dims: usize) -> Self {
+        Self::with_capacity(dims, 0)
+    }
+
+    /// [`Hnsw::new`] with room for `capacity` inserts reserved up front
+    /// (see [`Hnsw::reserve`]).
+    ///
+    /// # Panics
+    /// If `dims` is 0.
+    pub fn with_capacity(dims: usize, capacity: usize) -> Self {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(dims)
+            .build()
+            .expect("Hnsw::new needs dims > 0");
+        h.reserve(capacity);
+        h
+    }
 }
 
-impl<M: math::Metric> Hnsw<M> {
+impl<M: math::Metric, Id: IdType> Hnsw<M, Id> {
     /// k-NN search with a per-request `ef` override.
     /// - `k`: number of neighbors to return (pass k_expand if you overfetch upstream)
     /// - `ef`: beam width (will be clamped to at least `k` and 1)
+    ///
+    /// Returns at most `k` hits; fewer when the index holds fewer than `k`
+    /// reachable vectors. [`Hnsw::search_checked`] tells an index smaller
+    /// than `k` apart from a beam that came back short.
+    /// Below the builder's `exact_below` size this is [`Hnsw::search_exact`].
     #[inline]
-    pub fn search_with_ef(&self, query: &[f32], k: usize, ef: usize) -> Result<Vec<SearchHit>> {
-        if self.graph.nodes.is_empty() {
+    pub fn search_with_ef(
+        &self,
+        query: impl AsRef<[f32]>,
+        k: usize,
+        ef: usize,
+    ) -> Result<Vec<SearchHit<Id>>> {
+        if self.graph.active < self.exact_below {
+            return self.search_exact(query, k);
+        }
+        let query = match self.prepare_query(query.as_ref())? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        // Ensure ef is sane: at least k and >=1
+        let ef_eff = ef.max(k.max(1));
+
+        let hits = self
+            .graph
+            .knn(&query, k, &self.metric, ef_eff, self.recent_ties);
+
+        // Feed LRU without a write-lock (same as `search`)
+        if self.track_access {
+            let mut ids: Vec<Id> = Vec::with_capacity(hits.len());
+            for (eid, _dist) in &hits {
+                ids.push(*eid);
+            }
+            self.graph.touch_many(&ids, now_millis());
+        }
+
+        Ok(hits)
+    }
+
+    /// `search` that polls `cancel` during the descent and the layer-0
+    /// ef-search and returns `VcalError::Cancelled` once it is set.
+    /// Cancelled searches touch nothing.
+    pub fn search_cancellable(
+        &self,
+        query: &[f32],
+        k: usize,
+        cancel: &AtomicBool,
+    ) -> Result<Vec<SearchHit<Id>>> {
+        let query = match self.prepare_query(query)? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        let cand = self
+            .graph
+            .candidates_with(
+                &query,
+                self.ef.max(k),
+                &self.metric,
+                graph::SearchOpts {
+                    cancel: Some(cancel),
+                    skip: None,
+                    max_visits: None,
+                    within: None,
+                },
+            )
+            .ok_or(VcalError::Cancelled)?;
+        let hits = self.graph.rank(cand, k, self.recent_ties);
+
+        if self.track_access {
+            let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+            self.graph.touch_many(&ids, now_millis());
+        }
+        Ok(hits)
+    }
+
+    /// Low-latency `search`: the upper-layer descent runs as usual, but the
+    /// layer-0 beam stops once `max_layer0_visits` nodes (at least 1) have
+    /// been scored. Latency is then bounded by roughly that many distance
+    /// calls regardless of `ef`, at the cost of recall: the beam may stop
+    /// before reaching the true neighbours, and fewer than `k` hits come back
+    /// when the cap is below `k`. With a cap well above `ef` this is `search`.
+    pub fn search_shallow(
+        &self,
+        query: &[f32],
+        k: usize,
+        max_layer0_visits: usize,
+    ) -> Result<Vec<SearchHit<Id>>> {
+        let query = match self.prepare_query(query)? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        let cand = self
+            .graph
+            .candidates_with(
+                &query,
+                self.ef.max(k),
+                &self.metric,
+                graph::SearchOpts {
+                    cancel: None,
+                    skip: None,
+                    max_visits: Some(max_layer0_visits.max(1)),
+                    within: None,
+                },
+            )
+            .unwrap_or_default();
+        let hits = self.graph.rank(cand, k, self.recent_ties);
+
+        if self.track_access {
+            let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+            self.graph.touch_many(&ids, now_millis());
+        }
+        Ok(hits)
+    }
+
+    /// Lazily yield the ef-search beam in ascending distance order, so callers
+    /// can stop early (`find`, `take_while`). The ef-search itself runs in
+    /// full up front; only the sort is deferred. Yields at most `ef` hits.
+    /// With access tracking on, a hit's `last_hit` is updated when yielded.
+    pub fn search_iter(
+        &self,
+        query: &[f32],
+        ef: usize,
+    ) -> Result<impl Iterator<Item = SearchHit<Id>> + '_> {
+        use ordered_float::OrderedFloat;
+        use std::cmp::Reverse;
+
+        let cand = match self.prepare_query(query)? {
+            Some(q) => self.graph.candidates(&q, ef.max(1), &self.metric),
+            None => Vec::new(),
+        };
+        // Secondary key mirrors `knn`: freshest first under `recent_ties`.
+        let recent = self.recent_ties;
+        let mut heap: std::collections::BinaryHeap<Reverse<(OrderedFloat<f32>, u64, usize)>> = cand
+            .into_iter()
+            .map(|(nid, d)| {
+                let tie = if recent {
+                    u64::MAX - self.graph.nodes[nid].seq
+                } else {
+                    0
+                };
+                Reverse((OrderedFloat(d), tie, nid))
+            })
+            .collect();
+
+        let (track, now) = (self.track_access, now_millis());
+        Ok(std::iter::from_fn(move || {
+            let Reverse((d, _, nid)) = heap.pop()?;
+            let node = &self.graph.nodes[nid];
+            if track {
+                node.touch(now);
+            }
+            Some((node.ext_id, d.into_inner()))
+        }))
+    }
+
+    /// Validate a query against the index. `Ok(None)` means "empty index,
+    /// answer with no hits" (`empty_returns_ok`); an index holding only
+    /// tombstones counts as empty. Otherwise returns the query
+    /// to search with (copied when `flush_subnormals` finds any, and
+    /// L2-normalised under `normalize_query`).
+    fn prepare_query<'q>(&self, query: &'q [f32]) -> Result<Option<Cow<'q, [f32]>>> {
+        // A zero-dim index would otherwise surface as a confusing
+        // `DimensionMismatch { expected: 0, .. }`.
+        if self.dims == 0 {
+            return Err(VcalError::InvalidConfig("index built without dims"));
+        }
+        if self.is_empty() {
+            if self.empty_returns_ok {
+                return Ok(None);
+            }
             return Err(VcalError::EmptyIndex);
         }
         if query.len() != self.dims {
@@ -64,23 +331,25 @@ impl<M: math::Metric> Hnsw<M> {
                 found: query.len(),
             });
         }
-        // Ensure ef is sane: at least k and >=1
-        let ef_eff = ef.max(k.max(1));
-
-        let hits = self.graph.knn(query, k, &self.metric, ef_eff);
-
-        // Feed LRU without a write-lock (same as `search`)
-        let mut ids: Vec<u64> = Vec::with_capacity(hits.len());
-        for (eid, _dist) in &hits {
-            ids.push(*eid);
+        let flushed = if self.flush_subnormals {
+            math::flushed_subnormals(query)
+        } else {
+            None
+        };
+        let query = flushed.map_or(Cow::Borrowed(query), Cow::Owned);
+        if self.normalize_query {
+            return Ok(Some(Cow::Owned(math::l2_normalized(&query))));
         }
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        self.graph.touch_many(&ids, now);
+        Ok(Some(query))
+    }
 
-        Ok(hits)
+    /// `vec` as it should be stored: with subnormals flushed under
+    /// `flush_subnormals` (a new allocation only if any were found).
+    fn stored_vec(&self, vec: Arc<[f32]>) -> Arc<[f32]> {
+        if !self.flush_subnormals {
+            return vec;
+        }
+        math::flushed_subnormals(&vec).map_or(vec, Arc::from)
     }
 
     /// Return the embedding dimensionality this index was built for.
@@ -89,6 +358,27 @@ impl<M: math::Metric> Hnsw<M> {
         self.dims
     }
 
+    /// Stable 64-bit hash of the index's logical content: the parameters
+    /// compared by `==` plus, per live node, its id, vector and per-layer
+    /// neighbour id sets. Independent of NodeId order, tombstones and
+    /// `last_hit`, so `a == b` implies equal checksums (e.g. primary vs
+    /// replica, or before vs after a snapshot roundtrip). O(n·(d + m log m)).
+    pub fn checksum(&self) -> u64 {
+        use std::hash::Hasher;
+        let mut h = graph::Fnv64::default();
+        for p in [self.dims, self.m, self.m_max0, self.ef, self.efc] {
+            h.write_usize(p);
+        }
+        h.write_u64(self.graph.live_checksum());
+        h.finish()
+    }
+
+    /// Name of the configured metric (`Metric::name`), e.g. `"cosine"`.
+    #[inline]
+    pub fn metric_name(&self) -> &'static str {
+        self.metric.name()
+    }
+
     /// Set query-time ef
     #[inline]
     pub fn set_ef(&mut self, ef: usize) {
@@ -101,73 +391,973 @@ impl<M: math::Metric> Hnsw<M> {
         (self.m, self.ef)
     }
 
+    /// Max degree used on layer 0 (upper layers use `m`).
+    #[inline]
+    pub fn m_max0(&self) -> usize {
+        self.m_max0
+    }
+
+    /// Change the construction beam; it applies from the next insert on
+    /// (every insert reads it afresh), and existing links are not revisited.
+    /// As with the builder, inserts widen a beam below `m` to `m`.
+    /// For a one-off override see [`Hnsw::insert_with_efc`].
     #[inline]
     pub fn set_ef_construction(&mut self, efc: usize) {
         self.efc = efc.max(1);
     }
 
-    /// Insert a vector with an external identifier.
-    pub fn insert(&mut self, vec: Vec<f32>, ext_id: ExternalId) -> Result<()> {
+    /// Reset the level sampler's RNG to `seed`, so the next inserts draw
+    /// the same levels as after any other `reseed(seed)` (or a build with
+    /// `.seed(seed)`). For tests and reproducible rebuilds; only the level
+    /// draws depend on it, so it does not touch existing nodes.
+    #[inline]
+    pub fn reseed(&mut self, seed: u64) {
+        self.graph.reseed(seed);
+    }
+
+    /// Construction beam on layer 0 (defaults to `ef_construction`).
+    #[inline]
+    pub fn efc0(&self) -> usize {
+        self.efc0.unwrap_or(self.efc)
+    }
+
+    /// Override the layer-0 construction beam; `None` follows `ef_construction`.
+    #[inline]
+    pub fn set_ef_construction_layer0(&mut self, efc0: Option<usize>) {
+        self.efc0 = efc0.map(|e| e.max(1));
+    }
+
+    /// Insert a vector with an external identifier. Anything convertible
+    /// into `Vec<f32>` is accepted. Vectors are stored as `Arc<[f32]>`, so
+    /// this copies once; [`Hnsw::insert_shared`] stores an existing `Arc`.
+    pub fn insert(&mut self, vec: impl Into<Vec<f32>>, ext_id: Id) -> Result<()> {
+        self.insert_with_level(vec, ext_id).map(|_| ())
+    }
+
+    /// `insert` that reports whether `ext_id` was new or replaced a live
+    /// node, decided in the same `&mut self` call as the write (no gap
+    /// between a `contains` check and the insert).
+    pub fn upsert(&mut self, vec: impl Into<Vec<f32>>, ext_id: Id) -> Result<Upserted> {
+        let existed = self.live_nid(ext_id).is_ok();
+        self.insert(vec, ext_id)?;
+        Ok(if existed {
+            Upserted::Replaced
+        } else {
+            Upserted::Inserted
+        })
+    }
+
+    /// `insert`, returning the level the node was drawn at (0 = layer 0
+    /// only). With a fixed `seed`, recording these reproduces the tower.
+    pub fn insert_with_level(&mut self, vec: impl Into<Vec<f32>>, ext_id: Id) -> Result<usize> {
+        let (efc, efc0) = (self.efc, self.efc0());
+        self.insert_arc(Arc::from(vec.into()), ext_id, efc, efc0)
+    }
+
+    /// `insert` that keeps `vec` itself rather than a copy, so indexes built
+    /// over the same vectors (e.g. filtered sub-indexes of a full one) share
+    /// one heap allocation per vector. `total_bytes` still counts the vector
+    /// in every index holding it: it is what that index keeps alive.
+    pub fn insert_shared(&mut self, vec: Arc<[f32]>, ext_id: Id) -> Result<()> {
+        let (efc, efc0) = (self.efc, self.efc0());
+        self.insert_arc(vec, ext_id, efc, efc0).map(|_| ())
+    }
+
+    /// `insert` from a packed blob of little-endian `f32`s (4 bytes per
+    /// component, on every host), decoded in one pass straight into the
+    /// stored vector with no intermediate `Vec`. A blob that is not exactly
+    /// `dims * 4` bytes fails with `DimensionMismatch`, `found` counting a
+    /// trailing partial component as one.
+    pub fn insert_bytes(&mut self, bytes: &[u8], ext_id: Id) -> Result<()> {
+        const F32: usize = std::mem::size_of::<f32>();
+        if bytes.len() != self.dims * F32 {
+            return Err(VcalError::DimensionMismatch {
+                expected: self.dims,
+                found: (bytes.len() + F32 - 1) / F32,
+            });
+        }
+        let vec: Arc<[f32]> = bytes
+            .chunks_exact(F32)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        self.insert_shared(vec, ext_id)
+    }
+
+    /// `insert_shared` at a fixed top layer instead of a drawn one.
+    pub(crate) fn insert_at_level(
+        &mut self,
+        vec: Arc<[f32]>,
+        ext_id: Id,
+        level: usize,
+    ) -> Result<()> {
+        if vec.len() != self.dims {
+            return Err(VcalError::DimensionMismatch {
+                expected: self.dims,
+                found: vec.len(),
+            });
+        }
+        let efc0 = self.efc0();
+        self.graph.add_at_level(
+            vec,
+            ext_id,
+            level,
+            &self.metric,
+            self.m,
+            self.m_max0,
+            self.efc,
+            efc0,
+        );
+        #[cfg(feature = "ivf")]
+        self.sync_ivf();
+        Ok(())
+    }
+
+    /// `insert` with construction beam `efc` on every layer for this one
+    /// insert, in place of `ef_construction` (and any layer-0 override);
+    /// the index's settings are untouched. For a quality-sensitive batch,
+    /// either call this per vector or `set_ef_construction` around it.
+    /// An `efc` below `m` is widened to `m`.
+    pub fn insert_with_efc(
+        &mut self,
+        vec: impl Into<Vec<f32>>,
+        ext_id: Id,
+        efc: usize,
+    ) -> Result<()> {
+        let efc = efc.max(1);
+        self.insert_arc(Arc::from(vec.into()), ext_id, efc, efc)
+            .map(|_| ())
+    }
+
+    fn insert_arc(
+        &mut self,
+        vec: Arc<[f32]>,
+        ext_id: Id,
+        efc: usize,
+        efc0: usize,
+    ) -> Result<usize> {
+        if vec.len() != self.dims {
+            return Err(VcalError::DimensionMismatch {
+                expected: self.dims,
+                found: vec.len(),
+            });
+        }
+        let vec = self.stored_vec(vec);
+        let lvl = self
+            .graph
+            .add(vec, ext_id, &self.metric, self.m, self.m_max0, efc, efc0);
+        #[cfg(feature = "ivf")]
+        self.sync_ivf();
+        Ok(lvl)
+    }
+
+    /// Transactional `insert`: rejects non-finite components up front, and if
+    /// wiring the node fails midway (a panic, e.g. from a custom metric) the
+    /// index is restored exactly as it was and `VcalError::InsertFailed` is
+    /// returned. Upserts roll back to the previous vector. Costs a copy of
+    /// every node the insert touches; needs `panic = "unwind"`.
+    pub fn try_insert(&mut self, vec: impl Into<Vec<f32>>, ext_id: Id) -> Result<()> {
+        let vec = vec.into();
+        if vec.len() != self.dims {
+            return Err(VcalError::DimensionMismatch {
+                expected: self.dims,
+                found: vec.len(),
+            });
+        }
+        if vec.iter().any(|x| !x.is_finite()) {
+            return Err(VcalError::InvalidParameter(
+                "vector contains NaN or infinite components",
+            ));
+        }
+        let efc0 = self.efc0();
+        let vec = self.stored_vec(vec.into());
+        let res = self.graph.try_add(
+            vec,
+            ext_id,
+            &self.metric,
+            self.m,
+            self.m_max0,
+            self.efc,
+            efc0,
+        );
+        #[cfg(feature = "ivf")]
+        self.sync_ivf();
+        res.map_err(|payload| {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic during insert".to_string());
+            VcalError::InsertFailed(msg)
+        })
+    }
+
+    /// Overwrite the vector stored under `ext_id` in place, keeping its
+    /// NodeId, level and edges. Edges were chosen for the old vector, so
+    /// after a large change call `relink_node` (or search quality for and
+    /// around this node degrades).
+    pub fn update_vector(&mut self, ext_id: Id, vec: Vec<f32>) -> Result<()> {
         if vec.len() != self.dims {
             return Err(VcalError::DimensionMismatch {
                 expected: self.dims,
                 found: vec.len(),
             });
         }
-        self.graph.add(vec, ext_id, &self.metric, self.m, self.efc);
+        let nid = self.live_nid(ext_id)?;
+        self.graph.nodes[nid].vec = self.stored_vec(vec.into()).into();
+        #[cfg(feature = "ivf")]
+        self.rebucket_ivf(nid);
+        Ok(())
+    }
+
+    /// Re-run neighbour selection for `ext_id` from its current vector on
+    /// each of its layers: its old edges (both directions) are dropped and
+    /// new ones chosen as on insert. Unlike delete + insert, the node keeps
+    /// its NodeId, level, `last_hit` and insertion order.
+    pub fn relink_node(&mut self, ext_id: Id) -> Result<()> {
+        let nid = self.live_nid(ext_id)?;
+        let efc0 = self.efc0();
+        self.graph
+            .relink(nid, &self.metric, self.m, self.m_max0, self.efc, efc0);
         Ok(())
     }
 
+    /// Live ids reachable from `ext_id` within `hops` layer-0 edge
+    /// traversals (breadth-first, nearest hop first, link order within a
+    /// hop), excluding `ext_id` itself. Tombstoned nodes are neither
+    /// returned nor walked through. Errors with `UnknownId` if the seed isn't
+    /// live. Exposes graph locality, e.g. for diversity sampling; it says
+    /// nothing about distances.
+    pub fn neighborhood(&self, ext_id: Id, hops: usize) -> Result<Vec<Id>> {
+        let seed = self.live_nid(ext_id)?;
+        let nodes = &self.graph.nodes;
+        let mut seen = std::collections::HashSet::new();
+        seen.insert(seed);
+        let mut frontier = vec![seed];
+        let mut out = Vec::new();
+        for _ in 0..hops {
+            let mut next = Vec::new();
+            for &nid in &frontier {
+                for &nb in self.graph.neighbors(nid, 0) {
+                    if nb < nodes.len() && !nodes[nb].is_deleted() && seen.insert(nb) {
+                        out.push(nodes[nb].ext_id);
+                        next.push(nb);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        Ok(out)
+    }
+
+    fn live_nid(&self, ext_id: Id) -> Result<node::NodeId> {
+        self.graph
+            .by_ext
+            .get(&ext_id)
+            .copied()
+            .filter(|&nid| !self.graph.nodes[nid].is_deleted())
+            .ok_or_else(|| VcalError::unknown_id(ext_id))
+    }
+
+    /// Insert `(vector, id)` pairs until exhausted or `cancel` is set; the
+    /// flag is checked before each insert, so at most one insert runs after
+    /// it is raised. Returns the count inserted, or `VcalError::Cancelled`
+    /// (already-inserted vectors stay in the index). A wrong-length vector
+    /// fails with `DimensionMismatchAt` carrying its position in `items`.
+    pub fn insert_many_cancellable<I>(&mut self, items: I, cancel: &AtomicBool) -> Result<usize>
+    where
+        I: IntoIterator<Item = (Vec<f32>, Id)>,
+    {
+        let mut n = 0;
+        for (vec, id) in items {
+            if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(VcalError::Cancelled);
+            }
+            self.insert(vec, id).map_err(|e| e.at(n))?;
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    /// Merge another index into this one by re-inserting its live vectors
+    /// (in original insertion order, keeping their `last_hit`). Ids present in
+    /// both are upserted, i.e. `other`'s vector wins. Returns the number merged.
+    pub fn merge(&mut self, other: Hnsw<M, Id>) -> Result<usize> {
+        if other.dims != self.dims {
+            return Err(VcalError::DimensionMismatch {
+                expected: self.dims,
+                found: other.dims,
+            });
+        }
+        let mut nodes = other.graph.nodes;
+        nodes.retain(|n| !n.is_deleted());
+        nodes.sort_by_key(|n| n.seq);
+
+        let merged = nodes.len();
+        for n in nodes {
+            let ts = n.last_hit.into_inner();
+            let vec = self.stored_vec(n.vec.to_arc());
+            self.graph.add(
+                vec,
+                n.ext_id,
+                &self.metric,
+                self.m,
+                self.m_max0,
+                self.efc,
+                self.efc0(),
+            );
+            self.graph.touch_many(&[n.ext_id], ts);
+        }
+        #[cfg(feature = "ivf")]
+        self.sync_ivf();
+        Ok(merged)
+    }
+
+    /// A new index over the same live vectors with degree budget `m` and
+    /// construction beam `efc`. Nodes are re-inserted in their original
+    /// order with their `last_hit`, sharing vector allocations with `self`.
+    /// With `keep_levels`, each node keeps its current top layer, so only
+    /// connectivity changes and a recall difference is down to `m`/`efc`
+    /// alone; otherwise levels are drawn afresh for the new `m`, continuing
+    /// from `self`'s level-sampler state (a seeded index rebuilds the same
+    /// way every time).
+    ///
+    /// Reset, because they would override the new parameters: `m_max0`
+    /// becomes `2 * m`, a custom `degree_budget` is dropped, and
+    /// `ef_construction_layer0` is cleared so `efc` applies on every layer
+    /// (widened to `m` when smaller, as with the builder).
+    /// Everything else carries over: `ef`, the metric, the level sampler, the
+    /// query-path flags and, under `ivf`, the trained centroids (nodes are
+    /// re-bucketed as they are inserted).
+    pub fn rebuild_with(&self, m: usize, efc: usize, keep_levels: bool) -> Result<Self>
+    where
+        M: Clone,
+    {
+        if m < 2 || efc == 0 {
+            return Err(VcalError::InvalidParameter(
+                "rebuild_with needs m >= 2 and efc >= 1",
+            ));
+        }
+        let mut graph = graph::Graph::new();
+        graph.sampler = self.graph.sampler;
+        graph.rng = self.graph.rng.clone();
+        let mut out = Hnsw {
+            dims: self.dims,
+            m,
+            m_max0: 2 * m,
+            ef: self.ef,
+            efc,
+            efc0: None,
+            recent_ties: self.recent_ties,
+            normalize_query: self.normalize_query,
+            flush_subnormals: self.flush_subnormals,
+            empty_returns_ok: self.empty_returns_ok,
+            track_access: self.track_access,
+            exact_below: self.exact_below,
+            metric: self.metric.clone(),
+            graph,
+            #[cfg(feature = "ivf")]
+            ivf: self.ivf.as_ref().map(ivf::Ivf::untrained_copy),
+        };
+
+        let mut live: Vec<&node::Node<Id>> = self
+            .graph
+            .nodes
+            .iter()
+            .filter(|n| !n.is_deleted())
+            .collect();
+        live.sort_by_key(|n| n.seq);
+        out.reserve(live.len());
+        for n in live {
+            let vec = n.vec.to_arc();
+            if keep_levels {
+                out.insert_at_level(vec, n.ext_id, n.links.len() - 1)?;
+            } else {
+                out.insert_shared(vec, n.ext_id)?;
+            }
+            out.graph.touch_many(
+                &[n.ext_id],
+                n.last_hit.load(std::sync::atomic::Ordering::Relaxed),
+            );
+        }
+        Ok(out)
+    }
+
     #[inline]
     pub fn params_full(&self) -> (usize, usize, usize) {
         (self.m, self.ef, self.efc)
     }
 
-    /// k-NN search using the index’s default `ef`.
+    /// k-NN search using the index’s default `ef`. The query may be any
+    /// `AsRef<[f32]>`: a slice, `&Vec<f32>`, `Box<[f32]>`, an array, ...
     #[inline]
-    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchHit>> {
+    pub fn search(&self, query: impl AsRef<[f32]>, k: usize) -> Result<Vec<SearchHit<Id>>> {
         self.search_with_ef(query, k, self.ef)
     }
 
+    /// Nearest live vector, or `None` when there is none (e.g. an empty
+    /// index under `empty_returns_ok`). A k = 1 beam: the layer-0 search
+    /// still runs at the configured `ef`, which is what buys recall over a
+    /// pure greedy walk, but it tracks the best hit as a single running
+    /// minimum, skips distance memoisation, and stops once the closest
+    /// unexpanded candidate is farther than the `ef`-th best distance seen,
+    /// where `search` keeps expanding every candidate it admitted. Usually
+    /// the same hit as `search(query, 1)`, at a fraction of the cost; raise
+    /// `ef` if the occasional miss matters (`benches/search_one.rs`).
+    pub fn search_one(&self, query: impl AsRef<[f32]>) -> Result<Option<SearchHit<Id>>> {
+        let query = match self.prepare_query(query.as_ref())? {
+            Some(q) => q,
+            None => return Ok(None),
+        };
+        let hit = if self.graph.active < self.exact_below {
+            self.graph.best(&self.scan_all(&query), self.recent_ties)
+        } else {
+            self.graph
+                .nearest(&query, self.ef, &self.metric, self.recent_ties)
+                .map(|(nid, d)| (self.graph.nodes[nid].ext_id, d))
+        };
+        if let (true, Some((id, _))) = (self.track_access, hit) {
+            self.graph.touch_many(&[id], now_millis());
+        }
+        Ok(hit)
+    }
+
+    /// `search` as parallel arrays: `ids[i]` is at distance `dists[i]`,
+    /// nearest first. The distances form one contiguous `f32` slice for
+    /// vectorised re-ranking.
+    pub fn search_split(&self, query: impl AsRef<[f32]>, k: usize) -> Result<(Vec<Id>, Vec<f32>)> {
+        Ok(self.search(query, k)?.into_iter().unzip())
+    }
+
+    /// `search` into a caller-owned buffer: `out` is cleared, then filled
+    /// with up to `k` hits, so a buffer reused across queries stops
+    /// allocating once it has grown to `k`. On error `out` is left empty.
+    pub fn search_into(&self, query: &[f32], k: usize, out: &mut Vec<SearchHit<Id>>) -> Result<()> {
+        out.clear();
+        if self.graph.active < self.exact_below {
+            out.extend(self.search_exact(query, k)?);
+            return Ok(());
+        }
+        let query = match self.prepare_query(query)? {
+            Some(q) => q,
+            None => return Ok(()),
+        };
+        if k == 0 {
+            return Ok(());
+        }
+        let cand = self.graph.candidates(&query, self.ef.max(k), &self.metric);
+        self.graph.rank_into(cand, k, self.recent_ties, out);
+
+        if self.track_access {
+            self.graph.touch_hits(out, now_millis());
+        }
+        Ok(())
+    }
+
+    /// Every live vector with its exact distance to `pivot`, nearest first.
+    /// Brute force, O(n·d + n log n) time and O(n) memory: meant for offline
+    /// analysis (clustering, audits), not the query hot path. Does not
+    /// update `last_hit`.
+    pub fn iter_by_distance(&self, pivot: &[f32]) -> Result<Vec<SearchHit<Id>>> {
+        let pivot = match self.prepare_query(pivot)? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        let cand = self.scan_all(&pivot);
+        let all = cand.len();
+        Ok(self.graph.rank(cand, all, self.recent_ties))
+    }
+
+    /// Exact k-NN by scanning every live vector, ignoring the graph: perfect
+    /// recall at O(n·d) per query, which beats the beam on small indexes.
+    /// Same empty-index and `last_hit` behaviour as `search`.
+    pub fn search_exact(&self, query: impl AsRef<[f32]>, k: usize) -> Result<Vec<SearchHit<Id>>> {
+        let query = match self.prepare_query(query.as_ref())? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        let hits = self.graph.rank(self.scan_all(&query), k, self.recent_ties);
+        if self.track_access {
+            let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+            self.graph.touch_many(&ids, now_millis());
+        }
+        Ok(hits)
+    }
+
+    /// `(node, distance)` for every live node, unsorted.
+    fn scan_all(&self, query: &[f32]) -> Vec<(node::NodeId, f32)> {
+        let nodes = &self.graph.nodes;
+        let live: Vec<node::NodeId> = (0..nodes.len())
+            .filter(|&nid| !nodes[nid].is_deleted())
+            .collect();
+        let vecs: Vec<&[f32]> = live.iter().map(|&nid| &nodes[nid].vec[..]).collect();
+        let mut dists = vec![0.0_f32; live.len()];
+        self.metric.distance_batch(query, &vecs, &mut dists);
+        live.into_iter().zip(dists).collect()
+    }
+
+    /// Search a batch of nearby queries with one shared upper-layer descent:
+    /// the descent runs once for the batch centroid, and every query starts
+    /// its own layer-0 beam from the node it reaches. Saves
+    /// `(queries - 1)` descents but trades a little recall, since a query
+    /// far from the centroid starts its beam farther from its answers; use
+    /// it only when the queries are genuinely close (pagination windows,
+    /// re-phrasings). Returns one hit list per query, in order.
+    pub fn search_batch_clustered<Q: AsRef<[f32]>>(
+        &self,
+        queries: &[Q],
+        k: usize,
+    ) -> Result<Vec<Vec<SearchHit<Id>>>> {
+        let mut prepared = Vec::with_capacity(queries.len());
+        for q in queries {
+            match self.prepare_query(q.as_ref())? {
+                Some(q) => prepared.push(q),
+                None => return Ok(vec![Vec::new(); queries.len()]),
+            }
+        }
+        if prepared.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut centroid = vec![0.0f32; self.dims];
+        for q in &prepared {
+            for (c, x) in centroid.iter_mut().zip(q.iter()) {
+                *c += x;
+            }
+        }
+        let n = prepared.len() as f32;
+        centroid.iter_mut().for_each(|c| *c /= n);
+
+        let entry = match self.graph.layer0_entry(&centroid, &self.metric) {
+            Some(e) if k > 0 => e,
+            _ => return Ok(vec![Vec::new(); queries.len()]),
+        };
+        let ef = self.ef.max(k);
+        let now = now_millis();
+        Ok(prepared
+            .iter()
+            .map(|q| {
+                let cand = self
+                    .graph
+                    .candidates_from(&[entry], q, ef, &self.metric, graph::SearchOpts::default())
+                    .unwrap_or_default();
+                let hits = self.graph.rank(cand, k, self.recent_ties);
+                if self.track_access {
+                    let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+                    self.graph.touch_many(&ids, now);
+                }
+                hits
+            })
+            .collect())
+    }
+
+    /// Distance of the `k`-th nearest hit, or `None` when fewer than `k`
+    /// are found (or `k == 0`): the same value as
+    /// `search(query, k)?.last()`, without building the hit list, for
+    /// calibrating `max_distance` thresholds. Does not update `last_hit`.
+    pub fn kth_distance(&self, query: impl AsRef<[f32]>, k: usize) -> Result<Option<f32>> {
+        let query = match self.prepare_query(query.as_ref())? {
+            Some(q) => q,
+            None => return Ok(None),
+        };
+        if k == 0 {
+            return Ok(None);
+        }
+        let mut cand = if self.graph.active < self.exact_below {
+            self.scan_all(&query)
+        } else {
+            self.graph.candidates(&query, self.ef.max(k), &self.metric)
+        };
+        if cand.len() < k {
+            return Ok(None);
+        }
+        let (_, kth, _) = cand.select_nth_unstable_by(k - 1, |a, b| {
+            a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(Some(kth.1))
+    }
+
+    /// `search` with scores instead of distances: each hit carries
+    /// [`Metric::normalized_similarity`] of its distance, in `[0, 1]` and
+    /// descending (for cosine, `1 - dist / 2`: identical = 1, orthogonal = 0.5).
+    pub fn search_similarity(&self, query: impl AsRef<[f32]>, k: usize) -> Result<Vec<(Id, f32)>> {
+        let mut hits = self.search(query, k)?;
+        for hit in &mut hits {
+            hit.1 = self.metric.normalized_similarity(hit.1);
+        }
+        Ok(hits)
+    }
+
+    /// `search` that drops every hit farther than `max_distance`, so it may
+    /// return fewer than `k` (possibly none). This is a filter over the
+    /// finished beam, not part of it: the beam runs exactly as in `search`
+    /// (same cost), then out-of-range candidates are removed before ranking
+    /// and are not touched for LRU. Admitting only in-range nodes to the beam
+    /// would change nothing, since they are its closest entries anyway and
+    /// are never displaced by farther ones.
+    pub fn search_thresholded(
+        &self,
+        query: impl AsRef<[f32]>,
+        k: usize,
+        max_distance: f32,
+    ) -> Result<Vec<SearchHit<Id>>> {
+        let query = match self.prepare_query(query.as_ref())? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        let mut cand = self.graph.candidates(&query, self.ef.max(k), &self.metric);
+        cand.retain(|&(_, d)| d <= max_distance);
+        let hits = self.graph.rank(cand, k, self.recent_ties);
+
+        if self.track_access {
+            let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+            self.graph.touch_many(&ids, now_millis());
+        }
+        Ok(hits)
+    }
+
+    /// Dedup check: the id of the nearest stored vector if its distance to
+    /// `vec` is at most `threshold` (so `0.0` matches exact duplicates), else
+    /// `None`. One `k = 1` search; an empty index yields `Ok(None)`.
+    pub fn contains_near(&self, vec: &[f32], threshold: f32) -> Result<Option<Id>> {
+        let hits = match self.search(vec, 1) {
+            Err(VcalError::EmptyIndex) => return Ok(None),
+            r => r?,
+        };
+        Ok(hits
+            .first()
+            .filter(|&&(_, d)| d <= threshold)
+            .map(|&(id, _)| id))
+    }
+
+    /// Like [`Hnsw::search`], but also reports whether `k` exceeds the number
+    /// of live vectors (`truncated == true`: the index ran out of vectors, so
+    /// no more results exist). A short result with `truncated == false` is
+    /// an approximate miss, e.g. live nodes the beam could not reach.
+    #[inline]
+    pub fn search_checked(
+        &self,
+        query: impl AsRef<[f32]>,
+        k: usize,
+    ) -> Result<(Vec<SearchHit<Id>>, bool)> {
+        let hits = self.search(query, k)?;
+        let truncated = k > self.len();
+        Ok((hits, truncated))
+    }
+
+    /// "More like `ext_id`": search with the stored vector of `ext_id` and
+    /// return up to `k` hits, never including `ext_id` itself.
+    /// Errors with `VcalError::UnknownId` if the id is not live.
+    pub fn search_by_id(&self, ext_id: Id, k: usize) -> Result<Vec<SearchHit<Id>>> {
+        let nid = self.live_nid(ext_id)?;
+        self.search_excluding(&self.graph.nodes[nid].vec, k, &[ext_id])
+    }
+
+    /// k-NN search that never returns any id in `exclude` ("more like this,
+    /// but not these"). Excluded nodes are still traversed, so they keep the
+    /// graph connected and the remaining results lose no recall. Meant for
+    /// small denylists; unknown ids are ignored.
+    pub fn search_excluding(
+        &self,
+        query: &[f32],
+        k: usize,
+        exclude: &[Id],
+    ) -> Result<Vec<SearchHit<Id>>> {
+        let query = match self.prepare_query(query)? {
+            Some(q) => q,
+            None => return Ok(Vec::new()),
+        };
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        let skip_nids: Vec<usize> = exclude
+            .iter()
+            .filter_map(|id| self.graph.by_ext.get(id).copied())
+            .collect();
+        let skip = |nid: usize| skip_nids.contains(&nid);
+        let cand = self
+            .graph
+            .candidates_with(
+                &query,
+                self.ef.max(k),
+                &self.metric,
+                graph::SearchOpts {
+                    cancel: None,
+                    skip: Some(&skip),
+                    max_visits: None,
+                    within: None,
+                },
+            )
+            .unwrap_or_default();
+        let hits = self.graph.rank(cand, k, self.recent_ties);
+
+        if self.track_access {
+            let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+            self.graph.touch_many(&ids, now_millis());
+        }
+        Ok(hits)
+    }
+
+    /// Page in every live vector and adjacency list before a query burst.
+    /// Returns the number of bytes touched.
+    #[inline]
+    pub fn warm(&self) -> usize {
+        self.graph.warm()
+    }
+
+    /// Construction sanity check: search for `sample` randomly chosen stored
+    /// vectors (k = 1) and return the fraction that find themselves as the top
+    /// hit. Values well below 1.0 point at orphans or a broken graph.
+    ///
+    /// Does not touch LRU timestamps. Returns 1.0 on an empty index.
+    pub fn self_recall(&self, sample: usize, seed: u64) -> f32 {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let live: Vec<usize> = (0..self.graph.nodes.len())
+            .filter(|&nid| !self.graph.nodes[nid].is_deleted())
+            .collect();
+        let n = sample.min(live.len());
+        if n == 0 {
+            return 1.0;
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut found = 0usize;
+        for i in rand::seq::index::sample(&mut rng, live.len(), n) {
+            let node = &self.graph.nodes[live[i]];
+            let hits = self.graph.knn(&node.vec, 1, &self.metric, self.ef, false);
+            if hits.first().map(|h| h.0) == Some(node.ext_id) {
+                found += 1;
+            }
+        }
+        found as f32 / n as f32
+    }
+
+    /// Connected components of the live layer-0 graph (edges taken as
+    /// undirected); 1 for a healthy non-empty index, 0 when empty. Vectors
+    /// outside the entry point's component can never be returned, so a
+    /// count above 1 means silently lost recall (`finalize` re-links
+    /// orphans but does not merge larger fragments). O(n·m).
+    pub fn connected_components(&self) -> usize {
+        self.graph.connected_components()
+    }
+
+    /// `connected_components() <= 1`.
+    pub fn is_connected(&self) -> bool {
+        self.connected_components() <= 1
+    }
+
+    /// Read-only graph health check; see [`AuditReport`].
+    pub fn audit(&self) -> AuditReport {
+        self.graph.audit()
+    }
+
+    /// One-shot maintenance after bulk ingestion: drops dangling edges,
+    /// prunes over-degree nodes, re-links layer-0 orphans, and recomputes
+    /// `total_bytes` and the entry point.
+    pub fn finalize(&mut self) -> FinalizeReport {
+        self.graph
+            .finalize(&self.metric, self.m, self.m_max0, self.efc0())
+    }
+
+    /// Min/max/mean L2 norm over live vectors. Single O(n·d) pass; all
+    /// fields are zero on an empty index.
+    pub fn norm_stats(&self) -> NormStats {
+        let mut st = NormStats {
+            count: 0,
+            min: f32::INFINITY,
+            max: 0.0,
+            mean: 0.0,
+        };
+        let mut sum = 0.0_f64;
+        for n in self.graph.nodes.iter().filter(|n| !n.is_deleted()) {
+            let norm = n.vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+            st.count += 1;
+            st.min = st.min.min(norm);
+            st.max = st.max.max(norm);
+            sum += norm as f64;
+        }
+        if st.count == 0 {
+            return NormStats::default();
+        }
+        st.mean = (sum / st.count as f64) as f32;
+        st
+    }
+
     /// Expose basic stats for eviction/monitoring.
     #[inline]
     pub fn stats(&self) -> (usize, usize) {
         self.graph.stats()
     }
 
-    /// Evict by LRU until caps are satisfied (soft cap helper).
+    /// Index `l` holds the number of live nodes whose top layer is `l`.
+    /// Expect geometric decay (ratio ~`1/m`); everything on layer 0 points
+    /// at a broken level sampler.
+    pub fn level_histogram(&self) -> Vec<usize> {
+        let mut hist = vec![0usize; self.graph.max_level + 1];
+        for n in self.graph.nodes.iter().filter(|n| !n.is_deleted()) {
+            let top = n.links.len().saturating_sub(1);
+            if top >= hist.len() {
+                hist.resize(top + 1, 0);
+            }
+            hist[top] += 1;
+        }
+        hist
+    }
+
+    /// Named alternative to `stats()` / `params_full()` for monitoring.
+    pub fn stats_detailed(&self) -> IndexStats {
+        let (active, total_bytes) = self.graph.stats();
+        let node_count = self.graph.nodes.len();
+        IndexStats {
+            active,
+            tombstones: node_count - active,
+            node_count,
+            total_bytes,
+            dims: self.dims,
+            m: self.m,
+            m_max0: self.m_max0,
+            ef: self.ef,
+            efc: self.efc,
+            max_level: self.graph.max_level,
+        }
+    }
+
+    /// Evict by LRU until caps are satisfied (soft cap helper). Ties on
+    /// `last_hit` (same millisecond, e.g. a batch insert, or v1 snapshots
+    /// whose second timestamps were scaled up) go oldest-inserted first.
     pub fn evict_lru_until(
         &mut self,
         max_vecs: Option<usize>,
         max_bytes: Option<usize>,
     ) -> (usize, usize) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        self.graph.evict_lru_until(max_vecs, max_bytes, now)
+        self.graph
+            .evict_lru_until(max_vecs, max_bytes, now_millis())
+    }
+
+    /// Set `ext_id`'s `last_hit` (Unix milliseconds) directly, e.g. to
+    /// restore access recency from external analytics after a snapshot load.
+    /// Returns `false` if the id is not live. Takes `&self`: the stamp is
+    /// atomic, as it is for searches.
+    pub fn set_last_hit(&self, ext_id: Id, ts_ms: u64) -> bool {
+        match self.live_nid(ext_id) {
+            Ok(nid) => {
+                self.graph.nodes[nid].touch(ts_ms);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// `ext_id`'s `last_hit` in Unix milliseconds, or `None` if not live.
+    pub fn last_hit(&self, ext_id: Id) -> Option<u64> {
+        let nid = self.live_nid(ext_id).ok()?;
+        Some(
+            self.graph.nodes[nid]
+                .last_hit
+                .load(std::sync::atomic::Ordering::Relaxed),
+        )
+    }
+
+    /// `evict_lru_until` in bounded increments: evicts at most `budget`
+    /// nodes and returns `(evicted, more)`, where `more` means the caps are
+    /// still exceeded. Call repeatedly (e.g. once per tick) to keep eviction
+    /// off the latency-critical path. Each call costs one O(n) scan.
+    pub fn evict_lru_step(
+        &mut self,
+        max_vecs: Option<usize>,
+        max_bytes: Option<usize>,
+        budget: usize,
+    ) -> (usize, bool) {
+        self.graph.evict_lru_step(max_vecs, max_bytes, budget)
     }
 
     /// Idempotent delete by external id. Returns true if something was removed.
     #[inline]
-    pub fn delete(&mut self, ext_id: ExternalId) -> bool {
+    pub fn delete(&mut self, ext_id: Id) -> bool {
         self.graph.delete(ext_id)
     }
 
+    /// Delete a batch of ids with one repair pass (cheaper than looping
+    /// `delete`). Returns the number actually removed.
+    #[inline]
+    pub fn delete_many(&mut self, ext_ids: &[Id]) -> usize {
+        self.graph.delete_many(ext_ids)
+    }
+
+    /// Delete every live id for which `pred` holds (e.g. one tenant's id
+    /// range), with the single repair pass of `delete_many`. Returns the
+    /// number removed. `pred` sees each live id once, in no fixed order.
+    pub fn delete_matching<F: FnMut(Id) -> bool>(&mut self, mut pred: F) -> usize {
+        let victims: Vec<Id> = self
+            .graph
+            .nodes
+            .iter()
+            .filter(|n| !n.is_deleted() && pred(n.ext_id))
+            .map(|n| n.ext_id)
+            .collect();
+        self.delete_many(&victims)
+    }
+
+    /// Collapse near-duplicates: visiting live nodes in ascending id order,
+    /// a node is removed if a surviving node with a smaller id lies within
+    /// `threshold` (strictly less) of it, so each cluster keeps its smallest
+    /// id. Candidates come from one graph search per node at the index's
+    /// `ef`, so this costs O(n · search) and only sees duplicates the beam
+    /// reaches; removal is a single `delete_many`. Returns the count removed.
+    pub fn dedup_near(&mut self, threshold: f32) -> usize
+    where
+        Id: Ord,
+    {
+        let nodes = &self.graph.nodes;
+        let mut order: Vec<node::NodeId> = (0..nodes.len())
+            .filter(|&nid| !nodes[nid].is_deleted())
+            .collect();
+        order.sort_by_key(|&nid| nodes[nid].ext_id);
+
+        let mut redundant = vec![false; nodes.len()];
+        let mut victims = Vec::new();
+        for nid in order {
+            let id = nodes[nid].ext_id;
+            let dup = self
+                .graph
+                .candidates(&nodes[nid].vec, self.ef, &self.metric)
+                .into_iter()
+                .any(|(c, d)| d < threshold && !redundant[c] && nodes[c].ext_id < id);
+            if dup {
+                redundant[nid] = true;
+                victims.push(id);
+            }
+        }
+        self.delete_many(&victims)
+    }
+
     /// Check whether an id exists.
     #[inline]
-    pub fn contains(&self, ext_id: ExternalId) -> bool {
+    pub fn contains(&self, ext_id: Id) -> bool {
         self.graph.contains_ext(ext_id)
     }
 
     /// TTL sweep: evict nodes whose last_hit is older than `ttl_secs`.
     #[inline]
     pub fn evict_ttl(&mut self, ttl_secs: u64) -> (usize, usize) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        self.graph.evict_ttl(ttl_secs, now)
+        self.evict_ttl_millis(ttl_secs.saturating_mul(1000))
+    }
+
+    /// TTL sweep at millisecond resolution (`last_hit` is kept in ms).
+    #[inline]
+    pub fn evict_ttl_millis(&mut self, ttl_ms: u64) -> (usize, usize) {
+        self.graph.evict_ttl(ttl_ms, now_millis())
+    }
+
+    /// `evict_ttl_millis` that evicts at most `budget` expired nodes and
+    /// returns `(evicted, more)`, where `more` means expired nodes remain.
+    pub fn evict_ttl_step(&mut self, ttl_ms: u64, budget: usize) -> (usize, bool) {
+        self.graph.evict_ttl_step(ttl_ms, now_millis(), budget)
     }
 
     /// Convenience: number of active vectors.
@@ -176,36 +1366,227 @@ impl<M: math::Metric> Hnsw<M> {
         self.stats().0
     }
 
+    /// True when the index holds no active vectors.
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
-    /// Convenience: approximate total bytes of active nodes.
+    /// Number of nodes the index can hold before node storage reallocates.
     #[inline]
-    pub fn total_bytes(&self) -> usize {
-        self.stats().1
+    pub fn capacity(&self) -> usize {
+        self.graph.nodes.capacity()
     }
 
-    // ------------------------------------------------------------------
-    // Snapshot helpers (enabled with `serde`)
-    // ------------------------------------------------------------------
+    /// Reserve room for `additional` more inserts ahead of an ingestion burst,
+    /// avoiding reallocation churn while it runs.
+    pub fn reserve(&mut self, additional: usize) {
+        self.graph.reserve(additional);
+    }
+
+    /// Move every live vector into one contiguous arena, in NodeId (roughly
+    /// insertion) order, instead of one heap allocation each, for cache
+    /// locality in the distance loops of read-mostly indexes; run it after
+    /// a bulk load. Later inserts and updates allocate per vector again,
+    /// and deleted or replaced vectors keep their arena space, until the
+    /// next call, which is also how the arena is compacted. Copies every
+    /// vector once (old and new storage coexist until it returns), and
+    /// vectors added by `insert_shared` stop sharing the caller's
+    /// allocation. Returns the arena length in floats. [`FrozenHnsw`]
+    /// always stores its vectors this way.
+    pub fn pack_vectors(&mut self) -> usize {
+        self.graph.pack_vectors()
+    }
+
+    /// Rebuild the per-level node registry from live nodes and release its
+    /// spare capacity. Deletes remove registry entries in O(1) but leave
+    /// their capacity behind; run this after heavy churn. O(nodes).
+    pub fn compact_levels(&mut self) {
+        self.graph.compact_levels();
+    }
+
+    /// Approximate resident bytes per vector: `dims` components, the
+    /// index's current average number of neighbour entries per live node,
+    /// and fixed per-node overhead (node struct, id map and registry
+    /// entries) that `total_bytes` leaves out. Before the first insert the
+    /// degree is taken from [`HnswBuilder::estimate_bytes`]'s assumption.
+    pub fn estimated_bytes_per_vector(&self) -> usize {
+        let live = self.graph.nodes.iter().filter(|n| !n.is_deleted());
+        let (mut count, mut links) = (0usize, 0usize);
+        for n in live {
+            count += 1;
+            links += n.links.iter().map(|l| l.len()).sum::<usize>();
+        }
+        let degree = if count == 0 {
+            let m = self.m as f64;
+            self.m_max0 as f64 + m / (m - 1.0)
+        } else {
+            links as f64 / count as f64
+        };
+        node::estimate_node_bytes::<Id>(self.dims, degree)
+    }
+
+    /// Convenience: approximate total bytes of active nodes.
+    #[inline]
+    pub fn total_bytes(&self) -> usize {
+        self.stats().1
+    }
+
+    // ------------------------------------------------------------------
+    // Snapshot helpers (enabled with `serde`)
+    // ------------------------------------------------------------------
 
     #[cfg(feature = "serde")]
     /// Serialise index to bytes (`serde_json` by default).
     /// Note: `vcal_core::to_bytes(&hnsw)` is also available as a free function.
-    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>>
+    where
+        Id: serde::Serialize,
+    {
         serialize::to_bytes(self)
     }
 
+    #[cfg(feature = "serde")]
+    /// Serialise index keeping deleted ids as tombstone entries (no vectors),
+    /// so consumers can propagate removals. See [`Hnsw::tombstones`].
+    pub fn to_bytes_with_tombstones(&self) -> Result<Vec<u8>>
+    where
+        Id: serde::Serialize,
+    {
+        serialize::to_bytes_with_tombstones(self)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Stream the snapshot into `w` without materialising it in memory.
+    /// Writer failures surface as `VcalError::Io`.
+    pub fn write_snapshot<W: std::io::Write>(&self, w: &mut W) -> Result<()>
+    where
+        Id: serde::Serialize,
+    {
+        serialize::write_snapshot(self, w)
+    }
+
     #[cfg(feature = "serde")]
     /// Restore index from bytes.
     /// Note: `vcal_core::from_slice::<M>(bytes)` is also available as a free function.
     pub fn from_slice(bytes: &[u8]) -> Result<Self>
     where
         M: Default,
+        Id: serde::de::DeserializeOwned,
+    {
+        serialize::from_slice_ids(bytes)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Restore index from bytes without repairing it: any broken invariant
+    /// (dangling edge, duplicate ext_id, layer mismatch, ...) is reported as
+    /// `VcalError::Serialize` instead of being silently fixed.
+    pub fn from_slice_strict(bytes: &[u8]) -> Result<Self>
+    where
+        M: Default,
+        Id: serde::de::DeserializeOwned,
+    {
+        serialize::from_slice_strict_ids(bytes)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Parse a snapshot and report its damage ([`AuditReport`]) without
+    /// loading or repairing it, e.g. to gate `from_slice` on a health check.
+    pub fn audit_snapshot(bytes: &[u8]) -> Result<AuditReport>
+    where
+        Id: serde::de::DeserializeOwned,
+    {
+        serialize::audit_snapshot_ids::<Id>(bytes)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Restore index incrementally from a reader (file, socket, decoder).
+    pub fn read_snapshot<R: std::io::Read>(r: R) -> Result<Self>
+    where
+        M: Default,
+        Id: serde::de::DeserializeOwned,
+    {
+        serialize::read_snapshot_ids(r)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Insert every `{"id": .., "vec": [..]}` line of a JSONL stream
+    /// (blank lines skipped), returning how many were inserted. Stops at the
+    /// first bad line, keeping earlier ones: malformed JSON is
+    /// `VcalError::Serialize` naming the 1-based line, a wrong-length vector
+    /// `DimensionMismatchAt` with the 0-based line as `index`.
+    pub fn import_jsonl<R: std::io::Read>(&mut self, r: R) -> Result<usize>
+    where
+        Id: serde::de::DeserializeOwned,
+    {
+        serialize::import_jsonl(self, r)
+    }
+
+    #[cfg(feature = "compress")]
+    /// Serialise index to zstd-compressed bytes at `level`.
+    pub fn to_bytes_compressed(&self, level: i32) -> Result<Vec<u8>>
+    where
+        Id: serde::Serialize,
+    {
+        serialize::to_bytes_compressed(self, level)
+    }
+
+    #[cfg(feature = "compress")]
+    /// Restore index from compressed or plain snapshot bytes (auto-detected).
+    pub fn from_slice_compressed(bytes: &[u8]) -> Result<Self>
+    where
+        M: Default,
+        Id: serde::de::DeserializeOwned,
     {
-        serialize::from_slice::<M>(bytes)
+        serialize::from_slice_compressed_ids(bytes)
+    }
+}
+
+impl<M: math::Metric, Id: IdType + Ord> Hnsw<M, Id> {
+    /// Ids that were deleted and not re-inserted (tombstones still held by
+    /// the graph, e.g. restored from `to_bytes_with_tombstones`).
+    pub fn tombstones(&self) -> Vec<Id> {
+        let mut out: Vec<Id> = self
+            .graph
+            .nodes
+            .iter()
+            .filter(|n| n.is_deleted() && !self.graph.by_ext.contains_key(&n.ext_id))
+            .map(|n| n.ext_id)
+            .collect();
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+}
+
+/// Summary only: parameters plus graph counters, never vectors or links.
+impl<M: math::Metric, Id: IdType> std::fmt::Debug for Hnsw<M, Id> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hnsw")
+            .field("dims", &self.dims)
+            .field("m", &self.m)
+            .field("m_max0", &self.m_max0)
+            .field("ef", &self.ef)
+            .field("efc", &self.efc)
+            .field("efc0", &self.efc0())
+            .field("graph", &self.graph)
+            .finish()
+    }
+}
+
+/// Structural equality: same `dims`, `m`, `m_max0`, `ef`, `efc`, and the same
+/// live `(ext_id, vector, neighbour ext ids per layer)` entries. Matching is by
+/// ext id, so an index equals its snapshot round-trip even though NodeIds are
+/// renumbered. Ignored: the metric value, `efc0` and query flags, tombstones,
+/// `last_hit`, insertion order and the order of neighbour lists.
+impl<M: math::Metric, Id: IdType> PartialEq for Hnsw<M, Id> {
+    fn eq(&self, other: &Self) -> bool {
+        self.dims == other.dims
+            && self.m == other.m
+            && self.m_max0 == other.m_max0
+            && self.ef == other.ef
+            && self.efc == other.efc
+            && self.graph.same_live_graph(&other.graph)
     }
 }
 
@@ -213,8 +1594,12 @@ impl<M: math::Metric> Hnsw<M> {
 // Smoke-tests (compile-time verified)
 // ----------------------------------------------------------------------
 #[cfg(test)]
+// Query call sites keep the `&[..]` / `&Vec` form that predates
+// `impl AsRef<[f32]>`; they double as source-compatibility checks.
+#[allow(clippy::needless_borrows_for_generic_args)]
 mod tests {
     use super::*;
+    use crate::test_util::rand_vecs;
 
     #[test]
     fn smoke_insert_search() {
@@ -261,4 +1646,2183 @@ mod tests {
             Ok(_) => panic!("expected InvalidDimensions error"),
         }
     }
+
+    #[test]
+    fn m_max0_defaults_to_twice_m() {
+        let h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .m(6)
+            .build()
+            .unwrap();
+        assert_eq!(h.m_max0(), 12);
+        let h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .m(6)
+            .m_max0(9)
+            .build()
+            .unwrap();
+        assert_eq!(h.m_max0(), 9);
+    }
+
+    #[test]
+    fn layer0_degree_uses_m_max0_budget() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(4)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(300, 8, 7).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        let mut max0 = 0;
+        for n in &h.graph.nodes {
+            max0 = max0.max(n.links[0].len());
+            assert!(n.links[0].len() <= 8);
+            for l in &n.links[1..] {
+                assert!(l.len() <= 4);
+            }
+        }
+        assert!(max0 > 4, "layer 0 never exceeded the upper-layer budget");
+    }
+
+    #[test]
+    fn search_checked_reports_truncation() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        for i in 0..3u64 {
+            h.insert(vec![1.0, i as f32, 0.5, 0.0], i).unwrap();
+        }
+        let (hits, truncated) = h.search_checked(&[1.0, 0.0, 0.5, 0.0], 10).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert!(truncated);
+
+        let (hits, truncated) = h.search_checked(&[1.0, 0.0, 0.5, 0.0], 2).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn search_checked_short_beam_is_not_truncation() {
+        let data = rand_vecs(50, 8, 104);
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(104)
+            .build()
+            .unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        // Cut node 7 out of the graph: the beam can no longer return all 50.
+        let g = &mut h.graph;
+        let lost = g.by_ext[&7];
+        assert_ne!(g.entry, Some(lost));
+        for n in g.nodes.iter_mut() {
+            for adj in n.links.iter_mut() {
+                adj.retain(|x| *x != lost);
+            }
+        }
+
+        let (hits, truncated) = h.search_checked(&data[7], h.len()).unwrap();
+        assert!(hits.len() < h.len());
+        assert!(!truncated);
+        let (_, truncated) = h.search_checked(data[7].clone(), h.len() + 1).unwrap();
+        assert!(truncated);
+    }
+
+    #[test]
+    fn seeded_indexes_stay_identical_after_deletes() {
+        let data = rand_vecs(200, 8, 3);
+        let build = || {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(8)
+                .seed(42)
+                .build()
+                .unwrap();
+            for (i, v) in data.iter().enumerate() {
+                h.insert(v.clone(), i as u64).unwrap();
+            }
+            for id in (0..200u64).step_by(3) {
+                h.delete(id);
+            }
+            h
+        };
+        let (a, b) = (build(), build());
+
+        assert_eq!(a.graph.entry, b.graph.entry);
+        assert_eq!(a.graph.max_level, b.graph.max_level);
+        assert_eq!(a.graph.levels, b.graph.levels);
+        for (na, nb) in a.graph.nodes.iter().zip(&b.graph.nodes) {
+            assert_eq!(na.ext_id, nb.ext_id);
+            assert_eq!(na.links, nb.links);
+        }
+    }
+
+    #[test]
+    fn entry_after_deleting_entry_is_smallest_valid_on_top_level() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(9)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(100, 8, 5).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        let old = h.graph.entry.unwrap();
+        h.delete(h.graph.nodes[old].ext_id);
+
+        let new = h.graph.entry.unwrap();
+        let top = h.graph.nodes[new].links.len() - 1;
+        let expected = h.graph.levels[top].iter().copied().min().unwrap();
+        assert_eq!(new, expected);
+    }
+
+    #[test]
+    fn warm_touches_all_live_bytes() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        assert_eq!(h.warm(), 0);
+        for (i, v) in rand_vecs(50, 8, 11).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        h.delete(0);
+        assert_eq!(h.warm(), h.total_bytes());
+    }
+
+    #[test]
+    fn self_recall_is_high_on_healthy_graph() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(16)
+            .seed(1)
+            .build()
+            .unwrap();
+        assert_eq!(h.self_recall(10, 0), 1.0);
+        for (i, v) in rand_vecs(500, 16, 2).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        assert!(h.self_recall(100, 7) > 0.95);
+        assert_eq!(h.self_recall(100, 7), h.self_recall(100, 7));
+    }
+
+    #[test]
+    fn delete_many_matches_looped_delete() {
+        let data = rand_vecs(300, 8, 4);
+        let build = || {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(8)
+                .seed(5)
+                .build()
+                .unwrap();
+            for (i, v) in data.iter().enumerate() {
+                h.insert(v.clone(), i as u64).unwrap();
+            }
+            h
+        };
+        let ids: Vec<u64> = (0..300u64)
+            .filter(|i| i % 4 == 1)
+            .chain([1, 9999])
+            .collect();
+
+        let mut a = build();
+        let looped = ids.iter().filter(|&&id| a.delete(id)).count();
+        let mut b = build();
+        let repairs = graph::MASS_REPAIRS.with(|n| n.get());
+        assert_eq!(b.delete_many(&ids), looped);
+        assert_eq!(graph::MASS_REPAIRS.with(|n| n.get()), repairs + 1);
+
+        assert_eq!(a.len(), b.len());
+        for id in 0..300u64 {
+            assert_eq!(a.contains(id), b.contains(id));
+        }
+        let q = &data[0];
+        assert_eq!(a.search(q, 10).unwrap(), b.search(q, 10).unwrap());
+    }
+
+    #[test]
+    fn finalize_prunes_and_relinks_orphans() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(4)
+            .seed(3)
+            .build()
+            .unwrap();
+        let data = rand_vecs(200, 8, 6);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        assert_eq!(h.finalize(), FinalizeReport::default());
+
+        // Orphan node 10 on layer 0 and overload node 20.
+        let orphan = h.graph.by_ext[&10];
+        for n in h.graph.nodes.iter_mut() {
+            n.links[0].retain(|x| *x != orphan);
+        }
+        let fat = h.graph.by_ext[&20];
+        let extra: Vec<usize> = (0..h.graph.nodes.len())
+            .filter(|&x| x != fat && !h.graph.nodes[fat].links[0].contains(&x))
+            .take(12)
+            .collect();
+        h.graph.nodes[fat].links[0].extend(extra);
+
+        let report = h.finalize();
+        assert!(report.edges_pruned >= 4);
+        assert_eq!(report.orphans_fixed, 1);
+        assert!(h.graph.nodes[fat].links[0].len() <= h.m_max0());
+        assert!(h.graph.nodes.iter().any(|n| n.links[0].contains(&orphan)));
+        assert_eq!(h.search(&data[10], 1).unwrap()[0].0, 10);
+    }
+
+    #[test]
+    fn recent_ties_prefers_newest_duplicate() {
+        let dup = vec![0.3, -0.2, 0.1, 0.4, 0.0, 0.2, -0.1, 0.5];
+        for seed in 0..20 {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(8)
+                .recent_ties(true)
+                .seed(seed)
+                .build()
+                .unwrap();
+            for (i, v) in rand_vecs(50, 8, 8).into_iter().enumerate() {
+                h.insert(v, 100 + i as u64).unwrap();
+            }
+            h.insert(dup.clone(), 1).unwrap();
+            h.insert(dup.clone(), 2).unwrap();
+            assert_eq!(h.search(&dup, 1).unwrap()[0].0, 2, "seed {}", seed);
+
+            h.insert(dup.clone(), 1).unwrap(); // upsert makes id 1 the freshest
+            assert_eq!(h.search(&dup, 1).unwrap()[0].0, 1, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn normalize_query_fixes_unnormalized_dot_queries() {
+        let build = |on: bool| {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .metric(Dot)
+                .dims(8)
+                .normalize_query(on)
+                .build()
+                .unwrap();
+            for (i, v) in rand_vecs(40, 8, 12).into_iter().enumerate() {
+                h.insert(math::l2_normalized(&v), i as u64).unwrap();
+            }
+            h
+        };
+        let q: Vec<f32> = rand_vecs(1, 8, 13).remove(0);
+        let loud: Vec<f32> = q.iter().map(|x| x * 10.0).collect();
+
+        let plain = build(false).search(&loud, 1).unwrap()[0].1;
+        let norm = build(true).search(&loud, 1).unwrap()[0].1;
+        let expected = build(false).search(&math::l2_normalized(&q), 1).unwrap()[0].1;
+        assert!(plain < 0.0);
+        assert!((norm - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn empty_returns_ok_flag() {
+        let h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        assert!(h.is_empty());
+        assert!(matches!(h.search(&[1.0; 4], 3), Err(VcalError::EmptyIndex)));
+
+        let h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .empty_returns_ok(true)
+            .build()
+            .unwrap();
+        assert!(h.search(&[1.0; 4], 3).unwrap().is_empty());
+        assert!(h.search_with_ef(&[1.0; 4], 3, 16).unwrap().is_empty());
+    }
+
+    #[test]
+    fn track_access_off_leaves_last_hit_untouched() {
+        use std::sync::atomic::Ordering;
+
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .track_access(false)
+            .build()
+            .unwrap();
+        h.insert(vec![1.0; 4], 1).unwrap();
+        let nid = h.graph.by_ext[&1];
+        h.graph.nodes[nid].last_hit.store(5, Ordering::Relaxed);
+        h.search(&[1.0; 4], 1).unwrap();
+        assert_eq!(h.graph.nodes[nid].last_hit.load(Ordering::Relaxed), 5);
+
+        h.track_access = true;
+        h.search(&[1.0; 4], 1).unwrap();
+        assert!(h.graph.nodes[nid].last_hit.load(Ordering::Relaxed) > 5);
+    }
+
+    #[test]
+    fn norm_stats_on_known_vectors() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(2).build().unwrap();
+        assert_eq!(h.norm_stats(), NormStats::default());
+        h.insert(vec![3.0, 4.0], 1).unwrap(); // 5
+        h.insert(vec![0.0, 1.0], 2).unwrap(); // 1
+        h.insert(vec![6.0, 8.0], 3).unwrap(); // 10
+        h.insert(vec![0.0, 0.0], 4).unwrap(); // 0
+        h.delete(3);
+
+        let st = h.norm_stats();
+        assert_eq!(st.count, 3);
+        assert_eq!(st.min, 0.0);
+        assert_eq!(st.max, 5.0);
+        assert!((st.mean - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn merge_makes_both_shards_searchable() {
+        let data = rand_vecs(120, 8, 21);
+        let shard = |range: std::ops::Range<usize>| {
+            let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+            for i in range {
+                h.insert(data[i].clone(), i as u64).unwrap();
+            }
+            h
+        };
+        let mut a = shard(0..60);
+        let mut b = shard(60..120);
+        b.delete(61);
+
+        assert_eq!(a.merge(b).unwrap(), 59);
+        assert_eq!(a.len(), 119);
+        for (i, v) in data.iter().enumerate() {
+            if i == 61 {
+                assert!(!a.contains(61));
+                continue;
+            }
+            assert_eq!(a.search(v, 1).unwrap()[0].0, i as u64);
+        }
+
+        let other = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        assert!(matches!(
+            a.merge(other),
+            Err(VcalError::DimensionMismatch {
+                expected: 8,
+                found: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn cosine_epsilon_builder_handles_tiny_vectors() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .cosine_epsilon(0.0)
+            .dims(2)
+            .build()
+            .unwrap();
+        h.insert(vec![1e-7, 0.0], 1).unwrap();
+        h.insert(vec![0.0, 1e-7], 2).unwrap();
+        let hits = h.search(&[1e-7, 1e-9], 2).unwrap();
+        assert_eq!(hits[0].0, 1);
+        assert!(hits[0].1 < 1e-3);
+    }
+
+    #[test]
+    fn builder_clone_templates_configs() {
+        let base = HnswBuilder::<Cosine>::default()
+            .m(12)
+            .ef_construction(80)
+            .ef_search(40);
+        let a = base.clone().dims(16).build().unwrap();
+        let b = base.dims(32).build().unwrap();
+        assert_eq!(a.params_full(), b.params_full());
+        assert_eq!((a.dims(), b.dims()), (16, 32));
+    }
+
+    #[test]
+    fn debug_prints_summary_not_vectors() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        h.insert(vec![0.125; 4], 1).unwrap();
+        h.insert(vec![0.5; 4], 2).unwrap();
+        h.delete(2);
+        let s = format!("{:?}", h);
+        assert!(s.contains("dims: 4"));
+        assert!(s.contains("active: 1"));
+        assert!(s.contains("tombstones: 1"));
+        assert!(!s.contains("0.125"));
+    }
+
+    #[test]
+    fn search_iter_is_sorted_and_stops_early() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let data = rand_vecs(100, 8, 29);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        let all: Vec<SearchHit> = h.search_iter(&data[5], 32).unwrap().collect();
+        assert_eq!(all.len(), 32);
+        assert!(all.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(all[0].0, 5);
+        assert_eq!(&all[..10], &h.search_with_ef(&data[5], 10, 32).unwrap()[..]);
+
+        let mut seen = 0;
+        let hit = h
+            .search_iter(&data[5], 32)
+            .unwrap()
+            .inspect(|_| seen += 1)
+            .find(|&(id, _)| id % 2 == 1);
+        assert!(hit.is_some());
+        assert!(seen < 32);
+    }
+
+    #[test]
+    fn ef_construction_layer0_defaults_and_overrides() {
+        let h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .ef_construction(50)
+            .build()
+            .unwrap();
+        assert_eq!(h.efc0(), 50);
+
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .ef_construction(16)
+            .ef_construction_layer0(120)
+            .seed(3)
+            .build()
+            .unwrap();
+        assert_eq!(h.efc0(), 120);
+        assert_eq!(h.params_full().2, 16);
+        let data = rand_vecs(200, 8, 31);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        assert!(h.self_recall(50, 1) >= 0.9);
+
+        h.set_ef_construction_layer0(None);
+        assert_eq!(h.efc0(), 16);
+    }
+
+    #[test]
+    fn cancelled_search_and_insert_return_promptly() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let data = rand_vecs(100, 8, 37);
+        let cancel = AtomicBool::new(false);
+        let items = data.iter().cloned().zip(0u64..).inspect(|&(_, id)| {
+            if id == 40 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+        assert!(matches!(
+            h.insert_many_cancellable(items, &cancel),
+            Err(VcalError::Cancelled)
+        ));
+        assert_eq!(h.len(), 40);
+
+        cancel.store(false, Ordering::Relaxed);
+        let rest = data.iter().cloned().zip(0u64..).skip(40);
+        assert_eq!(h.insert_many_cancellable(rest, &cancel).unwrap(), 60);
+        assert_eq!(
+            h.search_cancellable(&data[3], 5, &cancel).unwrap(),
+            h.search(&data[3], 5).unwrap()
+        );
+
+        cancel.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            h.search_cancellable(&data[3], 5, &cancel),
+            Err(VcalError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn reserve_grows_capacity_not_len() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        h.insert(vec![1.0, 0.0, 0.0, 0.0], 1).unwrap();
+        h.reserve(1000);
+        assert!(h.capacity() >= 1001);
+        assert_eq!(h.len(), 1);
+    }
+
+    #[test]
+    fn lru_eviction_breaks_timestamp_ties_by_insertion_order() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let ids = [42u64, 7, 99, 3, 15, 64];
+        for (v, &id) in rand_vecs(ids.len(), 8, 41).into_iter().zip(&ids) {
+            h.insert(v, id).unwrap();
+        }
+        h.graph.touch_many(&ids, 1_000);
+
+        assert_eq!(h.evict_lru_until(Some(2), None).0, 4);
+        assert_eq!(h.tombstones(), vec![3, 7, 42, 99]);
+        assert!(h.contains(15) && h.contains(64));
+    }
+
+    #[test]
+    fn ttl_resolves_below_one_second() {
+        use std::sync::atomic::Ordering;
+
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        for (i, v) in rand_vecs(3, 8, 43).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        let now = crate::node::now_millis();
+        let stamps = [now - 800, now - 100, now - 499];
+        for (id, ts) in stamps.iter().enumerate() {
+            let nid = h.graph.by_ext[&(id as u64)];
+            h.graph.nodes[nid].last_hit.store(*ts, Ordering::Relaxed);
+        }
+
+        assert_eq!(h.evict_ttl_millis(500).0, 1);
+        assert!(!h.contains(0));
+        assert!(h.contains(1) && h.contains(2));
+        assert_eq!(h.evict_ttl(1).0, 0);
+    }
+
+    #[test]
+    fn search_computes_each_node_distance_once() {
+        use std::sync::Mutex;
+
+        /// Cosine that records which stored vector each call touched.
+        #[derive(Default)]
+        struct Recording {
+            seen: Mutex<Vec<usize>>,
+        }
+        impl Metric for Recording {
+            fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+                self.seen.lock().unwrap().push(a.as_ptr() as usize);
+                Cosine.distance(a, b)
+            }
+        }
+
+        let mut h = HnswBuilder::new(Recording::default())
+            .dims(16)
+            .m(6)
+            .seed(5)
+            .build()
+            .unwrap();
+        let data = rand_vecs(400, 16, 47);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        assert!(h.graph.max_level > 0);
+
+        h.metric.seen.lock().unwrap().clear();
+        h.search_with_ef(&data[9], 10, 64).unwrap();
+        let mut seen = h.metric.seen.lock().unwrap().clone();
+        let calls = seen.len();
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), calls);
+    }
+
+    #[test]
+    fn search_by_id_excludes_self_and_rejects_unknown() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let data = rand_vecs(60, 8, 53);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+
+        let hits = h.search_by_id(12, 5).unwrap();
+        assert_eq!(hits.len(), 5);
+        assert!(hits.iter().all(|&(id, _)| id != 12));
+        let direct = h.search(&data[12], 6).unwrap();
+        assert_eq!(hits, direct[1..]);
+
+        assert!(matches!(
+            h.search_by_id(999, 5),
+            Err(VcalError::UnknownId(999))
+        ));
+        h.delete(12);
+        assert!(matches!(
+            h.search_by_id(12, 5),
+            Err(VcalError::UnknownId(12))
+        ));
+    }
+
+    #[test]
+    fn search_excluding_skips_denylist_without_losing_recall() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let data = rand_vecs(200, 8, 59);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+
+        let q = &data[17];
+        let full = h.search_with_ef(q, 15, 128).unwrap();
+        let exclude: Vec<u64> = full[..5].iter().map(|h| h.0).collect();
+        let hits = h.search_excluding(q, 10, &exclude).unwrap();
+
+        assert_eq!(hits.len(), 10);
+        assert!(hits.iter().all(|(id, _)| !exclude.contains(id)));
+        assert_eq!(hits, full[5..]);
+    }
+
+    #[test]
+    fn stats_detailed_matches_tuple_accessors() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(6)
+            .ef_search(40)
+            .ef_construction(90)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(30, 8, 61).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        h.delete(4);
+        h.delete(5);
+
+        let st = h.stats_detailed();
+        assert_eq!((st.active, st.total_bytes), h.stats());
+        assert_eq!((st.m, st.ef, st.efc), h.params_full());
+        assert_eq!((st.tombstones, st.node_count), (2, 30));
+        assert_eq!((st.dims, st.m_max0), (8, 12));
+        assert_eq!(st.max_level, h.graph.max_level);
+    }
+
+    #[test]
+    fn level_histogram_decays_geometrically() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .m(4)
+            .ef_construction(16)
+            .seed(67)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(4000, 4, 67).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+
+        let hist = h.level_histogram();
+        assert_eq!(hist.iter().sum::<usize>(), h.len());
+        assert!(hist.len() >= 3);
+        // p(level >= l) = m^-l, so each layer should hold ~1/4 of the one below.
+        for l in 0..2 {
+            let ratio = hist[l + 1] as f64 / hist[l] as f64;
+            assert!(ratio > 0.15 && ratio < 0.35, "layer {}: {:?}", l, hist);
+        }
+    }
+
+    #[test]
+    fn zero_dim_index_reports_invalid_config_on_search() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        h.dims = 0;
+        assert!(matches!(
+            h.search(&[1.0, 0.0, 0.0, 0.0], 1),
+            Err(VcalError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn u128_ids_round_trip_through_search() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .build_with_ids::<u128>()
+            .unwrap();
+        let base = u128::from(u64::MAX) + 1;
+        let data = rand_vecs(40, 8, 73);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), base + i as u128).unwrap();
+        }
+
+        assert_eq!(h.search(&data[7], 1).unwrap()[0].0, base + 7);
+        assert!(h.contains(base + 39) && !h.contains(39));
+        assert!(h.delete(base + 3));
+        assert_eq!(h.tombstones(), vec![base + 3]);
+        assert!(h
+            .search_by_id(base + 5, 3)
+            .unwrap()
+            .iter()
+            .all(|hit| hit.0 != base + 5));
+        match h.search_by_id(base + 3, 3) {
+            Err(VcalError::UnknownCustomId(id)) => assert_eq!(id, format!("{:?}", base + 3)),
+            other => panic!("unexpected: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn try_insert_rolls_back_a_failed_insert() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Cosine that panics once its call budget runs out.
+        struct Fuse {
+            left: AtomicUsize,
+        }
+        impl Metric for Fuse {
+            fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+                if self.left.fetch_sub(1, Ordering::Relaxed) == 0 {
+                    panic!("injected metric failure");
+                }
+                Cosine.distance(a, b)
+            }
+        }
+
+        type Snapshot = (Vec<Vec<Vec<usize>>>, Vec<Vec<usize>>, IndexStats, bool);
+        fn state(h: &Hnsw<Fuse>) -> Snapshot {
+            let links = h
+                .graph
+                .nodes
+                .iter()
+                .map(|n| n.links.iter().map(|a| a.to_vec()).collect())
+                .collect();
+            let deleted = h.graph.nodes.iter().any(|n| n.is_deleted());
+            (links, h.graph.levels.clone(), h.stats_detailed(), deleted)
+        }
+
+        let mut h = HnswBuilder::new(Fuse {
+            left: AtomicUsize::new(usize::MAX),
+        })
+        .dims(8)
+        .m(4)
+        .seed(79)
+        .build()
+        .unwrap();
+        let data = rand_vecs(81, 8, 79);
+        for (i, v) in data.iter().take(80).enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+
+        let before = state(&h);
+        let before_hits = h.search(&data[3], 5).unwrap();
+        let prev_panic_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        // Fresh id, then an upsert of an existing id; both fail mid-wiring.
+        for &(id, budget) in &[(80u64, 40usize), (3, 25)] {
+            h.metric.left.store(budget, Ordering::Relaxed);
+            let err = h.try_insert(data[80].clone(), id).unwrap_err();
+            assert!(matches!(err, VcalError::InsertFailed(ref m) if m.contains("injected")));
+            assert!(state(&h) == before);
+        }
+        std::panic::set_hook(prev_panic_hook);
+
+        h.metric.left.store(usize::MAX, Ordering::Relaxed);
+        assert_eq!(h.search(&data[3], 5).unwrap(), before_hits);
+        assert!(!h.contains(80));
+        assert!(matches!(
+            h.try_insert(vec![f32::NAN; 8], 81),
+            Err(VcalError::InvalidParameter(_))
+        ));
+        h.try_insert(data[80].clone(), 80).unwrap();
+        assert!(h.contains(80));
+    }
+
+    #[test]
+    fn search_shallow_caps_layer0_work() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(16)
+            .m(8)
+            .ef_search(64)
+            .seed(83)
+            .build()
+            .unwrap();
+        let data = rand_vecs(500, 16, 83);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+
+        let q = &data[42];
+        // A cap the beam never reaches behaves exactly like `search`.
+        assert_eq!(
+            h.search_shallow(q, 10, usize::MAX).unwrap(),
+            h.search(q, 10).unwrap()
+        );
+        // Fewer scored nodes than `k` bounds the result size.
+        let tight = h.search_shallow(q, 10, 4).unwrap();
+        assert!(!tight.is_empty() && tight.len() <= 4);
+        assert!(tight.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(h.search_shallow(q, 10, 0).unwrap().len(), 1);
+        assert!(h.search_shallow(q, 0, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn build_from_iter_inserts_everything() {
+        let data = rand_vecs(200, 8, 89);
+        let h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(89)
+            .build_from_iter(data.iter().cloned().enumerate().map(|(i, v)| (i as u64, v)))
+            .unwrap();
+        assert_eq!(h.len(), 200);
+        assert!(h.capacity() >= 200);
+        assert_eq!(h.search(&data[17], 1).unwrap()[0].0, 17);
+
+        let bad = vec![(0u64, vec![0.5; 8]), (1, vec![0.5; 3])];
+        assert!(matches!(
+            HnswBuilder::<Cosine>::default()
+                .dims(8)
+                .build_from_iter(bad),
+            Err(VcalError::DimensionMismatchAt {
+                found: 3,
+                index: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn insert_with_level_reports_the_drawn_level() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(2)
+            .seed(97)
+            .build()
+            .unwrap();
+        let mut top = 0;
+        for (i, v) in rand_vecs(300, 8, 97).into_iter().enumerate() {
+            let lvl = h.insert_with_level(v, i as u64).unwrap();
+            let nid = h.graph.by_ext[&(i as u64)];
+            assert_eq!(lvl, h.graph.nodes[nid].links.len() - 1);
+            top = top.max(lvl);
+        }
+        // m = 2 makes upper levels common enough to exercise the tower.
+        assert!(top > 0);
+        assert_eq!(top, h.graph.max_level);
+    }
+
+    #[test]
+    fn stale_entry_is_rescanned_once_not_per_query() {
+        use std::sync::atomic::Ordering;
+
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(101)
+            .build()
+            .unwrap();
+        let data = rand_vecs(200, 8, 101);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        for id in 0..150u64 {
+            h.delete(id);
+        }
+        // Leave `entry` pointing at a tombstone, as a reader could observe it.
+        let stale = h.graph.nodes.iter().position(|n| n.is_deleted()).unwrap();
+        h.graph.entry = Some(stale);
+
+        let scans = h.graph.entry_scans.load(Ordering::Relaxed);
+        for q in &data[150..] {
+            let hits = h.search(q, 3).unwrap();
+            assert!(hits.iter().all(|&(id, _)| id >= 150));
+        }
+        assert_eq!(h.graph.entry_scans.load(Ordering::Relaxed), scans + 1);
+
+        // A hint that dies is replaced by one more scan.
+        let hint = h.graph.entry_hint.load(Ordering::Relaxed);
+        h.delete(h.graph.nodes[hint].ext_id);
+        h.graph.entry = Some(stale);
+        let scans = h.graph.entry_scans.load(Ordering::Relaxed);
+        h.search(&data[199], 3).unwrap();
+        h.search(&data[198], 3).unwrap();
+        assert_eq!(h.graph.entry_scans.load(Ordering::Relaxed), scans + 1);
+    }
+
+    #[test]
+    fn partial_eq_is_structural() {
+        let build = |seed| {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(8)
+                .seed(seed)
+                .build()
+                .unwrap();
+            for (i, v) in rand_vecs(100, 8, 103).into_iter().enumerate() {
+                h.insert(v, i as u64).unwrap();
+            }
+            h
+        };
+        let (mut a, mut b) = (build(103), build(103));
+        assert!(a == b);
+
+        // Tombstones left by delete and upsert are not compared.
+        a.delete(5);
+        b.delete(5);
+        let v = rand_vecs(100, 8, 103).swap_remove(7);
+        b.insert(v.clone(), 7).unwrap();
+        a.insert(v, 7).unwrap();
+        assert!(a == b);
+
+        b.insert(vec![0.25; 8], 500).unwrap();
+        assert!(a != b);
+        b.delete(500);
+        let mut c = build(103);
+        c.set_ef(7);
+        assert!(c != build(103));
+    }
+
+    #[test]
+    fn relink_node_repairs_drifted_vectors() {
+        let build = || {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(16)
+                .m(8)
+                .ef_construction(64)
+                .ef_search(32)
+                .seed(127)
+                .build()
+                .unwrap();
+            for (i, v) in rand_vecs(1000, 16, 127).into_iter().enumerate() {
+                h.insert(v, i as u64).unwrap();
+            }
+            h
+        };
+        let drifted = rand_vecs(100, 16, 131);
+        let self_hits = |h: &Hnsw| {
+            (0..100)
+                .filter(|&i| h.search(&drifted[i], 1).unwrap()[0].0 == i as u64 * 10)
+                .count()
+        };
+
+        let mut h = build();
+        for (i, v) in drifted.iter().enumerate() {
+            h.update_vector(i as u64 * 10, v.clone()).unwrap();
+        }
+        let stale = self_hits(&h);
+        let nids: Vec<usize> = (0..100).map(|i| h.graph.by_ext[&(i * 10)]).collect();
+        for i in 0..100u64 {
+            h.relink_node(i * 10).unwrap();
+        }
+        let relinked = self_hits(&h);
+        // Identity is kept: same NodeIds, no tombstones, consistent bytes.
+        assert_eq!(
+            nids,
+            (0..100)
+                .map(|i| h.graph.by_ext[&(i * 10)])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(h.stats_detailed().tombstones, 0);
+        let bytes: usize = h.graph.nodes.iter().map(|n| n.bytes).sum();
+        assert_eq!(h.stats().1, bytes);
+
+        let mut reinserted = build();
+        for (i, v) in drifted.iter().enumerate() {
+            reinserted.insert(v.clone(), i as u64 * 10).unwrap();
+        }
+        let fresh = self_hits(&reinserted);
+        assert!(relinked > stale, "stale {} relinked {}", stale, relinked);
+        assert!(
+            relinked + 5 >= fresh,
+            "relinked {} delete+insert {}",
+            relinked,
+            fresh
+        );
+
+        assert!(matches!(h.relink_node(5_000), Err(VcalError::UnknownId(_))));
+        assert!(matches!(
+            h.update_vector(10, vec![0.0; 3]),
+            Err(VcalError::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn contains_near_applies_the_threshold() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(137)
+            .build()
+            .unwrap();
+        assert_eq!(h.contains_near(&[0.5; 8], 0.1).unwrap(), None);
+
+        let data = rand_vecs(50, 8, 137);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        let mut near = data[9].clone();
+        near[0] += 0.01;
+        assert_eq!(h.contains_near(&near, 0.01).unwrap(), Some(9));
+        assert_eq!(h.contains_near(&data[9], 0.0).unwrap(), Some(9));
+
+        let far = rand_vecs(1, 8, 139).remove(0);
+        let nearest = h.search(&far, 1).unwrap()[0].1;
+        assert!(nearest > 0.01);
+        assert_eq!(h.contains_near(&far, 0.01).unwrap(), None);
+        assert!(h.contains_near(&[0.5; 3], 0.1).is_err());
+    }
+
+    #[test]
+    fn iter_by_distance_is_sorted_and_complete() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(139)
+            .build()
+            .unwrap();
+        let data = rand_vecs(120, 8, 139);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        h.delete(3);
+        h.delete(77);
+
+        let all = h.iter_by_distance(&data[10]).unwrap();
+        assert_eq!(all.len(), h.len());
+        assert_eq!(all[0], (10, 0.0));
+        assert!(all.windows(2).all(|w| w[0].1 <= w[1].1));
+        let mut ids: Vec<u64> = all.iter().map(|h| h.0).collect();
+        ids.sort_unstable();
+        let live: Vec<u64> = (0..120).filter(|&i| i != 3 && i != 77).collect();
+        assert_eq!(ids, live);
+        assert_eq!(&all[..5], &h.search(&data[10], 5).unwrap()[..]);
+    }
+
+    #[test]
+    fn search_into_reuses_and_clears_the_buffer() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(151)
+            .build()
+            .unwrap();
+        let data = rand_vecs(200, 8, 151);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+
+        let mut out = Vec::new();
+        h.search_into(&data[0], 10, &mut out).unwrap();
+        assert_eq!(out, h.search(&data[0], 10).unwrap());
+        let cap = out.capacity();
+
+        // Fewer hits than last time: stale entries must not survive.
+        h.search_into(&data[1], 3, &mut out).unwrap();
+        assert_eq!(out, h.search(&data[1], 3).unwrap());
+        assert_eq!(out.capacity(), cap);
+
+        h.search_into(&data[2], 0, &mut out).unwrap();
+        assert!(out.is_empty());
+        h.search_into(&data[2], 5, &mut out).unwrap();
+        assert!(h.search_into(&[0.0; 3], 5, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn build_progress_is_reported_sparsely() {
+        let data = rand_vecs(250, 8, 163);
+        let mut calls = Vec::new();
+        let h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(163)
+            .build_from_with_progress(
+                data.into_iter().enumerate().map(|(i, v)| (i as u64, v)),
+                100,
+                |done, total| calls.push((done, total)),
+            )
+            .unwrap();
+        assert_eq!(h.len(), 250);
+        assert_eq!(calls, vec![(100, 250), (200, 250), (250, 250)]);
+
+        let mut n = 0;
+        HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .build_from_with_progress(
+                rand_vecs(10, 8, 1)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| (i as u64, v)),
+                0,
+                |_, _| n += 1,
+            )
+            .unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn compact_levels_matches_live_tops() {
+        let data = rand_vecs(600, 8, 163);
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(4)
+            .seed(163)
+            .build()
+            .unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        for i in (0..600u64).filter(|i| i % 5 != 0) {
+            h.delete(i);
+        }
+        h.compact_levels();
+
+        let g = &h.graph;
+        let mut want = vec![Vec::new(); g.max_level + 1];
+        for (nid, n) in g.nodes.iter().enumerate() {
+            if !n.is_deleted() {
+                want[n.links.len() - 1].push(nid);
+            }
+        }
+        assert!(!want[g.max_level].is_empty());
+        assert_eq!(g.levels, want);
+        assert!(g.levels.iter().all(|l| l.capacity() == l.len()));
+        assert_eq!(
+            g.entry.map(|e| g.nodes[e].links.len() - 1),
+            Some(g.max_level)
+        );
+        assert_eq!(h.search(&data[5], 1).unwrap()[0].0 % 5, 0);
+    }
+
+    #[test]
+    fn search_and_insert_accept_any_container() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(3).build().unwrap();
+        h.insert(vec![1.0, 0.0, 0.0], 1).unwrap();
+        h.insert([0.0f32, 1.0, 0.0].as_ref(), 2).unwrap();
+        h.insert(vec![0.0, 0.0, 1.0].into_boxed_slice(), 3).unwrap();
+
+        let boxed: Box<[f32]> = vec![0.0, 0.0, 1.0].into_boxed_slice();
+        assert_eq!(h.search(&boxed, 1).unwrap()[0].0, 3);
+        assert_eq!(h.search([0.0f32, 1.0, 0.0], 1).unwrap()[0].0, 2);
+        assert_eq!(h.search(vec![1.0, 0.0, 0.0], 1).unwrap()[0].0, 1);
+        assert_eq!(h.search_with_ef(&boxed[..], 1, 4).unwrap()[0].0, 3);
+    }
+
+    #[test]
+    fn batch_inserts_report_the_bad_record() {
+        let mut batch: Vec<(Vec<f32>, u64)> =
+            (0..50).map(|i| (vec![i as f32 + 1.0; 8], i)).collect();
+        batch[37].0.truncate(7);
+
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let err = h
+            .insert_many_cancellable(batch.clone(), &AtomicBool::new(false))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            VcalError::DimensionMismatchAt {
+                expected: 8,
+                found: 7,
+                index: 37
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "dimension mismatch at record 37: expected 8, got 7"
+        );
+        assert_eq!(h.len(), 37);
+
+        let err = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .build_from_iter(batch.into_iter().map(|(v, id)| (id, v)))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            VcalError::DimensionMismatchAt { index: 37, .. }
+        ));
+
+        // Single inserts keep the plain variant.
+        assert!(matches!(
+            h.insert(vec![0.0; 3], 99),
+            Err(VcalError::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn exact_below_bypasses_the_graph_on_small_indexes() {
+        let data = rand_vecs(60, 8, 166);
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(166)
+            .exact_below(100)
+            .build()
+            .unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        // Cut node 7 out of the graph: only a scan can still find it.
+        let g = &mut h.graph;
+        let lost = g.by_ext[&7];
+        assert_ne!(g.entry, Some(lost));
+        for n in g.nodes.iter_mut() {
+            for adj in n.links.iter_mut() {
+                adj.retain(|x| *x != lost);
+            }
+        }
+
+        let mut out = Vec::new();
+        for q in data.iter().step_by(6) {
+            assert_eq!(h.search(q, 5).unwrap(), h.search_exact(q, 5).unwrap());
+            h.search_into(q, 5, &mut out).unwrap();
+            assert_eq!(out, h.search_exact(q, 5).unwrap());
+        }
+        assert_eq!(h.search(&data[7], 1).unwrap()[0].0, 7);
+        h.search_into(&data[7], 1, &mut out).unwrap();
+        assert_eq!(out[0].0, 7);
+
+        h.exact_below = 60;
+        assert_ne!(h.search(&data[7], 1).unwrap()[0].0, 7);
+        assert_eq!(h.search_exact(&data[7], 1).unwrap()[0].0, 7);
+    }
+
+    #[test]
+    fn search_similarity_scores_cosine_in_unit_range() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(2).build().unwrap();
+        h.insert(vec![1.0, 0.0], 1).unwrap();
+        h.insert(vec![0.0, 1.0], 2).unwrap();
+        h.insert(vec![-1.0, 0.0], 3).unwrap();
+
+        let hits = h.search_similarity([2.0, 0.0], 3).unwrap();
+        assert_eq!(hits, vec![(1, 1.0), (2, 0.5), (3, 0.0)]);
+    }
+
+    #[test]
+    fn eviction_steps_respect_the_budget() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .seed(168)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(100, 4, 168).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        let mut steps = 0;
+        loop {
+            let (n, more) = h.evict_lru_step(Some(75), None, 10);
+            assert!(n <= 10);
+            steps += 1;
+            if !more {
+                break;
+            }
+        }
+        assert_eq!((steps, h.len()), (3, 75));
+        assert_eq!(h.evict_lru_step(Some(75), None, 10), (0, false));
+        // Same victims as the one-shot sweep: oldest first.
+        assert!((0..25).all(|i| !h.contains(i)));
+
+        for (i, v) in rand_vecs(5, 4, 169).into_iter().enumerate() {
+            h.insert(v, 1000 + i as u64).unwrap();
+        }
+        for nid in 0..h.graph.nodes.len() {
+            let n = &h.graph.nodes[nid];
+            if n.ext_id < 1000 {
+                n.last_hit.store(0, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        assert_eq!(h.evict_ttl_step(60_000, 50), (50, true));
+        assert_eq!(h.evict_ttl_step(60_000, 50), (25, false));
+        assert_eq!(h.len(), 5);
+    }
+
+    #[test]
+    fn checksum_tracks_content_not_access() {
+        let data = rand_vecs(80, 8, 169);
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(169)
+            .build()
+            .unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        let before = h.checksum();
+        assert_eq!(h.checksum(), before);
+
+        h.search(&data[3], 5).unwrap();
+        assert_eq!(h.checksum(), before, "last_hit must not count");
+
+        h.set_ef(h.ef + 1);
+        assert_ne!(h.checksum(), before);
+        h.set_ef(h.ef - 1);
+        assert_eq!(h.checksum(), before);
+
+        h.delete(3);
+        assert_ne!(h.checksum(), before);
+    }
+
+    #[test]
+    fn search_thresholded_drops_distant_hits() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(2).build().unwrap();
+        // Angles from the query axis: ids 0..3 within 0.3 rad, the rest >= 1.0 rad.
+        let angles = [0.2f32, 0.1, 0.3, 1.0, 1.3, 1.6, 2.0, 2.4, 2.8, 3.1];
+        for (i, a) in angles.iter().enumerate() {
+            h.insert(vec![a.cos(), a.sin()], i as u64).unwrap();
+        }
+
+        let hits = h.search_thresholded([1.0, 0.0], 10, 0.1).unwrap();
+        let ids: Vec<u64> = hits.iter().map(|h| h.0).collect();
+        assert_eq!(ids, vec![1, 0, 2]);
+        assert!(hits.iter().all(|&(_, d)| d <= 0.1));
+
+        assert_eq!(h.search_thresholded([1.0, 0.0], 2, 0.1).unwrap().len(), 2);
+        assert!(h
+            .search_thresholded([0.0, -1.0], 10, 0.01)
+            .unwrap()
+            .is_empty());
+        assert_eq!(h.search_thresholded([1.0, 0.0], 10, 2.0).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn search_batch_clustered_matches_independent_searches() {
+        let data = rand_vecs(600, 8, 174);
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(174)
+            .build()
+            .unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        // A tight batch around one stored vector.
+        let base = &data[42];
+        let batch: Vec<Vec<f32>> = (0..6)
+            .map(|j| base.iter().map(|x| x + 0.01 * j as f32).collect())
+            .collect();
+        let got = h.search_batch_clustered(&batch, 5).unwrap();
+        assert_eq!(got.len(), 6);
+        for (q, hits) in batch.iter().zip(&got) {
+            assert_eq!(hits, &h.search(q, 5).unwrap());
+        }
+
+        assert!(h
+            .search_batch_clustered::<Vec<f32>>(&[], 5)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            h.search_batch_clustered(&batch, 0).unwrap(),
+            vec![vec![]; 6]
+        );
+        assert!(matches!(
+            h.search_batch_clustered(&[vec![0.0; 3]], 5),
+            Err(VcalError::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn set_last_hit_primes_lru_order() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        for (i, v) in rand_vecs(10, 4, 175).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        // Reverse the insertion order: id 9 is now the stalest.
+        for id in 0..10u64 {
+            assert!(h.set_last_hit(id, 1_000 * (10 - id)));
+        }
+        assert_eq!(h.last_hit(9), Some(1_000));
+        assert!(!h.set_last_hit(42, 5));
+        assert_eq!(h.last_hit(42), None);
+
+        assert_eq!(h.evict_lru_until(Some(7), None).0, 3);
+        assert!((7..10).all(|id| !h.contains(id)));
+        assert!((0..7).all(|id| h.contains(id)));
+    }
+
+    #[test]
+    fn neighborhood_walks_layer0_by_hops() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(2).build().unwrap();
+        for i in 0..7u64 {
+            h.insert(vec![1.0, i as f32], i).unwrap();
+        }
+        // Rewire layer 0 as 0 - 1 - 2 - 3 - 4 plus 0 - 5 - 6.
+        let edges: [&[u64]; 7] = [&[1, 5], &[0, 2], &[1, 3], &[2, 4], &[3], &[0, 6], &[5]];
+        for (id, nbrs) in edges.iter().enumerate() {
+            let nid = h.graph.by_ext[&(id as u64)];
+            let adj: node::Adj = nbrs.iter().map(|e| h.graph.by_ext[e]).collect();
+            h.graph.nodes[nid].links[0] = adj;
+        }
+        let set = |v: Vec<u64>| {
+            let mut v = v;
+            v.sort_unstable();
+            v
+        };
+
+        assert_eq!(set(h.neighborhood(0, 1).unwrap()), vec![1, 5]);
+        assert_eq!(set(h.neighborhood(0, 2).unwrap()), vec![1, 2, 5, 6]);
+        assert_eq!(h.neighborhood(0, 0).unwrap(), Vec::<u64>::new());
+        assert_eq!(set(h.neighborhood(3, 2).unwrap()), vec![1, 2, 4]);
+
+        // Tombstones cut the walk.
+        h.delete(5);
+        assert_eq!(set(h.neighborhood(0, 3).unwrap()), vec![1, 2, 3]);
+        assert!(matches!(h.neighborhood(5, 1), Err(VcalError::UnknownId(_))));
+    }
+
+    #[test]
+    fn incremental_bytes_match_full_recount_after_churn() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(4)
+            .seed(11)
+            .build()
+            .unwrap();
+        let data = rand_vecs(300, 8, 17);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        assert!(h.graph.bytes_consistent());
+
+        for id in (0..300u64).step_by(7) {
+            h.delete(id);
+        }
+        assert!(h.graph.bytes_consistent());
+
+        let batch: Vec<u64> = (1..300u64).step_by(11).collect();
+        h.delete_many(&batch);
+        for (i, v) in rand_vecs(50, 8, 19).into_iter().enumerate() {
+            h.insert(v, 1_000 + i as u64).unwrap();
+        }
+        h.update_vector(2, data[3].clone()).unwrap();
+        h.relink_node(4).unwrap();
+        assert!(h.graph.bytes_consistent());
+
+        h.evict_lru_until(Some(100), None);
+        assert!(h.graph.bytes_consistent());
+        assert_eq!(h.len(), 100);
+    }
+
+    #[test]
+    fn kth_distance_matches_last_search_hit() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(16)
+            .seed(5)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(200, 16, 23).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        for q in rand_vecs(10, 16, 29) {
+            for &k in &[1usize, 5, 20] {
+                let want = h.search(&q, k).unwrap().last().map(|hit| hit.1);
+                assert_eq!(h.kth_distance(&q, k).unwrap(), want);
+            }
+            assert_eq!(h.kth_distance(&q, 0).unwrap(), None);
+            assert_eq!(h.kth_distance(&q, 500).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn insert_shared_reuses_the_callers_allocation() {
+        let build = || {
+            HnswBuilder::<Cosine>::default()
+                .dims(8)
+                .seed(3)
+                .build()
+                .unwrap()
+        };
+        let (mut full, mut sub) = (build(), build());
+        let vecs: Vec<Arc<[f32]>> = rand_vecs(100, 8, 31).into_iter().map(Arc::from).collect();
+        for (i, v) in vecs.iter().enumerate() {
+            full.insert_shared(Arc::clone(v), i as u64).unwrap();
+            if i % 2 == 0 {
+                sub.insert_shared(Arc::clone(v), i as u64).unwrap();
+            }
+        }
+
+        // One allocation per vector: ours plus one reference per index.
+        assert_eq!(Arc::strong_count(&vecs[0]), 3);
+        assert_eq!(Arc::strong_count(&vecs[1]), 2);
+        let nid = full.graph.by_ext[&4];
+        assert!(full.graph.nodes[nid].vec.shares(&vecs[4]));
+
+        for i in (0..100).step_by(10) {
+            assert_eq!(full.search(&vecs[i][..], 1).unwrap()[0].0, i as u64);
+            assert_eq!(sub.search(&vecs[i][..], 1).unwrap()[0].0, i as u64);
+        }
+        // Each index accounts only for the vectors it holds.
+        let vec_bytes =
+            |h: &Hnsw<Cosine>| h.graph.nodes.iter().map(|n| n.vec.len() * 4).sum::<usize>();
+        assert_eq!(vec_bytes(&full), 100 * 8 * 4);
+        assert_eq!(vec_bytes(&sub), 50 * 8 * 4);
+        assert!(full.graph.bytes_consistent() && sub.graph.bytes_consistent());
+
+        // Deleting from one index leaves the other's copy intact.
+        full.delete(0);
+        assert_eq!(Arc::strong_count(&vecs[0]), 2);
+        assert_eq!(sub.search(&vecs[0][..], 1).unwrap()[0].0, 0);
+    }
+
+    #[test]
+    fn rebuild_with_keep_levels_changes_only_connectivity() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(16)
+            .m(8)
+            .seed(13)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(400, 16, 37).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        h.delete(5);
+
+        let level_of =
+            |h: &Hnsw<Cosine>, id: u64| h.graph.nodes[h.graph.by_ext[&id]].links.len() - 1;
+        let max_degree = |h: &Hnsw<Cosine>, layer: usize| {
+            h.graph
+                .nodes
+                .iter()
+                .filter(|n| !n.is_deleted() && n.links.len() > layer)
+                .map(|n| n.links[layer].len())
+                .max()
+                .unwrap_or(0)
+        };
+        assert!(max_degree(&h, 0) > 8);
+
+        let r = h.rebuild_with(4, 64, true).unwrap();
+        assert_eq!(r.len(), h.len());
+        assert!(!r.contains(5));
+        assert_eq!((r.m, r.efc, r.ef), (4, 64, h.ef));
+        assert_eq!(r.m_max0(), 8);
+        for id in (0..400u64).filter(|&id| id != 5) {
+            assert_eq!(level_of(&r, id), level_of(&h, id));
+        }
+        assert_eq!(r.level_histogram(), h.level_histogram());
+        assert!(max_degree(&r, 0) <= 8);
+        assert!(max_degree(&r, 1) <= 4);
+
+        for (i, v) in rand_vecs(400, 16, 37).iter().enumerate().step_by(40) {
+            assert_eq!(r.search(v, 1).unwrap()[0].0, i as u64);
+        }
+        assert!(h.rebuild_with(1, 64, true).is_err());
+    }
+
+    #[test]
+    fn rebuild_with_resets_only_the_overriding_settings() {
+        let budget: DegreeBudget = Arc::new(|layer| if layer == 0 { 6 } else { 3 });
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(6)
+            .m_max0(20)
+            .degree_budget(budget)
+            .ef_construction(40)
+            .ef_construction_layer0(80)
+            .ef_search(30)
+            .seed(181)
+            .recent_ties(true)
+            .track_access(false)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(200, 8, 181).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+
+        let r = h.rebuild_with(4, 32, false).unwrap();
+        assert_eq!((r.m, r.m_max0, r.efc, r.efc0), (4, 8, 32, None));
+        assert!(r.graph.degree_budget.is_none());
+        assert_eq!(r.ef, 30);
+        assert!(r.recent_ties && !r.track_access);
+
+        // Fresh levels continue from the source's sampler state, so a seeded
+        // index rebuilds identically.
+        let again = h.rebuild_with(4, 32, false).unwrap();
+        assert_eq!(r.checksum(), again.checksum());
+        assert_eq!(r.level_histogram(), again.level_histogram());
+    }
+
+    #[test]
+    fn memory_estimates_track_total_bytes() {
+        let (dims, m, n) = (128, 16, 500);
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(dims)
+            .m(m)
+            .ef_construction(64)
+            .seed(41)
+            .build()
+            .unwrap();
+        let empty = h.estimated_bytes_per_vector();
+        for (i, v) in rand_vecs(n, dims, 43).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        let actual = h.total_bytes();
+        let live = h.estimated_bytes_per_vector() * n;
+        let planned = HnswBuilder::estimate_bytes(dims, m, n);
+        for est in [live, planned, empty * n] {
+            assert!(est >= actual && est <= 2 * actual, "{} vs {}", est, actual);
+        }
+        assert!(live <= planned);
+        assert_eq!(empty * n, planned);
+    }
+
+    #[test]
+    fn insert_with_efc_widens_only_that_insert() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Counting(AtomicUsize);
+        impl Metric for Counting {
+            fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Cosine.distance(a, b)
+            }
+        }
+
+        let build = || {
+            let mut h = HnswBuilder::new(Counting::default())
+                .dims(16)
+                .m(4)
+                .ef_construction(8)
+                .seed(47)
+                .build()
+                .unwrap();
+            for (i, v) in rand_vecs(500, 16, 53).into_iter().enumerate() {
+                h.insert(v, i as u64).unwrap();
+            }
+            h
+        };
+        let calls = |h: &mut Hnsw<Counting>, f: &dyn Fn(&mut Hnsw<Counting>)| {
+            h.metric.0.store(0, Ordering::Relaxed);
+            f(h);
+            h.metric.0.load(Ordering::Relaxed)
+        };
+        let extra = rand_vecs(2, 16, 59);
+        let (mut plain, mut wide) = (build(), build());
+
+        let narrow = calls(&mut plain, &|h| h.insert(extra[0].clone(), 900).unwrap());
+        let widened = calls(&mut wide, &|h| {
+            h.insert_with_efc(extra[0].clone(), 900, 200).unwrap()
+        });
+        assert!(widened > 2 * narrow, "{} vs {}", widened, narrow);
+
+        // The override is gone: the next plain insert costs the same on both.
+        assert_eq!(wide.efc, 8);
+        let after_plain = calls(&mut plain, &|h| h.insert(extra[1].clone(), 901).unwrap());
+        let after_wide = calls(&mut wide, &|h| h.insert(extra[1].clone(), 901).unwrap());
+        assert!(
+            after_wide < 2 * after_plain,
+            "{} vs {}",
+            after_wide,
+            after_plain
+        );
+    }
+
+    #[test]
+    fn dedup_near_keeps_the_smallest_id_per_cluster() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(16)
+            .seed(61)
+            .build()
+            .unwrap();
+        let centers = rand_vecs(20, 16, 67);
+        let noise = rand_vecs(100, 16, 71);
+        // Cluster c holds ids c, c + 20, ..., c + 80, all within ~1e-4.
+        for copy in 0..5 {
+            for (c, center) in centers.iter().enumerate() {
+                let id = (c + 20 * copy) as u64;
+                let v: Vec<f32> = center
+                    .iter()
+                    .zip(&noise[id as usize])
+                    .map(|(x, n)| x + n * 1e-4)
+                    .collect();
+                h.insert(v, id).unwrap();
+            }
+        }
+
+        assert_eq!(h.dedup_near(1e-4), 80);
+        assert_eq!(h.len(), 20);
+        for c in 0..20u64 {
+            assert!(h.contains(c));
+        }
+        assert_eq!(h.dedup_near(1e-4), 0);
+    }
+
+    #[test]
+    fn insert_bytes_decodes_little_endian() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        for (i, v) in rand_vecs(50, 8, 73).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        let v = [0.5f32, -1.25, 3.0, 0.0, 7.5, -0.125, 2.0, 1.0];
+        let blob: Vec<u8> = v.iter().flat_map(|x| x.to_le_bytes()).collect();
+        h.insert_bytes(&blob, 99).unwrap();
+
+        let nid = h.graph.by_ext[&99];
+        assert_eq!(&h.graph.nodes[nid].vec[..], &v[..]);
+        assert_eq!(h.search(v, 1).unwrap()[0].0, 99);
+
+        for bad in [&blob[..28], &blob[..30], &[0u8; 36][..]] {
+            assert!(matches!(
+                h.insert_bytes(bad, 100),
+                Err(VcalError::DimensionMismatch { expected: 8, .. })
+            ));
+        }
+        assert!(!h.contains(100));
+    }
+
+    #[test]
+    fn search_split_matches_search() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(79)
+            .track_access(false)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(120, 8, 83).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        for q in rand_vecs(5, 8, 89) {
+            let (ids, dists) = h.search_split(&q, 10).unwrap();
+            assert_eq!(ids.len(), 10);
+            let pairs: Vec<_> = ids.into_iter().zip(dists).collect();
+            assert_eq!(pairs, h.search(&q, 10).unwrap());
+        }
+    }
+
+    #[test]
+    fn bounded_distance_search_matches_full_distance() {
+        /// Squared L2, optionally with an early-exit `distance_bounded`.
+        struct L2 {
+            bounded: bool,
+        }
+        impl Metric for L2 {
+            fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+                a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+            }
+            fn distance_bounded(&self, a: &[f32], b: &[f32], bound: f32) -> f32 {
+                if !self.bounded {
+                    return self.distance(a, b);
+                }
+                // Same summation order as `distance`, checked every 4 terms.
+                let mut acc = 0.0f32;
+                for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                    acc += (x - y) * (x - y);
+                    if i % 4 == 3 && acc > bound {
+                        break;
+                    }
+                }
+                acc
+            }
+        }
+
+        let data = rand_vecs(600, 32, 97);
+        let bounded = L2 { bounded: true };
+        for (a, b) in data.iter().zip(&data[1..]).take(50) {
+            let full = bounded.distance(a, b);
+            assert_eq!(bounded.distance_bounded(a, b, full), full);
+            assert_eq!(bounded.distance_bounded(a, b, f32::INFINITY), full);
+            assert!(bounded.distance_bounded(a, b, full * 0.5) > full * 0.5);
+        }
+
+        let build = |bounded: bool| {
+            let mut h = HnswBuilder::new(L2 { bounded })
+                .dims(32)
+                .m(8)
+                .seed(101)
+                .build()
+                .unwrap();
+            for (i, v) in data.iter().enumerate() {
+                h.insert(v.clone(), i as u64).unwrap();
+            }
+            h
+        };
+        let (fast, full) = (build(true), build(false));
+        assert!(fast.graph.same_live_graph(&full.graph));
+        for q in rand_vecs(20, 32, 103) {
+            assert_eq!(fast.search(&q, 10).unwrap(), full.search(&q, 10).unwrap());
+        }
+
+        let (a, b) = (&data[0], &data[1]);
+        let full = HammingBits.distance(a, b);
+        assert_eq!(HammingBits.distance_bounded(a, b, full), full);
+        assert!(HammingBits.distance_bounded(a, b, 3.0) > 3.0);
+    }
+
+    #[test]
+    fn upsert_reports_insert_or_replace() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        assert_eq!(
+            h.upsert([1.0, 0.0, 0.0, 0.0], 7).unwrap(),
+            Upserted::Inserted
+        );
+        assert_eq!(
+            h.upsert([0.0, 1.0, 0.0, 0.0], 7).unwrap(),
+            Upserted::Replaced
+        );
+        assert_eq!(h.len(), 1);
+        assert_eq!(h.search([0.0, 1.0, 0.0, 0.0], 1).unwrap()[0], (7, 0.0));
+
+        h.delete(7);
+        assert_eq!(
+            h.upsert([0.0, 0.0, 1.0, 0.0], 7).unwrap(),
+            Upserted::Inserted
+        );
+        assert!(h.upsert([1.0], 8).is_err());
+        assert!(!h.contains(8));
+    }
+
+    #[test]
+    fn connected_components_detects_a_cut() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(4)
+            .seed(107)
+            .build()
+            .unwrap();
+        assert_eq!(h.connected_components(), 0);
+        assert!(h.is_connected());
+        for (i, v) in rand_vecs(200, 8, 109).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        assert_eq!(h.connected_components(), 1);
+
+        // Cut node 17 out of layer 0 in both directions.
+        let cut = h.graph.by_ext[&17];
+        h.graph.nodes[cut].links[0].clear();
+        for n in h.graph.nodes.iter_mut() {
+            n.links[0].retain(|x| *x != cut);
+        }
+        assert_eq!(h.connected_components(), 2);
+        assert!(!h.is_connected());
+
+        // A tombstone is not a component of its own.
+        h.delete(17);
+        assert_eq!(h.connected_components(), 1);
+    }
+
+    #[test]
+    fn all_tombstones_search_like_an_empty_index() {
+        for ok in [false, true] {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(4)
+                .empty_returns_ok(ok)
+                .build()
+                .unwrap();
+            for i in 0..10u64 {
+                h.insert(vec![i as f32 + 1.0, 1.0, 0.5, 0.25], i).unwrap();
+            }
+            for i in 0..10u64 {
+                assert!(h.delete(i));
+            }
+            assert!(h.is_empty() && h.stats_detailed().tombstones == 10);
+            let res = h.search([1.0; 4], 3);
+            if ok {
+                assert!(res.unwrap().is_empty());
+            } else {
+                assert!(matches!(res, Err(VcalError::EmptyIndex)));
+            }
+        }
+    }
+
+    #[test]
+    fn degree_budget_caps_each_layer() {
+        let budget = |l: usize| match l {
+            0 => 10,
+            1 => 4,
+            _ => 2,
+        };
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(8)
+            .seed(149)
+            .degree_budget(Arc::new(budget))
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(1500, 8, 151).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        assert!(h.graph.max_level >= 2);
+        let mut full = [false; 3];
+        for n in &h.graph.nodes {
+            for (l, adj) in n.links.iter().enumerate() {
+                assert!(adj.len() <= budget(l), "layer {} degree {}", l, adj.len());
+                full[l.min(2)] |= adj.len() == budget(l);
+            }
+        }
+        assert_eq!(full, [true; 3]);
+        assert!(h.is_connected());
+        let q = &rand_vecs(1, 8, 151)[0];
+        assert_eq!(h.search(q, 1).unwrap()[0].0, 0);
+    }
+
+    #[test]
+    fn search_one_matches_search_k1() {
+        for (ties, exact) in [(false, 0), (true, 0), (true, 1000)] {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(8)
+                .m(6)
+                .ef_search(24)
+                .seed(157)
+                .recent_ties(ties)
+                .exact_below(exact)
+                .build()
+                .unwrap();
+            let data = rand_vecs(400, 8, 163);
+            for (i, v) in data.iter().enumerate() {
+                h.insert(v.clone(), i as u64).unwrap();
+            }
+            // Exact duplicates exercise the tie-break.
+            for i in 0..20u64 {
+                h.insert(data[i as usize].clone(), 1000 + i).unwrap();
+            }
+            for id in (0..400).step_by(7) {
+                h.delete(id);
+            }
+            let queries = rand_vecs(60, 8, 167).into_iter().chain(data[..30].to_vec());
+            for q in queries {
+                let (one, all) = (h.search_one(&q).unwrap(), h.search(&q, 1).unwrap());
+                // Without `recent_ties`, which of two exact duplicates wins
+                // is unspecified in either path.
+                if ties {
+                    assert_eq!(one, all.first().copied());
+                } else {
+                    assert_eq!(one.map(|h| h.1), all.first().map(|h| h.1));
+                }
+            }
+            assert!(h.search_one([0.0; 3]).is_err());
+        }
+
+        let h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .empty_returns_ok(true)
+            .build()
+            .unwrap();
+        assert_eq!(h.search_one([1.0; 4]).unwrap(), None);
+    }
+
+    #[test]
+    fn ef_construction_below_m_is_widened() {
+        let build = |efc: usize, efc0: Option<usize>| {
+            let b = HnswBuilder::<Cosine>::default()
+                .dims(4)
+                .m(8)
+                .seed(173)
+                .ef_construction(efc);
+            match efc0 {
+                Some(e) => b.ef_construction_layer0(e).build(),
+                None => b.build(),
+            }
+        };
+        let data = rand_vecs(200, 4, 173);
+        let links = |mut h: Hnsw<Cosine>| {
+            for (i, v) in data.iter().enumerate() {
+                h.insert(v.clone(), i as u64).unwrap();
+            }
+            h.graph
+                .nodes
+                .iter()
+                .map(|n| n.links.clone())
+                .collect::<Vec<_>>()
+        };
+        // Builder, layer-0 override and runtime setter all build exactly
+        // what `efc == m` would.
+        let reference = links(build(8, None).unwrap());
+        for (efc, efc0) in [(7, None), (8, Some(7)), (1, Some(1))] {
+            assert_eq!(links(build(efc, efc0).unwrap()), reference);
+        }
+        let mut h = build(8, None).unwrap();
+        h.set_ef_construction(2);
+        assert_eq!(links(h), reference);
+
+        // `rebuild_with` widens the same way.
+        let mut src = build(8, None).unwrap();
+        for (i, v) in data.iter().enumerate() {
+            src.insert(v.clone(), i as u64).unwrap();
+        }
+        let narrow = src.rebuild_with(8, 1, true).unwrap();
+        let wide = src.rebuild_with(8, 8, true).unwrap();
+        assert!(narrow
+            .graph
+            .nodes
+            .iter()
+            .zip(&wide.graph.nodes)
+            .all(|(x, y)| x.links == y.links));
+    }
+
+    #[test]
+    fn reseed_repeats_level_draws_mid_stream() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .m(2)
+            .build()
+            .unwrap();
+        let data = rand_vecs(300, 4, 179);
+        let draw = |h: &mut Hnsw<Cosine>, base: u64| -> Vec<usize> {
+            h.reseed(181);
+            data.iter()
+                .enumerate()
+                .map(|(i, v)| h.insert_with_level(v.clone(), base + i as u64).unwrap())
+                .collect()
+        };
+        let first = draw(&mut h, 0);
+        let second = draw(&mut h, 1000);
+        assert_eq!(first, second);
+        assert!(first.iter().any(|&l| l > 0));
+
+        let mut seeded = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .m(2)
+            .seed(181)
+            .build()
+            .unwrap();
+        let fresh: Vec<usize> = data
+            .iter()
+            .enumerate()
+            .map(|(i, v)| seeded.insert_with_level(v.clone(), i as u64).unwrap())
+            .collect();
+        assert_eq!(fresh, first);
+    }
+
+    #[test]
+    fn delete_matching_removes_even_ids() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(191)
+            .build()
+            .unwrap();
+        let data = rand_vecs(300, 8, 193);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        h.delete(1);
+        assert_eq!(h.delete_matching(|id| id % 2 == 0), 150);
+        assert_eq!(h.delete_matching(|id| id % 2 == 0), 0);
+        assert_eq!(h.len(), 149);
+        assert!(h.graph.bytes_consistent());
+        for (i, v) in data.iter().enumerate() {
+            let hits = h.search(v, 5).unwrap();
+            assert!(hits.iter().all(|&(id, _)| id % 2 == 1 && id != 1));
+            assert_eq!(h.contains(i as u64), i % 2 == 1 && i != 1);
+            if i % 2 == 1 && i != 1 {
+                assert_eq!(hits[0].0, i as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn flush_subnormals_keeps_results() {
+        // Every other component is subnormal, as in badly scaled input.
+        let denormalize = |v: Vec<f32>| -> Vec<f32> {
+            v.into_iter()
+                .enumerate()
+                .map(|(i, x)| if i % 2 == 0 { x } else { x * 1e-38 })
+                .collect()
+        };
+        let build = |flush: bool| {
+            let mut h = HnswBuilder::<Cosine>::default()
+                .dims(16)
+                .seed(197)
+                .flush_subnormals(flush)
+                .build()
+                .unwrap();
+            for (i, v) in rand_vecs(400, 16, 199).into_iter().enumerate() {
+                h.insert(denormalize(v), i as u64).unwrap();
+            }
+            h
+        };
+        let (plain, flushed) = (build(false), build(true));
+        assert!(plain
+            .graph
+            .nodes
+            .iter()
+            .any(|n| n.vec.iter().any(|x| x.is_subnormal())));
+        assert!(flushed
+            .graph
+            .nodes
+            .iter()
+            .all(|n| n.vec.iter().all(|x| !x.is_subnormal())));
+
+        for q in rand_vecs(40, 16, 211).into_iter().map(denormalize) {
+            let (a, b) = (plain.search(&q, 5).unwrap(), flushed.search(&q, 5).unwrap());
+            assert_eq!(a.len(), b.len());
+            for (x, y) in a.iter().zip(&b) {
+                assert_eq!(x.0, y.0);
+                assert!((x.1 - y.1).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn with_capacity_preallocates_a_default_index() {
+        let mut h = Hnsw::with_capacity(8, 300);
+        assert!(h.capacity() >= 300);
+        assert_eq!((h.dims(), h.m_max0(), h.len()), (8, 32, 0));
+        for (i, v) in rand_vecs(300, 8, 223).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        assert_eq!(h.search([0.5; 8], 3).unwrap().len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "dims > 0")]
+    fn new_rejects_zero_dims() {
+        let _ = Hnsw::new(0);
+    }
+
+    #[test]
+    fn pack_vectors_keeps_searches_and_compacts() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(227)
+            .build()
+            .unwrap();
+        let data = rand_vecs(300, 8, 229);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        let queries = rand_vecs(30, 8, 233);
+        let before: Vec<_> = queries.iter().map(|q| h.search(q, 5).unwrap()).collect();
+
+        assert_eq!(h.pack_vectors(), 300 * 8);
+        let after: Vec<_> = queries.iter().map(|q| h.search(q, 5).unwrap()).collect();
+        assert_eq!(before, after);
+        // Consecutive nodes sit back to back in one allocation.
+        let ptr = |nid: usize| h.graph.nodes[nid].vec.as_ptr();
+        assert!((1..300).all(|nid| ptr(nid) == ptr(nid - 1).wrapping_add(8)));
+
+        // Deletes and inserts after packing; repacking drops the dead space.
+        h.delete_matching(|id| id % 3 == 0);
+        h.insert(vec![0.5; 8], 1000).unwrap();
+        h.update_vector(1, data[2].clone()).unwrap();
+        assert_eq!(h.pack_vectors(), h.len() * 8);
+        assert!(h.graph.bytes_consistent());
+        let vec_of = |id: u64| &h.graph.nodes[h.graph.by_ext[&id]].vec[..];
+        assert_eq!(vec_of(1), &data[2][..]);
+        assert_eq!(h.search([0.5; 8], 1).unwrap()[0].0, 1000);
+        for id in (0..300u64).filter(|id| id % 3 != 0 && *id != 1) {
+            assert_eq!(vec_of(id), &data[id as usize][..]);
+        }
+    }
+
+    #[test]
+    fn finalize_ignores_links_from_tombstones() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m(4)
+            .seed(3)
+            .build()
+            .unwrap();
+        let data = rand_vecs(100, 8, 6);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        let dead = h.graph.by_ext[&5];
+        h.delete(5);
+
+        // Node 10's only layer-0 in-link now comes from the tombstone.
+        let orphan = h.graph.by_ext[&10];
+        for n in h.graph.nodes.iter_mut() {
+            n.links[0].retain(|x| *x != orphan);
+        }
+        h.graph.nodes[dead].links[0].push(orphan);
+        assert_eq!(h.audit().orphans, 1);
+
+        assert_eq!(h.finalize().orphans_fixed, 1);
+        assert_eq!(h.audit().orphans, 0);
+    }
+
+    #[test]
+    fn borrowed_call_sites_still_compile() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(3).build().unwrap();
+        let owned: Vec<f32> = vec![1.0, 0.0, 0.0];
+        h.insert(owned.clone(), 1).unwrap();
+        h.insert(vec![0.0, 1.0, 0.0], 2).unwrap();
+        h.try_insert(vec![0.0, 0.0, 1.0], 3).unwrap();
+
+        let slice: &[f32] = &[0.0, 1.0, 0.0];
+        let array = [0.0f32, 0.0, 1.0];
+        assert_eq!(h.search(&owned, 1).unwrap()[0].0, 1);
+        assert_eq!(h.search(slice, 1).unwrap()[0].0, 2);
+        assert_eq!(h.search(&array, 1).unwrap()[0].0, 3);
+        assert_eq!(h.search(&owned[..], 1).unwrap()[0].0, 1);
+        assert_eq!(h.search_with_ef(&owned, 1, 8).unwrap()[0].0, 1);
+        assert_eq!(h.search_with_ef(slice, 1, 8).unwrap()[0].0, 2);
+        assert!(h.insert_with_level(owned, 4).is_ok());
+    }
+
+    #[test]
+    fn hnsw_search_into_touches_exactly_the_hits() {
+        use std::sync::atomic::Ordering;
+
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .track_access(true)
+            .build()
+            .unwrap();
+        let data = rand_vecs(100, 8, 160);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        for n in &h.graph.nodes {
+            n.last_hit.store(0, Ordering::Relaxed);
+        }
+
+        let mut out = Vec::new();
+        h.search_into(&data[3], 5, &mut out).unwrap();
+        assert_eq!(out.len(), 5);
+        for (nid, n) in h.graph.nodes.iter().enumerate() {
+            let hit = out.iter().any(|&(id, _)| h.graph.by_ext[&id] == nid);
+            assert_eq!(n.last_hit.load(Ordering::Relaxed) > 0, hit);
+        }
+    }
 }
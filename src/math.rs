@@ -2,54 +2,678 @@
 //!
 //! * `Metric` trait abstracts over cosine / dot etc.
 //! * Works on stable Rust 1.56.
-//! * Fully safe scalar implementation in v0.1.1.
+//! * Fully safe: inner loops accumulate into `LANES` independent partial sums
+//!   and reduce once at the end, which LLVM auto-vectorises without intrinsics.
+//!   There are no per-arch kernels.
+//! * The `scalar` feature swaps the lane kernels for strictly sequential
+//!   loops, to rule out accumulation order when chasing a divergence.
+
+/// Width of the partial-sum accumulators (one AVX2 register of f32).
+///
+/// Tail handling: the trailing `len % LANES` elements (at most 7) are added
+/// sequentially after the lane reduction. For odd dims such as 100 or 300
+/// that is 4 scalar steps, which is cheaper than a masked load would save.
+const LANES: usize = 8;
+
+/// Whether the distance kernels run on AVX2 (x86_64 only). The lane loops
+/// are auto-vectorised at compile time, so this is decided by the build's
+/// target features, not by the CPU: a default `x86_64` build uses SSE2
+/// even on AVX2 hardware unless compiled with `-C target-cpu=native` (or
+/// `-C target-feature=+avx2`). There is no runtime dispatch to detect.
+/// Always `false` under the `scalar` feature.
+pub fn simd_active() -> bool {
+    !SCALAR && cfg!(all(target_arch = "x86_64", target_feature = "avx2"))
+}
+
+/// `scalar` feature: every kernel below takes its sequential twin instead.
+const SCALAR: bool = cfg!(feature = "scalar");
+
+/// Sum of `a[i] * b[i]`, one accumulator, in index order.
+#[inline]
+fn dot_seq(a: &[f32], b: &[f32]) -> f32 {
+    let mut dot = 0.0_f32;
+    for (&x, &y) in a.iter().zip(b) {
+        dot += x * y;
+    }
+    dot
+}
+
+/// `(a·b, |a|², |b|²)`, one accumulator each, in index order.
+#[inline]
+fn cosine_terms_seq(a: &[f32], b: &[f32]) -> (f32, f32, f32) {
+    let (mut dot, mut na, mut nb) = (0.0_f32, 0.0_f32, 0.0_f32);
+    for (&x, &y) in a.iter().zip(b) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    (dot, na, nb)
+}
+
+/// Sum of `a[i] * b[i]`, accumulated per lane with a single final reduction.
+#[inline]
+fn dot_lanes(a: &[f32], b: &[f32]) -> f32 {
+    if SCALAR {
+        return dot_seq(a, b);
+    }
+    let mut acc = [0.0_f32; LANES];
+    let (ca, cb) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let (ta, tb) = (ca.remainder(), cb.remainder());
+    for (x, y) in ca.zip(cb) {
+        for i in 0..LANES {
+            acc[i] += x[i] * y[i];
+        }
+    }
+    let mut dot: f32 = acc.iter().sum();
+    for (x, y) in ta.iter().zip(tb) {
+        dot += x * y;
+    }
+    dot
+}
+
+/// `(a·b, |a|², |b|²)` in one pass, same accumulation scheme as `dot_lanes`.
+#[inline]
+fn cosine_terms_lanes(a: &[f32], b: &[f32]) -> (f32, f32, f32) {
+    if SCALAR {
+        return cosine_terms_seq(a, b);
+    }
+    let (mut dot, mut na, mut nb) = ([0.0_f32; LANES], [0.0_f32; LANES], [0.0_f32; LANES]);
+    let (ca, cb) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let (ta, tb) = (ca.remainder(), cb.remainder());
+    for (x, y) in ca.zip(cb) {
+        for i in 0..LANES {
+            dot[i] += x[i] * y[i];
+            na[i] += x[i] * x[i];
+            nb[i] += y[i] * y[i];
+        }
+    }
+    let (mut dot, mut na, mut nb): (f32, f32, f32) =
+        (dot.iter().sum(), na.iter().sum(), nb.iter().sum());
+    for (&x, &y) in ta.iter().zip(tb) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    (dot, na, nb)
+}
+
+/// `(a·b, |b|²)` in one pass — the per-candidate half of cosine when `|a|²`
+/// is already known.
+#[inline]
+fn dot_and_norm_lanes(a: &[f32], b: &[f32]) -> (f32, f32) {
+    if SCALAR {
+        let (dot, _, nb) = cosine_terms_seq(a, b);
+        return (dot, nb);
+    }
+    let (mut dot, mut nb) = ([0.0_f32; LANES], [0.0_f32; LANES]);
+    let (ca, cb) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let (ta, tb) = (ca.remainder(), cb.remainder());
+    for (x, y) in ca.zip(cb) {
+        for i in 0..LANES {
+            dot[i] += x[i] * y[i];
+            nb[i] += y[i] * y[i];
+        }
+    }
+    let (mut dot, mut nb): (f32, f32) = (dot.iter().sum(), nb.iter().sum());
+    for (&x, &y) in ta.iter().zip(tb) {
+        dot += x * y;
+        nb += y * y;
+    }
+    (dot, nb)
+}
+
+/// L2-normalised copy of `v` (returned unchanged if its norm is zero).
+#[inline]
+pub(crate) fn l2_normalized(v: &[f32]) -> Vec<f32> {
+    let norm = dot_lanes(v, v).sqrt();
+    if norm == 0.0 {
+        return v.to_vec();
+    }
+    v.iter().map(|x| x / norm).collect()
+}
+
+/// Copy of `v` with subnormal components flushed to zero (sign kept), or
+/// `None` if it has none, so the common case costs a scan and no copy.
+#[inline]
+pub(crate) fn flushed_subnormals(v: &[f32]) -> Option<Vec<f32>> {
+    if !v.iter().any(|x| x.is_subnormal()) {
+        return None;
+    }
+    Some(
+        v.iter()
+            .map(|&x| {
+                if x.is_subnormal() {
+                    0.0_f32.copysign(x)
+                } else {
+                    x
+                }
+            })
+            .collect(),
+    )
+}
 
 pub trait Metric: Send + Sync + 'static {
     fn distance(&self, a: &[f32], b: &[f32]) -> f32;
+
+    /// Distances from `query` to each of `candidates`, written to `out`
+    /// (`out.len()` must equal `candidates.len()`). Override to reuse
+    /// per-query work across candidates.
+    fn distance_batch(&self, query: &[f32], candidates: &[&[f32]], out: &mut [f32]) {
+        debug_assert_eq!(candidates.len(), out.len());
+        for (o, c) in out.iter_mut().zip(candidates) {
+            *o = self.distance(query, c);
+        }
+    }
+
+    /// `distance`, allowed to stop early once the result is known to exceed
+    /// `bound`: it must equal `distance` bit for bit whenever that is `<= bound`,
+    /// and otherwise may return any value `> bound`. The beam search passes
+    /// its current worst kept distance, so partial sums that already exceed
+    /// it are abandoned. Only sound for metrics that accumulate non-negative
+    /// terms (squared L2, L1, Hamming); cosine and dot products can still
+    /// fall as terms are added, so they keep this default (full distance).
+    #[inline]
+    fn distance_bounded(&self, a: &[f32], b: &[f32], bound: f32) -> f32 {
+        let _ = bound;
+        self.distance(a, b)
+    }
+
+    /// Theoretical `(min, max)` of `distance`, or `None` if unbounded.
+    /// Lets callers normalise scores without guessing the metric's scale.
+    fn distance_range(&self) -> Option<(f32, f32)> {
+        None
+    }
+
+    /// Map a distance to a similarity in `[0, 1]` (1 = identical), strictly
+    /// decreasing in `dist`. Bounded metrics map linearly over
+    /// `distance_range` (cosine: `1 - dist / 2`); unbounded ones fall back to
+    /// `1 / (1 + max(dist, 0))`.
+    fn normalized_similarity(&self, dist: f32) -> f32 {
+        match self.distance_range() {
+            Some((lo, hi)) if hi > lo => (1.0 - (dist - lo) / (hi - lo)).clamp(0.0, 1.0),
+            _ => 1.0 / (1.0 + dist.max(0.0)),
+        }
+    }
+
+    /// Short stable identifier for logs and snapshot metadata.
+    fn name(&self) -> &'static str {
+        "unknown"
+    }
 }
 
+/// Cosine distance `1 - cos θ`.
+///
+/// * Zero norm (either side exactly 0): returns 1.0, i.e. "orthogonal".
+/// * Tiny norm (`|a|·|b|` below `COSINE_EPS`): the denominator is clamped to
+///   `COSINE_EPS`, which shrinks `cos θ` towards 0. Use [`CosineEps`] with a
+///   smaller (or zero) epsilon for very small-magnitude embeddings.
+/// * Normal norms: exact.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Cosine;
 
+/// Default denominator floor used by [`Cosine`].
+pub const COSINE_EPS: f32 = 1e-12;
+
+#[inline]
+fn cosine_from_terms(dot: f32, na: f32, nb: f32) -> f32 {
+    cosine_from_terms_eps(dot, na, nb, COSINE_EPS)
+}
+
+#[inline]
+fn cosine_from_terms_eps(dot: f32, na: f32, nb: f32, eps: f32) -> f32 {
+    if na == 0.0 || nb == 0.0 {
+        return 1.0;
+    }
+
+    let denom = (na.sqrt() * nb.sqrt()).max(eps);
+    let cos = (dot / denom).clamp(-1.0, 1.0);
+    1.0 - cos
+}
+
+/// [`Cosine`] with a configurable denominator floor. `epsilon = 0.0` makes
+/// every non-zero norm exact; exact zero norms still return 1.0.
+#[derive(Debug, Clone, Copy)]
+pub struct CosineEps {
+    pub epsilon: f32,
+}
+
+impl Default for CosineEps {
+    fn default() -> Self {
+        Self {
+            epsilon: COSINE_EPS,
+        }
+    }
+}
+
+impl Metric for CosineEps {
+    #[inline]
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        debug_assert_eq!(a.len(), b.len());
+
+        let (dot, na, nb) = cosine_terms_lanes(a, b);
+        cosine_from_terms_eps(dot, na, nb, self.epsilon)
+    }
+
+    #[inline]
+    fn distance_range(&self) -> Option<(f32, f32)> {
+        Some((0.0, 2.0))
+    }
+
+    fn name(&self) -> &'static str {
+        "cosine_eps"
+    }
+}
+
+impl Cosine {
+    /// Reference path: strictly sequential accumulation, no lane splitting.
+    /// Used to catch accumulation-order divergence in the fast path.
+    #[cfg(test)]
+    pub(crate) fn distance_scalar(&self, a: &[f32], b: &[f32]) -> f32 {
+        let (dot, na, nb) = cosine_terms_seq(a, b);
+        cosine_from_terms(dot, na, nb)
+    }
+}
+
 impl Metric for Cosine {
     #[inline]
     fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
         debug_assert_eq!(a.len(), b.len());
 
-        let (mut dot, mut na, mut nb) = (0.0_f32, 0.0_f32, 0.0_f32);
+        let (dot, na, nb) = cosine_terms_lanes(a, b);
+        cosine_from_terms(dot, na, nb)
+    }
 
-        for i in 0..a.len() {
-            let x = a[i];
-            let y = b[i];
-            dot += x * y;
-            na += x * x;
-            nb += y * y;
+    /// Computes the query norm once for the whole batch.
+    fn distance_batch(&self, query: &[f32], candidates: &[&[f32]], out: &mut [f32]) {
+        debug_assert_eq!(candidates.len(), out.len());
+        let nq = dot_lanes(query, query);
+        for (o, c) in out.iter_mut().zip(candidates) {
+            debug_assert_eq!(query.len(), c.len());
+            let (dot, nc) = dot_and_norm_lanes(query, c);
+            *o = cosine_from_terms(dot, nq, nc);
         }
+    }
 
-        if na == 0.0 || nb == 0.0 {
-            return 1.0;
-        }
+    /// `1 - cos θ` lies in `[0, 2]`.
+    #[inline]
+    fn distance_range(&self) -> Option<(f32, f32)> {
+        Some((0.0, 2.0))
+    }
 
-        const EPS: f32 = 1e-12;
-        let denom = (na.sqrt() * nb.sqrt()).max(EPS);
-        let cos = (dot / denom).clamp(-1.0, 1.0);
-        1.0 - cos
+    fn name(&self) -> &'static str {
+        "cosine"
     }
 }
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Dot;
 
+impl Dot {
+    /// Reference path: strictly sequential accumulation, no lane splitting.
+    #[cfg(test)]
+    pub(crate) fn distance_scalar(&self, a: &[f32], b: &[f32]) -> f32 {
+        1.0 - dot_seq(a, b)
+    }
+}
+
 impl Metric for Dot {
     #[inline]
     fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
         debug_assert_eq!(a.len(), b.len());
 
+        1.0 - dot_lanes(a, b)
+    }
+
+    /// `1 - a·b` is unbounded for unnormalised inputs.
+    #[inline]
+    fn distance_range(&self) -> Option<(f32, f32)> {
+        None
+    }
+
+    /// `(1 + a·b) / 2`: the cosine mapping, exact for unit vectors and
+    /// clamped to `[0, 1]` otherwise.
+    #[inline]
+    fn normalized_similarity(&self, dist: f32) -> f32 {
+        (1.0 - dist / 2.0).clamp(0.0, 1.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "dot"
+    }
+}
+
+/// Weighted blend of two metrics: `alpha * a + (1 - alpha) * b`.
+///
+/// Both components see the same slices. Their scales add directly, so
+/// blending metrics with different ranges (e.g. `Cosine` in `[0, 2]` with an
+/// unbounded `Dot`) lets the wider one dominate whatever `alpha` says; check
+/// `distance_range` on each side and normalise inputs before relying on the
+/// weight. `Default` is an even 50/50 blend.
+#[derive(Debug, Clone, Copy)]
+pub struct Blend<A: Metric, B: Metric> {
+    pub a: A,
+    pub b: B,
+    /// Weight of `a`, in `[0, 1]`.
+    pub alpha: f32,
+}
+
+impl<A: Metric, B: Metric> Blend<A, B> {
+    /// `alpha` is clamped to `[0, 1]`.
+    pub fn new(a: A, b: B, alpha: f32) -> Self {
+        Self {
+            a,
+            b,
+            alpha: alpha.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl<A: Metric + Default, B: Metric + Default> Default for Blend<A, B> {
+    fn default() -> Self {
+        Self::new(A::default(), B::default(), 0.5)
+    }
+}
+
+impl<A: Metric, B: Metric> Metric for Blend<A, B> {
+    #[inline]
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        self.alpha * self.a.distance(a, b) + (1.0 - self.alpha) * self.b.distance(a, b)
+    }
+
+    /// Blended bounds when both components are bounded, else `None`.
+    #[inline]
+    fn distance_range(&self) -> Option<(f32, f32)> {
+        let (lo_a, hi_a) = self.a.distance_range()?;
+        let (lo_b, hi_b) = self.b.distance_range()?;
+        let w = self.alpha;
+        Some((w * lo_a + (1.0 - w) * lo_b, w * hi_a + (1.0 - w) * hi_b))
+    }
+
+    /// Just `"blend"`; the component names are on `a` and `b`.
+    fn name(&self) -> &'static str {
+        "blend"
+    }
+}
+
+/// Cosine distance over sparse vectors in the lane encoding used by
+/// [`SparseHnsw`](crate::SparseHnsw): lane 0 holds `|v|²`, followed by
+/// `(index, value)` pairs with the index stored as raw bits
+/// (`f32::from_bits`) in ascending order. The dot product merges the two
+/// index lists, so cost scales with non-zeros, not logical dimensions.
+/// Zero and tiny norms behave as in [`Cosine`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SparseCosine;
+
+impl SparseCosine {
+    /// Encode `(index, value)` pairs, already sorted by unique index.
+    pub(crate) fn encode(pairs: &[(u32, f32)]) -> Vec<f32> {
+        let mut lanes = Vec::with_capacity(1 + 2 * pairs.len());
+        lanes.push(pairs.iter().map(|&(_, v)| v * v).sum());
+        for &(i, v) in pairs {
+            lanes.push(f32::from_bits(i));
+            lanes.push(v);
+        }
+        lanes
+    }
+}
+
+impl Metric for SparseCosine {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        let (na, nb) = (
+            a.first().copied().unwrap_or(0.0),
+            b.first().copied().unwrap_or(0.0),
+        );
+        let (mut pa, mut pb) = (
+            a.get(1..).unwrap_or(&[]).chunks_exact(2),
+            b.get(1..).unwrap_or(&[]).chunks_exact(2),
+        );
+        let (mut x, mut y) = (pa.next(), pb.next());
         let mut dot = 0.0_f32;
-        for i in 0..a.len() {
-            dot += a[i] * b[i];
+        while let (Some(u), Some(v)) = (x, y) {
+            match u[0].to_bits().cmp(&v[0].to_bits()) {
+                std::cmp::Ordering::Less => x = pa.next(),
+                std::cmp::Ordering::Greater => y = pb.next(),
+                std::cmp::Ordering::Equal => {
+                    dot += u[1] * v[1];
+                    x = pa.next();
+                    y = pb.next();
+                }
+            }
         }
+        cosine_from_terms(dot, na, nb)
+    }
+
+    #[inline]
+    fn distance_range(&self) -> Option<(f32, f32)> {
+        Some((0.0, 2.0))
+    }
+
+    fn name(&self) -> &'static str {
+        "sparse_cosine"
+    }
+}
+
+/// Hamming distance over bit patterns: each `f32` lane carries 32 raw bits
+/// (`f32::from_bits`), and the distance is the number of differing bits,
+/// counted with `count_ones`. The result is an exact integer as long as it
+/// stays below 2^24, i.e. for codes up to 16M bits. Values are only ever
+/// compared by `to_bits`, never used as floats, so NaN patterns are fine.
+/// Use it through [`BinaryHnsw`](crate::BinaryHnsw), which packs `u64` words.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HammingBits;
+
+impl Metric for HammingBits {
+    #[inline]
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        debug_assert_eq!(a.len(), b.len());
+
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x.to_bits() ^ y.to_bits()).count_ones())
+            .sum::<u32>() as f32
+    }
+
+    /// Counts 8 lanes (256 bits) at a time, stopping once past `bound`.
+    fn distance_bounded(&self, a: &[f32], b: &[f32], bound: f32) -> f32 {
+        debug_assert_eq!(a.len(), b.len());
+
+        let mut acc = 0u32;
+        for (ca, cb) in a.chunks(8).zip(b.chunks(8)) {
+            acc += ca
+                .iter()
+                .zip(cb)
+                .map(|(x, y)| (x.to_bits() ^ y.to_bits()).count_ones())
+                .sum::<u32>();
+            if acc as f32 > bound {
+                break;
+            }
+        }
+        acc as f32
+    }
+
+    fn name(&self) -> &'static str {
+        "hamming_bits"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn lane_path_handles_partial_tail() {
+        for &d in &[1usize, 7, 8, 9, 100, 129] {
+            let a: Vec<f32> = (0..d).map(|i| ((i * 7 + 3) as f32).sin()).collect();
+            let b: Vec<f32> = (0..d).map(|i| ((i * 5 + 1) as f32).cos() * 0.1).collect();
+            assert!(
+                (Cosine.distance(&a, &b) - Cosine.distance_scalar(&a, &b)).abs() < 1e-4,
+                "cosine d={}",
+                d
+            );
+            assert!(
+                (Dot.distance(&a, &b) - Dot.distance_scalar(&a, &b)).abs() < 1e-4,
+                "dot d={}",
+                d
+            );
+        }
+    }
+
+    #[test]
+    fn lane_path_agrees_with_scalar_on_random_vectors() {
+        let mut rng = StdRng::seed_from_u64(113);
+        for _ in 0..200 {
+            let d = rng.random_range(1..800);
+            let a: Vec<f32> = (0..d).map(|_| rng.random::<f32>() * 2.0 - 1.0).collect();
+            let b: Vec<f32> = (0..d).map(|_| rng.random::<f32>() * 2.0 - 1.0).collect();
+            let (c, cs) = (Cosine.distance(&a, &b), Cosine.distance_scalar(&a, &b));
+            assert!((c - cs).abs() < 1e-4, "cosine d={}: {} vs {}", d, c, cs);
+            // Dot is unnormalised; compare relative to the magnitude of the sum.
+            let (o, os) = (Dot.distance(&a, &b), Dot.distance_scalar(&a, &b));
+            assert!(
+                (o - os).abs() < 1e-4 * os.abs().max(1.0),
+                "dot d={}: {} vs {}",
+                d,
+                o,
+                os
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "scalar")]
+    fn scalar_feature_matches_reference_exactly() {
+        let a: Vec<f32> = (0..100).map(|i| ((i * 7 + 3) as f32).sin()).collect();
+        let b: Vec<f32> = (0..100).map(|i| ((i * 5 + 1) as f32).cos()).collect();
+        assert_eq!(Cosine.distance(&a, &b), Cosine.distance_scalar(&a, &b));
+        assert_eq!(Dot.distance(&a, &b), Dot.distance_scalar(&a, &b));
+        assert!(!simd_active());
+    }
+
+    #[test]
+    fn distance_batch_matches_per_element() {
+        let q: Vec<f32> = (0..37).map(|i| (i as f32 * 0.3).sin()).collect();
+        let cands: Vec<Vec<f32>> = (0..10)
+            .map(|j| (0..37).map(|i| ((i * j) as f32 * 0.1).cos()).collect())
+            .collect();
+        let refs: Vec<&[f32]> = cands.iter().map(|c| &c[..]).collect();
+
+        fn check<M: Metric>(m: M, q: &[f32], refs: &[&[f32]]) {
+            let mut out = vec![0.0; refs.len()];
+            m.distance_batch(q, refs, &mut out);
+            for (o, c) in out.iter().zip(refs) {
+                assert!((o - m.distance(q, c)).abs() < 1e-6);
+            }
+        }
+        check(Cosine, &q, &refs);
+        check(Dot, &q, &refs);
+    }
+
+    #[test]
+    fn cosine_epsilon_cases() {
+        let tiny = [1e-7_f32, 0.0];
+        // Zero norm: orthogonal by convention, regardless of epsilon.
+        assert_eq!(CosineEps { epsilon: 0.0 }.distance(&[0.0, 0.0], &tiny), 1.0);
+        // Tiny norm: default floor (1e-12 > 1e-14) distorts, epsilon 0 is exact.
+        assert!(Cosine.distance(&tiny, &tiny) > 0.9);
+        assert!(CosineEps { epsilon: 0.0 }.distance(&tiny, &tiny) < 1e-6);
+        // Normal norms: identical to Cosine.
+        let (a, b) = ([1.0, 2.0], [2.0, -1.0]);
+        assert_eq!(
+            CosineEps::default().distance(&a, &b),
+            Cosine.distance(&a, &b)
+        );
+    }
+
+    #[test]
+    fn cosine_distance_stays_in_range() {
+        let (lo, hi) = Cosine.distance_range().unwrap();
+        let same = Cosine.distance(&[1.0, 2.0], &[1.0, 2.0]);
+        let opposite = Cosine.distance(&[1.0, 2.0], &[-1.0, -2.0]);
+        assert!(same >= lo && same < 1e-6);
+        assert!(opposite <= hi && (opposite - hi).abs() < 1e-6);
+    }
+
+    #[test]
+    fn dot_distance_is_unbounded() {
+        assert!(Dot.distance_range().is_none());
+    }
+
+    #[test]
+    fn even_blend_matches_manual_mix() {
+        let mut rng = StdRng::seed_from_u64(71);
+        let blend = Blend::<Cosine, Dot>::default();
+        for _ in 0..20 {
+            let a: Vec<f32> = (0..13).map(|_| rng.random::<f32>() - 0.5).collect();
+            let b: Vec<f32> = (0..13).map(|_| rng.random::<f32>() - 0.5).collect();
+            let want = 0.5 * Cosine.distance(&a, &b) + 0.5 * Dot.distance(&a, &b);
+            assert!((blend.distance(&a, &b) - want).abs() < 1e-6);
+        }
+        assert_eq!(blend.distance_range(), None);
+
+        let bounded = Blend::new(Cosine, CosineEps::default(), 2.0);
+        assert_eq!(bounded.alpha, 1.0);
+        assert_eq!(bounded.distance_range(), Some((0.0, 2.0)));
+    }
+
+    #[test]
+    fn metric_names() {
+        struct Custom;
+        impl Metric for Custom {
+            fn distance(&self, _: &[f32], _: &[f32]) -> f32 {
+                0.0
+            }
+        }
+        assert_eq!(Cosine.name(), "cosine");
+        assert_eq!(CosineEps::default().name(), "cosine_eps");
+        assert_eq!(Dot.name(), "dot");
+        assert_eq!(Blend::<Cosine, Dot>::default().name(), "blend");
+        assert_eq!(Custom.name(), "unknown");
+    }
+
+    #[test]
+    fn hamming_bits_counts_differing_bits() {
+        let a = [f32::from_bits(0b1011), f32::from_bits(u32::MAX)];
+        let b = [f32::from_bits(0b0001), f32::from_bits(0)];
+        assert_eq!(HammingBits.distance(&a, &b), 34.0);
+        assert_eq!(HammingBits.distance(&a, &a), 0.0);
+    }
+
+    #[test]
+    fn sparse_cosine_matches_dense_cosine() {
+        let dense_a = [0.0, 1.5, 0.0, -2.0, 0.0, 0.25, 0.0, 0.0];
+        let dense_b = [3.0, 0.5, 0.0, 1.0, 0.0, 0.0, 0.0, -1.0];
+        let sparse = |d: &[f32]| {
+            let pairs: Vec<(u32, f32)> = d
+                .iter()
+                .enumerate()
+                .filter(|(_, &v)| v != 0.0)
+                .map(|(i, &v)| (i as u32, v))
+                .collect();
+            SparseCosine::encode(&pairs)
+        };
+        let (sa, sb) = (sparse(&dense_a), sparse(&dense_b));
+        let want = Cosine.distance(&dense_a, &dense_b);
+        assert!((SparseCosine.distance(&sa, &sb) - want).abs() < 1e-6);
+        assert!(SparseCosine.distance(&sa, &sa).abs() < 1e-6);
+        // Disjoint supports are orthogonal; an empty vector has zero norm.
+        let (sc, empty) = (SparseCosine::encode(&[(2, 1.0)]), SparseCosine::encode(&[]));
+        assert_eq!(SparseCosine.distance(&sa, &sc), 1.0);
+        assert_eq!(SparseCosine.distance(&sa, &empty), 1.0);
+    }
 
-        1.0 - dot
+    #[test]
+    fn normalized_similarity_per_metric() {
+        assert_eq!(Cosine.normalized_similarity(0.0), 1.0);
+        assert_eq!(Cosine.normalized_similarity(1.0), 0.5);
+        assert_eq!(Cosine.normalized_similarity(2.0), 0.0);
+        // Dot over unit vectors agrees with cosine.
+        let (a, b) = ([0.6f32, 0.8], [0.8f32, -0.6]);
+        assert_eq!(Dot.normalized_similarity(Dot.distance(&a, &b)), 0.5);
+        assert_eq!(Dot.normalized_similarity(-3.0), 1.0);
+        // Unbounded metrics use 1 / (1 + d).
+        assert_eq!(HammingBits.normalized_similarity(0.0), 1.0);
+        assert_eq!(HammingBits.normalized_similarity(3.0), 0.25);
     }
 }
@@ -1,39 +1,126 @@
 //! node.rs — node definition and helpers for VCAL-core
 
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use smallvec::SmallVec;
+use std::ops::Deref;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub type NodeId = usize;
 
+/// Neighbour list of one node on one layer. Upper layers hold at most `m`
+/// (16 by default) entries, so they stay inline; layer 0 (`2 * m`) spills.
+pub(crate) type Adj = SmallVec<[NodeId; 16]>;
+
+/// Per-layer adjacency, outermost index = layer. Two layers inline covers
+/// all but ~M^-2 of nodes, so back-edge resizes on neighbours rarely allocate.
+pub(crate) type Links = SmallVec<[Adj; 2]>;
+
 pub(crate) const MAX_LINKS_PER_LVL: usize = 32;
 
-pub struct Node {
-    pub(crate) ext_id: u64,
-    pub(crate) vec: Vec<f32>,
-    pub(crate) links: Vec<Vec<NodeId>>,
+/// Current wall-clock time in Unix milliseconds (the `last_hit` unit).
+#[inline]
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// A node's vector: `len` components at `start` in a shared buffer. The
+/// buffer is normally the vector's own allocation, shared so several
+/// indexes can hold it (`insert_shared`); after `Graph::pack_vectors` it is
+/// one arena holding every live vector back to back.
+#[derive(Clone)]
+pub(crate) struct VecSlot {
+    buf: Arc<[f32]>,
+    // `u32` keeps `Node` small; arenas are capped at `u32::MAX` floats.
+    start: u32,
+    len: u32,
+}
+
+impl VecSlot {
+    /// The empty vector a tombstone keeps.
+    pub(crate) fn empty() -> Self {
+        Vec::new().into()
+    }
+
+    /// `len` components at `start` in `arena`.
+    pub(crate) fn in_arena(arena: &Arc<[f32]>, start: usize, len: usize) -> Self {
+        assert!(start + len <= arena.len() && arena.len() <= u32::MAX as usize);
+        Self {
+            buf: Arc::clone(arena),
+            start: start as u32,
+            len: len as u32,
+        }
+    }
+
+    /// The vector as a standalone `Arc`: the buffer itself when this slot
+    /// spans all of it, otherwise a copy out of the arena.
+    pub(crate) fn to_arc(&self) -> Arc<[f32]> {
+        if self.start == 0 && self.len as usize == self.buf.len() {
+            Arc::clone(&self.buf)
+        } else {
+            Arc::from(&**self)
+        }
+    }
+
+    /// Whether this slot views `buf` (the same allocation at the same spot).
+    #[cfg(test)]
+    pub(crate) fn shares(&self, buf: &Arc<[f32]>) -> bool {
+        self.as_ptr() == buf.as_ptr()
+    }
+}
+
+impl Deref for VecSlot {
+    type Target = [f32];
+
+    #[inline]
+    fn deref(&self) -> &[f32] {
+        let start = self.start as usize;
+        &self.buf[start..start + self.len as usize]
+    }
+}
+
+impl From<Arc<[f32]>> for VecSlot {
+    fn from(buf: Arc<[f32]>) -> Self {
+        let len = u32::try_from(buf.len()).expect("vector longer than u32::MAX");
+        Self { buf, start: 0, len }
+    }
+}
+
+impl From<Vec<f32>> for VecSlot {
+    fn from(v: Vec<f32>) -> Self {
+        Arc::<[f32]>::from(v).into()
+    }
+}
+
+pub struct Node<Id = u64> {
+    pub(crate) ext_id: Id,
+    pub(crate) vec: VecSlot,
+    pub(crate) links: Links,
+    /// Last insert/search hit, Unix milliseconds.
     pub(crate) last_hit: AtomicU64,
     pub(crate) deleted: AtomicBool,
     pub(crate) bytes: usize,
+    /// Monotonic insertion sequence number (higher = inserted later).
+    pub(crate) seq: u64,
 }
 
-impl Node {
-    pub fn new(ext_id: u64, level: usize, vec: Vec<f32>) -> Self {
-        let mut links = Vec::with_capacity(level + 1);
-        for _ in 0..=level {
-            links.push(Vec::new());
-        }
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+impl<Id> Node<Id> {
+    pub fn new(ext_id: Id, level: usize, vec: Arc<[f32]>) -> Self {
+        let mut links = Links::new();
+        links.resize(level + 1, Adj::new());
+        let now = now_millis();
 
         let mut s = Self {
             ext_id,
-            vec,
+            vec: vec.into(),
             links,
             last_hit: AtomicU64::new(now),
             deleted: AtomicBool::new(false),
             bytes: 0,
+            seq: 0,
         };
         s.recompute_bytes();
         s
@@ -49,13 +136,36 @@ impl Node {
         self.deleted.load(Ordering::Relaxed)
     }
 
+    /// Footprint of the vector and neighbour lists, without updating `bytes`.
     #[inline]
-    pub fn recompute_bytes(&mut self) -> usize {
+    pub fn compute_bytes(&self) -> usize {
         let mut b = self.vec.len() * std::mem::size_of::<f32>();
         for l in &self.links {
             b += l.len() * std::mem::size_of::<NodeId>();
         }
-        self.bytes = b;
         b
     }
+
+    #[inline]
+    pub fn recompute_bytes(&mut self) -> usize {
+        self.bytes = self.compute_bytes();
+        self.bytes
+    }
+}
+
+/// Bytes per vector beyond what `bytes` counts: the `Node` struct itself,
+/// the `Arc` header, and its entries in the id map and level registry.
+pub(crate) fn node_overhead<Id>() -> usize {
+    std::mem::size_of::<Node<Id>>()
+        + 2 * std::mem::size_of::<usize>()
+        + std::mem::size_of::<(Id, NodeId)>()
+        + std::mem::size_of::<NodeId>()
+}
+
+/// Rough resident bytes of one vector: its components, `degree` neighbour
+/// entries and the fixed per-node overhead.
+pub(crate) fn estimate_node_bytes<Id>(dims: usize, degree: f64) -> usize {
+    dims * std::mem::size_of::<f32>()
+        + (degree * std::mem::size_of::<NodeId>() as f64).ceil() as usize
+        + node_overhead::<Id>()
 }
@@ -4,20 +4,38 @@
 
 use crate::{
     graph::Graph,
-    math::{Cosine, Metric},
-    Hnsw, Result, VcalError,
+    math::{Cosine, CosineEps, Metric},
+    rand_level::Sampler,
+    ExternalId, Hnsw, IdType, Result, VcalError,
 };
+use std::sync::Arc;
 
 /// Reasonable defaults from the HNSW paper (Malkov, 2018).
 const DEF_M: usize = 16;
 const DEF_EF_CONSTRUCTION: usize = 200;
 const DEF_EF_SEARCH: usize = 128;
 
+/// Max degree for a given layer; see [`HnswBuilder::degree_budget`].
+pub type DegreeBudget = Arc<dyn Fn(usize) -> usize + Send + Sync>;
+
+/// Clone a configured builder to stamp out indexes that differ only in `dims`.
+#[derive(Clone)]
 pub struct HnswBuilder<M: Metric = Cosine> {
     dims: Option<usize>,
     m: usize,
+    m_max0: Option<usize>,
+    degree_budget: Option<DegreeBudget>,
     ef_construction: usize,
+    ef_construction_layer0: Option<usize>,
     ef_search: usize,
+    seed: Option<u64>,
+    level_sampler: Sampler,
+    recent_ties: bool,
+    normalize_query: bool,
+    flush_subnormals: bool,
+    empty_returns_ok: bool,
+    track_access: bool,
+    exact_below: usize,
     metric: M,
 }
 
@@ -27,8 +45,19 @@ impl<M: Metric> HnswBuilder<M> {
         Self {
             dims: None,
             m: DEF_M,
+            m_max0: None,
+            degree_budget: None,
             ef_construction: DEF_EF_CONSTRUCTION,
+            ef_construction_layer0: None,
             ef_search: DEF_EF_SEARCH,
+            seed: None,
+            level_sampler: Sampler::default(),
+            recent_ties: false,
+            normalize_query: false,
+            flush_subnormals: false,
+            empty_returns_ok: false,
+            track_access: true,
+            exact_below: 0,
             metric,
         }
     }
@@ -47,6 +76,29 @@ impl<M: Metric> HnswBuilder<M> {
         self
     }
 
+    /// Max degree on layer 0 (defaults to `2 * m`, as in the HNSW paper).
+    #[inline]
+    #[must_use]
+    pub fn m_max0(mut self, m0: usize) -> Self {
+        self.m_max0 = Some(m0.max(2));
+        self
+    }
+
+    /// Advanced: max degree per layer, replacing the flat `m` / `m_max0`
+    /// budgets (default `|l| if l == 0 { 2 * m } else { m }`). Results are
+    /// floored at 2. `m` still drives level sampling and the minimum
+    /// construction beam. Extreme budgets can blow up memory (large) or
+    /// wreck recall (small); measure before adopting one. Not stored in
+    /// snapshots; a restored index uses `m` / `m_max0`.
+    #[must_use]
+    pub fn degree_budget(mut self, budget: DegreeBudget) -> Self {
+        self.degree_budget = Some(budget);
+        self
+    }
+
+    /// Construction beam (default 200). Inserts widen a beam below `m` to
+    /// `m` so every layer can fill its degree budget, so any value under
+    /// `m` builds exactly what `m` would.
     #[inline]
     #[must_use]
     pub fn ef_construction(mut self, ef: usize) -> Self {
@@ -54,6 +106,17 @@ impl<M: Metric> HnswBuilder<M> {
         self
     }
 
+    /// Construction beam on layer 0 only; upper layers keep `ef_construction`.
+    /// Upper layers are sparse, so a small `ef_construction` with a larger
+    /// layer-0 value cuts build time at equal recall. Defaults to
+    /// `ef_construction`; like it, widened to `m` when smaller.
+    #[inline]
+    #[must_use]
+    pub fn ef_construction_layer0(mut self, ef: usize) -> Self {
+        self.ef_construction_layer0 = Some(ef.max(1));
+        self
+    }
+
     #[inline]
     #[must_use]
     pub fn ef_search(mut self, ef: usize) -> Self {
@@ -61,31 +124,213 @@ impl<M: Metric> HnswBuilder<M> {
         self
     }
 
+    /// Seed the level sampler so identical insert sequences build identical graphs.
+    #[inline]
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Level sampler implementation (default [`Sampler::CoinFlip`]). Not
+    /// stored in snapshots; a restored index samples with the default.
+    #[inline]
+    #[must_use]
+    pub fn level_sampler(mut self, sampler: Sampler) -> Self {
+        self.level_sampler = sampler;
+        self
+    }
+
+    /// Break exact distance ties in favour of the most recently inserted node.
+    #[inline]
+    #[must_use]
+    pub fn recent_ties(mut self, on: bool) -> Self {
+        self.recent_ties = on;
+        self
+    }
+
+    /// L2-normalise every query before searching. Guards `Dot` over
+    /// pre-normalised vectors (cosine via dot) against un-normalised queries.
+    /// Costs one allocation + copy of the query per search.
+    #[inline]
+    #[must_use]
+    pub fn normalize_query(mut self, on: bool) -> Self {
+        self.normalize_query = on;
+        self
+    }
+
+    /// Flush subnormal components (|x| < `f32::MIN_POSITIVE`) to zero in
+    /// queries and in inserted vectors before any distance is computed.
+    /// Arithmetic on subnormals is microcoded on many CPUs and can slow the
+    /// distance loops by an order of magnitude on badly scaled input. The
+    /// accuracy cost is below 1.2e-38 per component, i.e. nothing for
+    /// embeddings. Costs a scan per vector and a copy only when one is
+    /// found.
+    #[inline]
+    #[must_use]
+    pub fn flush_subnormals(mut self, on: bool) -> Self {
+        self.flush_subnormals = on;
+        self
+    }
+
+    /// Make `search` on an empty index return `Ok(vec![])` instead of
+    /// `VcalError::EmptyIndex` (default: `false`).
+    #[inline]
+    #[must_use]
+    pub fn empty_returns_ok(mut self, on: bool) -> Self {
+        self.empty_returns_ok = on;
+        self
+    }
+
+    /// Record `last_hit` on search hits (default: `true`). Turn off for
+    /// read-only replicas to make search free of atomic stores; TTL and LRU
+    /// eviction then only see insert times.
+    #[inline]
+    #[must_use]
+    pub fn track_access(mut self, on: bool) -> Self {
+        self.track_access = on;
+        self
+    }
+
+    /// Answer `search`, `search_with_ef` and `search_into` by brute force
+    /// (`search_exact`) while the index holds fewer than `threshold` live
+    /// vectors (default 0: always use the graph). Only the query path
+    /// changes; inserts still build the graph, so crossing the threshold
+    /// needs no rebuild.
+    #[inline]
+    #[must_use]
+    pub fn exact_below(mut self, threshold: usize) -> Self {
+        self.exact_below = threshold;
+        self
+    }
+
     #[inline]
     #[must_use]
     pub fn metric<T: Metric>(self, metric: T) -> HnswBuilder<T> {
         HnswBuilder {
             dims: self.dims,
             m: self.m,
+            m_max0: self.m_max0,
+            degree_budget: self.degree_budget,
             ef_construction: self.ef_construction,
+            ef_construction_layer0: self.ef_construction_layer0,
             ef_search: self.ef_search,
+            seed: self.seed,
+            level_sampler: self.level_sampler,
+            recent_ties: self.recent_ties,
+            normalize_query: self.normalize_query,
+            flush_subnormals: self.flush_subnormals,
+            empty_returns_ok: self.empty_returns_ok,
+            track_access: self.track_access,
+            exact_below: self.exact_below,
             metric,
         }
     }
 
     pub fn build(self) -> Result<Hnsw<M>> {
+        self.build_with_ids()
+    }
+
+    /// `build`, then insert every `(id, vector)` pair, reserving capacity from
+    /// the iterator's lower size hint. Stops at the first failed insert and
+    /// returns its error; a wrong-length vector is reported as
+    /// `DimensionMismatchAt` with its position in `items`.
+    pub fn build_from_iter<I>(self, items: I) -> Result<Hnsw<M>>
+    where
+        I: IntoIterator<Item = (ExternalId, Vec<f32>)>,
+    {
+        self.build_from_with_progress(items, 0, |_, _| {})
+    }
+
+    /// `build_from_iter` that calls `progress(done, total)` after every
+    /// `every` inserts and once more at the end if the last batch was short,
+    /// so the final call always reports completion. `total` is the
+    /// iterator's size hint (exact for `Vec`, ranges and the like).
+    /// `every == 0` disables the callback.
+    pub fn build_from_with_progress<I, F>(
+        self,
+        items: I,
+        every: usize,
+        mut progress: F,
+    ) -> Result<Hnsw<M>>
+    where
+        I: IntoIterator<Item = (ExternalId, Vec<f32>)>,
+        F: FnMut(usize, usize),
+    {
+        let items = items.into_iter();
+        let (lo, hi) = items.size_hint();
+        let total = hi.unwrap_or(lo);
+        let mut h = self.build()?;
+        h.reserve(lo);
+        let mut done = 0usize;
+        for (id, vec) in items {
+            h.insert(vec, id).map_err(|e| e.at(done))?;
+            done += 1;
+            if every > 0 && done % every == 0 {
+                progress(done, total.max(done));
+            }
+        }
+        if every > 0 && done % every != 0 {
+            progress(done, total.max(done));
+        }
+        Ok(h)
+    }
+
+    /// Like `build`, but keyed by a custom id type (e.g. `u128` UUIDs):
+    /// `builder.build_with_ids::<u128>()`.
+    pub fn build_with_ids<Id: IdType>(self) -> Result<Hnsw<M, Id>> {
         let dims = self.dims.ok_or(VcalError::InvalidDimensions { found: 0 })?;
         if dims == 0 {
             return Err(VcalError::InvalidDimensions { found: 0 });
         }
 
+        let mut graph = match self.seed {
+            Some(seed) => Graph::with_seed(seed),
+            None => Graph::new(),
+        };
+        graph.sampler = self.level_sampler;
+        graph.degree_budget = self.degree_budget;
+
         Ok(Hnsw {
             dims,
             m: self.m,
+            m_max0: self.m_max0.unwrap_or(self.m * 2),
             ef: self.ef_search,
             efc: self.ef_construction,
+            efc0: self.ef_construction_layer0,
+            recent_ties: self.recent_ties,
+            normalize_query: self.normalize_query,
+            flush_subnormals: self.flush_subnormals,
+            empty_returns_ok: self.empty_returns_ok,
+            track_access: self.track_access,
+            exact_below: self.exact_below,
             metric: self.metric,
-            graph: Graph::new(),
+            graph,
+            #[cfg(feature = "ivf")]
+            ivf: None,
+        })
+    }
+}
+
+impl HnswBuilder<Cosine> {
+    /// Approximate RAM for `expected_n` vectors of `dims` components at
+    /// degree budget `m`, before any index exists (e.g. to size a pod).
+    /// Assumes full layer-0 lists (`2 * m`) plus the expected `m / (m - 1)`
+    /// upper-layer links, so it errs high; metric-independent. See
+    /// [`Hnsw::estimated_bytes_per_vector`] for an estimate from a live index.
+    pub fn estimate_bytes(dims: usize, m: usize, expected_n: usize) -> usize {
+        let m = m.max(2) as f64;
+        let degree = 2.0 * m + m / (m - 1.0);
+        crate::node::estimate_node_bytes::<ExternalId>(dims, degree).saturating_mul(expected_n)
+    }
+
+    /// Switch to [`CosineEps`] with the given denominator floor (clamped to
+    /// `>= 0`). See [`Cosine`] for how zero, tiny and normal norms behave.
+    #[inline]
+    #[must_use]
+    pub fn cosine_epsilon(self, epsilon: f32) -> HnswBuilder<CosineEps> {
+        self.metric(CosineEps {
+            epsilon: epsilon.max(0.0),
         })
     }
 }
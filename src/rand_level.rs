@@ -10,6 +10,11 @@
 //! start at level 0 and keep promoting while rand() < 1/M.
 //! This is branch-cheap and MSRV 1.56-friendly.
 //!
+//! `M` is an integer, so there is no `ln(M)` to blow up; the hazard is the
+//! promotion probability instead. `M = 1` gives `p = 1` and `M = 0` gives
+//! `p = inf`, both of which would promote forever. `M < 2` therefore always
+//! yields level 0 (a flat, single-layer graph).
+//!
 //! ```rust
 //! let lvl = vcal_core::draw_level(16);
 //! assert!(lvl < 64);
@@ -17,13 +22,61 @@
 
 use rand::Rng;
 
+/// How node levels are drawn; see [`HnswBuilder::level_sampler`](crate::HnswBuilder::level_sampler).
+///
+/// Both follow `P(level ≥ l) = M^-l`, so their level distributions are the
+/// same; they differ in how they consume the RNG (`CoinFlip` takes one draw
+/// per promotion plus one, `InverseLn` exactly one), so a seeded index builds
+/// a different graph under each. Pick the one matching the implementation
+/// you are cross-validating against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampler {
+    /// Promote while `U < 1/M` (the default).
+    CoinFlip,
+    /// The paper's `floor(-ln(U) * mL)` with `mL = 1 / ln(M)`.
+    InverseLn,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler::CoinFlip
+    }
+}
+
+impl Sampler {
+    /// Draw a level with this sampler; `m < 2` returns 0 for both.
+    pub(crate) fn draw<R: Rng + ?Sized>(self, m: usize, rng: &mut R) -> usize {
+        match self {
+            Sampler::CoinFlip => draw_level_with(m, rng),
+            Sampler::InverseLn => {
+                if m < 2 {
+                    return 0;
+                }
+                let ml = 1.0 / (m as f64).ln();
+                // `1 - U` lies in (0, 1], keeping `ln` finite.
+                let u = 1.0 - rng.random::<f64>();
+                (-u.ln() * ml).floor() as usize
+            }
+        }
+    }
+}
+
+/// Random level for a node in a graph of degree `m`; `m < 2` returns 0.
 #[inline]
 pub fn draw_level(m: usize) -> usize {
-    debug_assert!(m >= 2, "M must be ≥ 2");
+    draw_level_with(m, &mut rand::rng())
+}
+
+/// Same as [`draw_level`], but drawing from a caller-supplied RNG
+/// (used by seeded indexes for reproducible graphs).
+#[inline]
+pub(crate) fn draw_level_with<R: Rng + ?Sized>(m: usize, rng: &mut R) -> usize {
+    if m < 2 {
+        return 0;
+    }
 
     let p = 1.0 / m as f64;
     let mut lvl = 0usize;
-    let mut rng = rand::rng();
     while rng.random::<f64>() < p {
         lvl += 1;
     }
@@ -43,6 +96,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn degenerate_m_is_flat_and_terminates() {
+        for _ in 0..1_000 {
+            assert_eq!(draw_level(0), 0);
+            assert_eq!(draw_level(1), 0);
+            assert!(draw_level(2) < 64);
+            assert_eq!(draw_level(usize::MAX), 0);
+        }
+    }
+
+    #[test]
+    fn samplers_agree_on_mean_level() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let n = 200_000;
+        for &m in &[2usize, 4, 16] {
+            let mean = |s: Sampler, seed| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                (0..n).map(|_| s.draw(m, &mut rng)).sum::<usize>() as f64 / n as f64
+            };
+            let expected = 1.0 / (m as f64 - 1.0);
+            let coin = mean(Sampler::CoinFlip, 1);
+            let inv = mean(Sampler::InverseLn, 2);
+            assert!(
+                (coin - expected).abs() < 0.02 * expected.max(0.5),
+                "m={} coin={}",
+                m,
+                coin
+            );
+            assert!(
+                (inv - expected).abs() < 0.02 * expected.max(0.5),
+                "m={} inv={}",
+                m,
+                inv
+            );
+        }
+        assert_eq!(Sampler::InverseLn.draw(1, &mut rand::rng()), 0);
+    }
+
     #[test]
     fn average_level_drops_as_m_increases() {
         let n = 20_000;
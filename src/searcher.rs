@@ -0,0 +1,160 @@
+//! searcher.rs — reusable per-thread query scratch.
+//!
+//! A plain `search` allocates its working sets (distance memo, visited set,
+//! both beam heaps, the beam and the result) on every call. A [`Searcher`]
+//! owns them instead and clears them between queries, so once its buffers
+//! have grown to the workload `Searcher::search_into` allocates nothing.
+//! Searchers only borrow the index immutably: give each thread its own and
+//! they search one shared index concurrently without locks.
+
+use crate::{graph::Scratch, math::Metric, node::now_millis, Hnsw, IdType, Result, SearchHit};
+
+/// Query scratch for [`Hnsw`] searches; see [`Hnsw::searcher`]. Not tied
+/// to one index, but sized by the last one it searched.
+#[derive(Default)]
+pub struct Searcher {
+    scratch: Scratch,
+}
+
+impl<M: Metric, Id: IdType> Hnsw<M, Id> {
+    /// A fresh [`Searcher`] for allocation-free repeated queries.
+    pub fn searcher(&self) -> Searcher {
+        Searcher::default()
+    }
+}
+
+impl Searcher {
+    /// Same hits as `index.search(query, k)`, using this searcher's buffers
+    /// for everything but the returned `Vec`.
+    pub fn search<M: Metric, Id: IdType>(
+        &mut self,
+        index: &Hnsw<M, Id>,
+        query: impl AsRef<[f32]>,
+        k: usize,
+    ) -> Result<Vec<SearchHit<Id>>> {
+        let mut out = Vec::with_capacity(k);
+        self.search_into(index, query, k, &mut out)?;
+        Ok(out)
+    }
+
+    /// `search` into a caller-owned buffer (cleared first). With a reused
+    /// `out`, a query allocates nothing once the buffers are warm, except
+    /// under `normalize_query` or for a query `flush_subnormals` rewrites
+    /// (one query copy each) and below `exact_below` (which falls back to
+    /// `search_exact`). On error `out` is left empty.
+    pub fn search_into<M: Metric, Id: IdType>(
+        &mut self,
+        index: &Hnsw<M, Id>,
+        query: impl AsRef<[f32]>,
+        k: usize,
+        out: &mut Vec<SearchHit<Id>>,
+    ) -> Result<()> {
+        out.clear();
+        if index.graph.active < index.exact_below {
+            out.extend(index.search_exact(query, k)?);
+            return Ok(());
+        }
+        let query = match index.prepare_query(query.as_ref())? {
+            Some(q) => q,
+            None => return Ok(()),
+        };
+        index.graph.knn_scratch(
+            &query,
+            k,
+            &index.metric,
+            index.ef.max(k.max(1)),
+            index.recent_ties,
+            &mut self.scratch,
+            out,
+        );
+        if index.track_access {
+            index.graph.touch_hits(out, now_millis());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::rand_vecs;
+    use crate::{Cosine, HnswBuilder};
+    use std::sync::Arc;
+
+    #[test]
+    fn searcher_matches_search_across_queries() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(16)
+            .m(8)
+            .seed(113)
+            .recent_ties(true)
+            .build()
+            .unwrap();
+        for (i, v) in rand_vecs(500, 16, 127).into_iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        for id in (0..500).step_by(9) {
+            h.delete(id);
+        }
+
+        let mut s = h.searcher();
+        let mut out = Vec::new();
+        for (i, q) in rand_vecs(50, 16, 131).iter().enumerate() {
+            let k = 1 + i % 20;
+            s.search_into(&h, q, k, &mut out).unwrap();
+            assert_eq!(out, h.search(q, k).unwrap());
+        }
+        assert_eq!(s.search(&h, [0.0; 16], 0).unwrap(), Vec::new());
+        assert!(s.search_into(&h, [0.0; 3], 1, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn searchers_share_an_index_across_threads() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .seed(137)
+            .build()
+            .unwrap();
+        let data = rand_vecs(200, 8, 139);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        let shared = Arc::new((h, data));
+        let workers: Vec<_> = (0..4)
+            .map(|t| {
+                let shared = Arc::clone(&shared);
+                std::thread::spawn(move || {
+                    let (h, data) = &*shared;
+                    let mut s = h.searcher();
+                    for i in (t..200).step_by(4) {
+                        assert_eq!(s.search(h, &data[i], 1).unwrap()[0].0, i as u64);
+                    }
+                })
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn search_into_touches_exactly_the_hits() {
+        use std::sync::atomic::Ordering;
+
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let data = rand_vecs(100, 8, 149);
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        for n in &h.graph.nodes {
+            n.last_hit.store(0, Ordering::Relaxed);
+        }
+
+        let mut out = Vec::new();
+        h.searcher().search_into(&h, &data[3], 5, &mut out).unwrap();
+        for (nid, n) in h.graph.nodes.iter().enumerate() {
+            let hit = out.iter().any(|&(id, _)| h.graph.by_ext[&id] == nid);
+            assert_eq!(n.last_hit.load(Ordering::Relaxed) > 0, hit);
+        }
+    }
+}
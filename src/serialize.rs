@@ -1,92 +1,412 @@
 use crate::{
     errors::{Result, VcalError},
-    graph::Graph,
+    graph::{AuditReport, Graph},
     math::Metric,
-    node::{Node, NodeId},
-    Hnsw,
+    node::{Adj, Links, Node, NodeId},
+    Hnsw, IdType,
 };
+use std::borrow::Cow;
 use std::sync::atomic::Ordering;
 
-const SNAPSHOT_VERSION: u32 = 1;
+/// v2: `last_hit` in Unix milliseconds (v1 stored seconds).
+const SNAPSHOT_VERSION: u32 = 2;
 
+/// zstd frame magic number; lets loaders tell compressed snapshots from JSON.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Snapshots without a `version` field predate versioning, i.e. are v1.
 fn default_snapshot_version() -> u32 {
-    SNAPSHOT_VERSION
+    1
+}
+
+/// `track_access` is on unless a snapshot says otherwise.
+fn default_true() -> bool {
+    true
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-struct SerNode {
-    ext_id: u64,
+#[derive(serde::Deserialize)]
+struct SerNode<Id> {
+    ext_id: Id,
+    /// Tombstone entry (written by `to_bytes_with_tombstones`): no vector.
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
     vec: Vec<f32>,
-    links: Vec<Vec<NodeId>>,
+    #[serde(default)]
+    links: Vec<Adj>,
     #[serde(default)]
     last_hit: Option<u64>,
+    #[serde(default)]
+    seq: Option<u64>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-struct SerGraph {
-    nodes: Vec<SerNode>,
+#[derive(serde::Deserialize)]
+struct SerGraph<Id> {
+    nodes: Vec<SerNode<Id>>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-struct SerIndex {
+#[derive(serde::Deserialize)]
+struct SerIndex<Id> {
     #[serde(default = "default_snapshot_version")]
     version: u32,
     dims: usize,
     m: usize,
+    #[serde(default)]
+    m_max0: Option<usize>,
     ef: usize,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     efc: Option<usize>,
-    graph: SerGraph,
+    #[serde(default)]
+    efc0: Option<usize>,
+    // Query-path flags; absent in older snapshots, which get the builder
+    // defaults.
+    #[serde(default)]
+    recent_ties: bool,
+    #[serde(default)]
+    normalize_query: bool,
+    #[serde(default)]
+    flush_subnormals: bool,
+    #[serde(default)]
+    empty_returns_ok: bool,
+    #[serde(default = "default_true")]
+    track_access: bool,
+    #[serde(default)]
+    exact_below: usize,
+    graph: SerGraph<Id>,
 }
 
-pub fn to_bytes<M: Metric>(idx: &Hnsw<M>) -> Result<Vec<u8>> {
-    let nodes: Vec<SerNode> = idx
-        .graph
-        .nodes
-        .iter()
-        .filter(|n| !n.is_deleted())
-        .map(|n| SerNode {
-            ext_id: n.ext_id,
-            vec: n.vec.clone(),
-            links: n.links.clone(),
-            last_hit: Some(n.last_hit.load(Ordering::Relaxed)),
-        })
-        .collect();
-
-    let ser = SerIndex {
+// Write side borrows straight from the index so nothing is cloned and the
+// node list is streamed one element at a time.
+
+#[derive(serde::Serialize)]
+struct SerNodeRef<'a, Id> {
+    ext_id: Id,
+    #[serde(skip_serializing_if = "is_false")]
+    deleted: bool,
+    #[serde(skip_serializing_if = "<[f32]>::is_empty")]
+    vec: &'a [f32],
+    #[serde(skip_serializing_if = "<[Adj]>::is_empty")]
+    links: Cow<'a, [Adj]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_hit: Option<u64>,
+    seq: Option<u64>,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+struct SnapNodes<'a, Id: IdType> {
+    nodes: &'a [Node<Id>],
+    /// Keep tombstones in place (NodeIds unchanged) instead of dropping them.
+    tombstones: bool,
+}
+
+impl<Id: IdType + serde::Serialize> serde::Serialize for SnapNodes<'_, Id> {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> core::result::Result<S::Ok, S::Error> {
+        // Dropping tombstones shifts NodeIds on reload, so links must be
+        // renumbered. Skip the table when there is nothing to drop.
+        let remap: Option<Vec<NodeId>> =
+            if self.tombstones || !self.nodes.iter().any(|n| n.is_deleted()) {
+                None
+            } else {
+                let mut next = 0;
+                Some(
+                    self.nodes
+                        .iter()
+                        .map(|n| {
+                            if n.is_deleted() {
+                                NodeId::MAX
+                            } else {
+                                next += 1;
+                                next - 1
+                            }
+                        })
+                        .collect(),
+                )
+            };
+
+        let tombstones = self.tombstones;
+        s.collect_seq(
+            self.nodes
+                .iter()
+                .filter(|n| tombstones || !n.is_deleted())
+                .map(|n| {
+                    if n.is_deleted() {
+                        return SerNodeRef {
+                            ext_id: n.ext_id,
+                            deleted: true,
+                            vec: &[],
+                            links: Cow::Borrowed(&[]),
+                            last_hit: None,
+                            seq: Some(n.seq),
+                        };
+                    }
+                    let links = match &remap {
+                        None => Cow::Borrowed(&n.links[..]),
+                        Some(map) => Cow::Owned(
+                            n.links
+                                .iter()
+                                .map(|adj| {
+                                    adj.iter()
+                                        .filter_map(|&x| map.get(x).copied())
+                                        .filter(|&x| x != NodeId::MAX)
+                                        .collect()
+                                })
+                                .collect(),
+                        ),
+                    };
+                    SerNodeRef {
+                        ext_id: n.ext_id,
+                        deleted: false,
+                        vec: &n.vec,
+                        links,
+                        last_hit: Some(n.last_hit.load(Ordering::Relaxed)),
+                        seq: Some(n.seq),
+                    }
+                }),
+        )
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SerGraphRef<'a, Id: IdType> {
+    nodes: SnapNodes<'a, Id>,
+}
+
+#[derive(serde::Serialize)]
+struct SerIndexRef<'a, Id: IdType> {
+    version: u32,
+    dims: usize,
+    m: usize,
+    m_max0: Option<usize>,
+    ef: usize,
+    efc: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    efc0: Option<usize>,
+    recent_ties: bool,
+    normalize_query: bool,
+    flush_subnormals: bool,
+    empty_returns_ok: bool,
+    track_access: bool,
+    exact_below: usize,
+    graph: SerGraphRef<'a, Id>,
+}
+
+fn json_err(e: serde_json::Error) -> VcalError {
+    if e.is_io() {
+        VcalError::Io(e.to_string())
+    } else {
+        VcalError::Serialize(e.to_string())
+    }
+}
+
+pub fn to_bytes<M: Metric, Id: IdType + serde::Serialize>(idx: &Hnsw<M, Id>) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_snapshot(idx, &mut out)?;
+    Ok(out)
+}
+
+/// Like `to_bytes`, but deleted nodes are kept as `{ext_id, deleted}` entries
+/// so a replica loading the snapshot learns about removals.
+pub fn to_bytes_with_tombstones<M: Metric, Id: IdType + serde::Serialize>(
+    idx: &Hnsw<M, Id>,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_snapshot_inner(idx, &mut out, true)?;
+    Ok(out)
+}
+
+/// Stream a snapshot into `w` node by node (same format as `to_bytes`).
+/// Extra memory is bounded by one node's worth plus the writer's buffer.
+pub fn write_snapshot<M: Metric, Id: IdType + serde::Serialize, W: std::io::Write>(
+    idx: &Hnsw<M, Id>,
+    w: &mut W,
+) -> Result<()> {
+    write_snapshot_inner(idx, w, false)
+}
+
+fn write_snapshot_inner<M: Metric, Id: IdType + serde::Serialize, W: std::io::Write>(
+    idx: &Hnsw<M, Id>,
+    w: &mut W,
+    tombstones: bool,
+) -> Result<()> {
+    let ser = SerIndexRef {
         version: SNAPSHOT_VERSION,
         dims: idx.dims,
         m: idx.m,
+        m_max0: Some(idx.m_max0),
         ef: idx.ef,
         efc: Some(idx.efc),
-        graph: SerGraph { nodes },
+        efc0: idx.efc0,
+        recent_ties: idx.recent_ties,
+        normalize_query: idx.normalize_query,
+        flush_subnormals: idx.flush_subnormals,
+        empty_returns_ok: idx.empty_returns_ok,
+        track_access: idx.track_access,
+        exact_below: idx.exact_below,
+        graph: SerGraphRef {
+            nodes: SnapNodes {
+                nodes: &idx.graph.nodes,
+                tombstones,
+            },
+        },
     };
-
-    serde_json::to_vec(&ser).map_err(|e| VcalError::Serialize(e.to_string()))
+    serde_json::to_writer(w, &ser).map_err(json_err)
 }
 
 pub fn from_slice<M: Metric + Default>(bytes: &[u8]) -> Result<Hnsw<M>> {
-    let snap: SerIndex =
-        serde_json::from_slice(bytes).map_err(|e| VcalError::Serialize(e.to_string()))?;
+    from_slice_ids(bytes)
+}
+
+/// `from_slice` for an index keyed by a custom id type.
+pub(crate) fn from_slice_ids<M, Id>(bytes: &[u8]) -> Result<Hnsw<M, Id>>
+where
+    M: Metric + Default,
+    Id: IdType + serde::de::DeserializeOwned,
+{
+    if bytes.starts_with(&ZSTD_MAGIC) {
+        return Err(VcalError::Serialize(
+            "zstd-compressed snapshot; load it with from_slice_compressed".into(),
+        ));
+    }
+    let snap: SerIndex<Id> = serde_json::from_slice(bytes).map_err(json_read_err)?;
+    from_snapshot(snap, false)
+}
 
-    if snap.version != SNAPSHOT_VERSION {
+/// `from_slice` without the repair pass: instead of quietly dropping bad
+/// edges, fails with `VcalError::Serialize` naming the first violated
+/// invariant (dangling, self or duplicate edge, edge to a node absent on
+/// that layer, node without layers, duplicate live ext_id). A snapshot that
+/// loads is kept exactly as written, neighbour order included.
+pub fn from_slice_strict<M: Metric + Default>(bytes: &[u8]) -> Result<Hnsw<M>> {
+    from_slice_strict_ids(bytes)
+}
+
+/// `from_slice_strict` for an index keyed by a custom id type.
+pub(crate) fn from_slice_strict_ids<M, Id>(bytes: &[u8]) -> Result<Hnsw<M, Id>>
+where
+    M: Metric + Default,
+    Id: IdType + serde::de::DeserializeOwned,
+{
+    if bytes.starts_with(&ZSTD_MAGIC) {
+        return Err(VcalError::Serialize(
+            "zstd-compressed snapshot; load it with from_slice_compressed".into(),
+        ));
+    }
+    let snap: SerIndex<Id> = serde_json::from_slice(bytes).map_err(json_read_err)?;
+    from_snapshot(snap, true)
+}
+
+/// Load a snapshot incrementally from `r` (wrap files/sockets in a
+/// `BufReader`). Truncated input is reported as `CorruptSnapshot`, malformed
+/// input as `Serialize`, and reader failures as `Io`.
+pub fn read_snapshot<M: Metric + Default, R: std::io::Read>(r: R) -> Result<Hnsw<M>> {
+    read_snapshot_ids(r)
+}
+
+/// `read_snapshot` for an index keyed by a custom id type.
+pub(crate) fn read_snapshot_ids<M, Id, R>(r: R) -> Result<Hnsw<M, Id>>
+where
+    M: Metric + Default,
+    Id: IdType + serde::de::DeserializeOwned,
+    R: std::io::Read,
+{
+    let snap: SerIndex<Id> = serde_json::from_reader(r).map_err(json_read_err)?;
+    from_snapshot(snap, false)
+}
+
+fn json_read_err(e: serde_json::Error) -> VcalError {
+    match e.classify() {
+        serde_json::error::Category::Eof => {
+            VcalError::CorruptSnapshot(format!("truncated snapshot: {}", e))
+        }
+        _ => json_err(e),
+    }
+}
+
+/// Invariants `sanitize` would otherwise repair; the first violation wins.
+fn check_snapshot<Id: IdType>(snap: &SerIndex<Id>) -> Result<()> {
+    let nodes = &snap.graph.nodes;
+    let bad = |msg: String| Err(VcalError::Serialize(format!("strict load: {}", msg)));
+    let mut seen = std::collections::HashSet::with_capacity(nodes.len());
+    for (nid, sn) in nodes.iter().enumerate().filter(|(_, sn)| !sn.deleted) {
+        if !seen.insert(sn.ext_id) {
+            return bad(format!("duplicate ext_id {:?} at node {}", sn.ext_id, nid));
+        }
+        if sn.links.is_empty() {
+            return bad(format!("node {} has no layers", nid));
+        }
+        for (l, adj) in sn.links.iter().enumerate() {
+            for (i, &nb) in adj.iter().enumerate() {
+                let reason = if nb >= nodes.len() || nodes[nb].deleted {
+                    "dangling edge"
+                } else if nb == nid {
+                    "self edge"
+                } else if nodes[nb].links.len() <= l {
+                    "edge to a node absent on this layer"
+                } else if adj[..i].contains(&nb) {
+                    "duplicate edge"
+                } else {
+                    continue;
+                };
+                return bad(format!("node {} layer {}: {} to {}", nid, l, reason, nb));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_version<Id>(snap: &SerIndex<Id>) -> Result<()> {
+    if snap.version == 0 || snap.version > SNAPSHOT_VERSION {
         return Err(VcalError::CorruptSnapshot(format!(
             "unsupported snapshot version: {}",
             snap.version
         )));
     }
+    Ok(())
+}
 
+fn from_snapshot<M: Metric + Default, Id: IdType>(
+    snap: SerIndex<Id>,
+    strict: bool,
+) -> Result<Hnsw<M, Id>> {
+    check_version(&snap)?;
+    if strict {
+        check_snapshot(&snap)?;
+    }
+    let mut h = load_unrepaired(snap)?;
+    if !strict {
+        let _ = h.graph.sanitize();
+    }
+    Ok(h)
+}
+
+/// Build the index exactly as the snapshot describes it, damage included.
+fn load_unrepaired<M: Metric + Default, Id: IdType>(snap: SerIndex<Id>) -> Result<Hnsw<M, Id>> {
     let efc = snap.efc.unwrap_or_else(|| snap.ef.max(1));
     let ef = snap.ef.max(1);
+    let m_max0 = snap.m_max0.unwrap_or(snap.m * 2);
+
+    if snap.dims == 0 {
+        return Err(VcalError::InvalidDimensions { found: 0 });
+    }
 
     let mut g = Graph::new();
     let mut max_level = 0usize;
 
-    for sn in &snap.graph.nodes {
+    for (index, sn) in snap
+        .graph
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, sn)| !sn.deleted)
+    {
         if sn.vec.len() != snap.dims {
-            return Err(VcalError::DimensionMismatch {
+            return Err(VcalError::DimensionMismatchAt {
                 expected: snap.dims,
                 found: sn.vec.len(),
+                index,
             });
         }
         let level = sn.links.len().saturating_sub(1);
@@ -103,11 +423,29 @@ pub fn from_slice<M: Metric + Default>(bytes: &[u8]) -> Result<Hnsw<M>> {
         let level = sn.links.len().saturating_sub(1);
         let node_id = g.nodes.len() as NodeId;
 
-        let mut node = Node::new(sn.ext_id, level, sn.vec);
-        node.links = sn.links;
+        if sn.deleted {
+            let node = Node::new(sn.ext_id, 0, Vec::new().into());
+            node.deleted.store(true, Ordering::Relaxed);
+            let mut node = node;
+            node.seq = sn.seq.unwrap_or(node_id as u64);
+            g.next_seq = g.next_seq.max(node.seq + 1);
+            g.nodes.push(node);
+            continue;
+        }
+
+        let mut node = Node::new(sn.ext_id, level, sn.vec.into());
+        node.links = Links::from_vec(sn.links);
         if let Some(ts) = sn.last_hit {
+            let ts = if snap.version < 2 {
+                ts.saturating_mul(1000)
+            } else {
+                ts
+            };
             node.last_hit.store(ts, Ordering::Relaxed);
         }
+        // Older snapshots have no seq: fall back to file order.
+        node.seq = sn.seq.unwrap_or(node_id as u64);
+        g.next_seq = g.next_seq.max(node.seq + 1);
         node.recompute_bytes();
 
         g.total_bytes += node.bytes;
@@ -127,20 +465,111 @@ pub fn from_slice<M: Metric + Default>(bytes: &[u8]) -> Result<Hnsw<M>> {
         None
     };
 
-    let mut h = Hnsw {
+    Ok(Hnsw {
         dims: snap.dims,
         m: snap.m,
+        m_max0,
         ef,
         efc,
+        efc0: snap.efc0,
+        recent_ties: snap.recent_ties,
+        normalize_query: snap.normalize_query,
+        flush_subnormals: snap.flush_subnormals,
+        empty_returns_ok: snap.empty_returns_ok,
+        track_access: snap.track_access,
+        exact_below: snap.exact_below,
         metric: M::default(),
         graph: g,
-    };
+        #[cfg(feature = "ivf")]
+        ivf: None,
+    })
+}
 
-    let _ = h.graph.sanitize();
-    Ok(h)
+/// Parse a snapshot and [`Graph::audit`] it as written, without repairing.
+pub(crate) fn audit_snapshot_ids<Id>(bytes: &[u8]) -> Result<AuditReport>
+where
+    Id: IdType + serde::de::DeserializeOwned,
+{
+    if bytes.starts_with(&ZSTD_MAGIC) {
+        return Err(VcalError::Serialize(
+            "zstd-compressed snapshot; decompress it before auditing".into(),
+        ));
+    }
+    let snap: SerIndex<Id> = serde_json::from_slice(bytes).map_err(json_read_err)?;
+    check_version(&snap)?;
+    let h: Hnsw<crate::Cosine, Id> = load_unrepaired(snap)?;
+    Ok(h.graph.audit())
+}
+
+/// One JSONL import record: `{"id": .., "vec": [..]}`.
+#[derive(serde::Deserialize)]
+struct JsonlRecord<Id> {
+    id: Id,
+    vec: Vec<f32>,
+}
+
+/// Stream `{"id": .., "vec": [..]}` lines from `r` into `idx`, one insert
+/// per line (blank lines are skipped). Stops at the first bad line: invalid
+/// JSON is `Serialize` naming the 1-based line, a wrong-length vector is
+/// `DimensionMismatchAt` with the 0-based line as `index`, and reader
+/// failures are `Io`. Lines before the failure stay inserted.
+pub(crate) fn import_jsonl<M, Id, R>(idx: &mut Hnsw<M, Id>, r: R) -> Result<usize>
+where
+    M: Metric,
+    Id: IdType + serde::de::DeserializeOwned,
+    R: std::io::Read,
+{
+    use std::io::BufRead;
+
+    let mut n = 0usize;
+    for (i, line) in std::io::BufReader::new(r).lines().enumerate() {
+        let line = line.map_err(|e| VcalError::Io(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let rec: JsonlRecord<Id> = serde_json::from_str(&line)
+            .map_err(|e| VcalError::Serialize(format!("line {}: {}", i + 1, e)))?;
+        idx.insert(rec.vec, rec.id).map_err(|e| e.at(i))?;
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// Snapshot as zstd-compressed JSON at `level` (1..=22; 0 = zstd default).
+#[cfg(feature = "compress")]
+pub fn to_bytes_compressed<M: Metric, Id: IdType + serde::Serialize>(
+    idx: &Hnsw<M, Id>,
+    level: i32,
+) -> Result<Vec<u8>> {
+    let raw = to_bytes(idx)?;
+    zstd::stream::encode_all(&raw[..], level).map_err(|e| VcalError::Serialize(e.to_string()))
+}
+
+/// Load a snapshot written by either `to_bytes` or `to_bytes_compressed`;
+/// the format is detected from the zstd magic bytes.
+#[cfg(feature = "compress")]
+pub fn from_slice_compressed<M: Metric + Default>(bytes: &[u8]) -> Result<Hnsw<M>> {
+    from_slice_compressed_ids(bytes)
+}
+
+/// `from_slice_compressed` for an index keyed by a custom id type.
+#[cfg(feature = "compress")]
+pub(crate) fn from_slice_compressed_ids<M, Id>(bytes: &[u8]) -> Result<Hnsw<M, Id>>
+where
+    M: Metric + Default,
+    Id: IdType + serde::de::DeserializeOwned,
+{
+    if !bytes.starts_with(&ZSTD_MAGIC) {
+        return from_slice_ids(bytes);
+    }
+    let raw = zstd::stream::decode_all(bytes).map_err(|e| VcalError::Serialize(e.to_string()))?;
+    from_slice_ids(&raw)
 }
 
 #[cfg(test)]
+// Query call sites keep the `&[..]` / `&Vec` form that predates
+// `impl AsRef<[f32]>`; they double as source-compatibility checks.
+#[allow(clippy::needless_borrows_for_generic_args)]
 mod tests {
     use super::*;
     use crate::{Cosine, HnswBuilder};
@@ -156,6 +585,28 @@ mod tests {
         assert_eq!(h2.search(&[0.5; 8], 1).unwrap()[0].0, 7);
     }
 
+    #[test]
+    fn snapshot_roundtrip_is_structurally_equal() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(8)
+            .m_max0(12)
+            .ef_construction(64)
+            .seed(5)
+            .build()
+            .unwrap();
+        for i in 0..120u64 {
+            let v: Vec<f32> = (0..8).map(|d| ((i * 8 + d) as f32 * 0.37).sin()).collect();
+            h.insert(v, i).unwrap();
+        }
+        for id in (0..120).step_by(7) {
+            h.delete(id);
+        }
+
+        let h2 = Hnsw::<Cosine>::from_slice(&h.to_bytes().unwrap()).unwrap();
+        assert!(h2 == h);
+        assert_eq!(h2.checksum(), h.checksum());
+    }
+
     #[test]
     fn snapshot_bad_json_returns_error() {
         let err = Hnsw::<Cosine>::from_slice(br#"{"not":"valid enough"}"#);
@@ -167,6 +618,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn v1_snapshot_last_hit_seconds_become_millis() {
+        let bytes = br#"{
+            "version": 1,
+            "dims": 2,
+            "m": 16,
+            "ef": 32,
+            "graph": {"nodes": [
+                {"ext_id": 9, "vec": [1.0, 0.0], "links": [[]], "last_hit": 1700000000}
+            ]}
+        }"#;
+
+        let h = Hnsw::<Cosine>::from_slice(bytes).unwrap();
+        let ts = h.graph.nodes[0].last_hit.load(Ordering::Relaxed);
+        assert_eq!(ts, 1_700_000_000_000);
+
+        let h2 = Hnsw::<Cosine>::from_slice(&h.to_bytes().unwrap()).unwrap();
+        assert_eq!(h2.graph.nodes[0].last_hit.load(Ordering::Relaxed), ts);
+    }
+
     #[test]
     fn snapshot_unknown_version_rejected() {
         let bytes = br#"{
@@ -185,4 +656,349 @@ mod tests {
             Ok(_) => panic!("expected error"),
         }
     }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compressed_roundtrip_at_several_levels() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(16).build().unwrap();
+        for i in 0..200u64 {
+            let v: Vec<f32> = (0..16).map(|d| ((i * 16 + d) as f32).sin()).collect();
+            h.insert(v, i).unwrap();
+        }
+        let raw = h.to_bytes().unwrap();
+        for &level in &[1, 3, 19] {
+            let packed = h.to_bytes_compressed(level).unwrap();
+            assert!(packed.len() < raw.len() / 2, "level {}", level);
+            let h2 = Hnsw::<Cosine>::from_slice_compressed(&packed).unwrap();
+            assert_eq!(h2.len(), 200);
+        }
+        // Plain JSON still loads through the compressed entry point.
+        assert_eq!(
+            Hnsw::<Cosine>::from_slice_compressed(&raw).unwrap().len(),
+            200
+        );
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compressed_truncated_or_misrouted_is_error() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        h.insert(vec![0.5; 8], 7).unwrap();
+        let packed = h.to_bytes_compressed(3).unwrap();
+
+        match Hnsw::<Cosine>::from_slice_compressed(&packed[..packed.len() / 2]) {
+            Err(VcalError::Serialize(_)) => {}
+            Err(other) => panic!("unexpected error: {}", other),
+            Ok(_) => panic!("expected error"),
+        }
+        match Hnsw::<Cosine>::from_slice(&packed) {
+            Err(VcalError::Serialize(_)) => {}
+            Err(other) => panic!("unexpected error: {}", other),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+
+    #[test]
+    fn write_snapshot_matches_to_bytes() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        for i in 0..20u64 {
+            h.insert(vec![i as f32 + 0.5; 8], i).unwrap();
+        }
+        let mut sink = Vec::new();
+        h.write_snapshot(&mut sink).unwrap();
+        assert_eq!(sink, h.to_bytes().unwrap());
+
+        let h2 = Hnsw::<Cosine>::from_slice(&sink).unwrap();
+        assert_eq!(h2.len(), 20);
+    }
+
+    #[test]
+    fn write_snapshot_maps_io_errors() {
+        struct Broken;
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        h.insert(vec![0.5; 8], 7).unwrap();
+        match h.write_snapshot(&mut Broken) {
+            Err(VcalError::Io(_)) => {}
+            Err(other) => panic!("unexpected error: {}", other),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+
+    #[test]
+    fn read_snapshot_streams_and_classifies_errors() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        let vecs: Vec<Vec<f32>> = (0..20u64)
+            .map(|i| (0..8).map(|d| ((i * 8 + d) as f32).sin()).collect())
+            .collect();
+        for (i, v) in vecs.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        let bytes = h.to_bytes().unwrap();
+
+        let h2: Hnsw<Cosine> = Hnsw::read_snapshot(std::io::Cursor::new(&bytes)).unwrap();
+        assert_eq!(h2.len(), 20);
+        assert_eq!(h2.search(&vecs[3], 1).unwrap()[0].0, 3);
+
+        match Hnsw::<Cosine>::read_snapshot(&bytes[..bytes.len() / 2]) {
+            Err(VcalError::CorruptSnapshot(msg)) => assert!(msg.contains("truncated")),
+            Err(other) => panic!("unexpected error: {}", other),
+            Ok(_) => panic!("expected error"),
+        }
+        match Hnsw::<Cosine>::read_snapshot(&br#"{"dims": "eight"}"#[..]) {
+            Err(VcalError::Serialize(_)) => {}
+            Err(other) => panic!("unexpected error: {}", other),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+
+    #[test]
+    fn tombstones_roundtrip_with_deletes() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(128);
+        let vecs: Vec<Vec<f32>> = (0..30)
+            .map(|_| (0..8).map(|_| rng.random::<f32>() - 0.5).collect())
+            .collect();
+        let mut h = HnswBuilder::<Cosine>::default().dims(8).build().unwrap();
+        for (i, v) in vecs.iter().enumerate() {
+            h.insert(v.clone(), i as u64).unwrap();
+        }
+        for id in [3u64, 7, 11, 19] {
+            h.delete(id);
+        }
+        h.insert(vecs[0].clone(), 7).unwrap(); // re-added: not a removal
+
+        let h2 = Hnsw::<Cosine>::from_slice(&h.to_bytes_with_tombstones().unwrap()).unwrap();
+        let mut gone = h2.tombstones();
+        gone.sort_unstable();
+        assert_eq!(gone, vec![3, 11, 19]);
+        assert_eq!(h2.len(), h.len());
+
+        // Default snapshots stay compact and still search correctly.
+        let h3 = Hnsw::<Cosine>::from_slice(&h.to_bytes().unwrap()).unwrap();
+        assert!(h3.tombstones().is_empty());
+        for (i, v) in vecs.iter().enumerate().skip(1) {
+            if [3, 7, 11, 19].contains(&i) {
+                continue;
+            }
+            assert_eq!(h2.search(v, 1).unwrap()[0].0, i as u64);
+            assert_eq!(h3.search(v, 1).unwrap()[0].0, i as u64);
+        }
+    }
+
+    #[test]
+    fn u128_ids_snapshot_roundtrip() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .build_with_ids::<u128>()
+            .unwrap();
+        let id = u128::MAX - 1;
+        h.insert(vec![1.0, 0.2, 0.0, 0.0], id).unwrap();
+        h.insert(vec![0.0, 1.0, 0.3, 0.0], 5).unwrap();
+
+        let h2 = Hnsw::<Cosine, u128>::from_slice(&h.to_bytes().unwrap()).unwrap();
+        assert_eq!(h2.search(&[1.0, 0.2, 0.0, 0.0], 1).unwrap()[0].0, id);
+    }
+
+    #[test]
+    fn strict_load_rejects_what_lenient_repairs() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .seed(172)
+            .build()
+            .unwrap();
+        for i in 0..40u64 {
+            let v: Vec<f32> = (0..4).map(|d| ((i * 4 + d) as f32 * 0.71).cos()).collect();
+            h.insert(v, i).unwrap();
+        }
+        let clean = h.to_bytes().unwrap();
+        let strict = Hnsw::<Cosine>::from_slice_strict(&clean).unwrap();
+        assert!(strict == h);
+
+        let mut json: serde_json::Value = serde_json::from_slice(&clean).unwrap();
+        json["graph"]["nodes"][3]["links"][0][0] = 999.into();
+        let dangling = serde_json::to_vec(&json).unwrap();
+        match Hnsw::<Cosine>::from_slice_strict(&dangling) {
+            Err(VcalError::Serialize(msg)) => {
+                assert!(
+                    msg.contains("node 3 layer 0: dangling edge to 999"),
+                    "{}",
+                    msg
+                )
+            }
+            other => panic!("expected Serialize error, got {:?}", other.map(|_| ())),
+        }
+        let lenient = Hnsw::<Cosine>::from_slice(&dangling).unwrap();
+        assert!(!lenient.graph.nodes[3].links[0].contains(&999));
+        assert_eq!(lenient.len(), 40);
+
+        let mut json: serde_json::Value = serde_json::from_slice(&clean).unwrap();
+        json["graph"]["nodes"][5]["ext_id"] = 4.into();
+        let dup = serde_json::to_vec(&json).unwrap();
+        assert!(matches!(
+            Hnsw::<Cosine>::from_slice_strict(&dup),
+            Err(VcalError::Serialize(msg)) if msg.contains("duplicate ext_id 4")
+        ));
+    }
+
+    #[test]
+    fn wrong_length_node_is_named_on_load() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(4).build().unwrap();
+        for i in 0..10u64 {
+            h.insert(vec![1.0, i as f32, 0.5, 0.25], i).unwrap();
+        }
+        h.delete(2);
+        let bytes = to_bytes_with_tombstones(&h).unwrap();
+        let mut json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        json["graph"]["nodes"][6]["vec"] = serde_json::json!([1.0, 2.0, 3.0]);
+        let bad = serde_json::to_vec(&json).unwrap();
+        for res in [
+            Hnsw::<Cosine>::from_slice(&bad).map(|_| ()),
+            Hnsw::<Cosine>::from_slice_strict(&bad).map(|_| ()),
+        ] {
+            assert!(matches!(
+                res,
+                Err(VcalError::DimensionMismatchAt {
+                    expected: 4,
+                    found: 3,
+                    index: 6
+                })
+            ));
+        }
+
+        json["dims"] = 0.into();
+        assert!(matches!(
+            Hnsw::<Cosine>::from_slice(&serde_json::to_vec(&json).unwrap()),
+            Err(VcalError::InvalidDimensions { found: 0 })
+        ));
+    }
+
+    #[test]
+    fn audit_counts_match_what_sanitize_repairs() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .seed(173)
+            .build()
+            .unwrap();
+        for i in 0..40u64 {
+            let v: Vec<f32> = (0..4).map(|d| ((i * 4 + d) as f32 * 0.53).sin()).collect();
+            h.insert(v, i).unwrap();
+        }
+        assert!(h.audit().is_clean());
+        let clean = h.to_bytes().unwrap();
+        assert!(Hnsw::<Cosine>::audit_snapshot(&clean).unwrap().is_clean());
+
+        let mut json: serde_json::Value = serde_json::from_slice(&clean).unwrap();
+        let nodes = &mut json["graph"]["nodes"];
+        let first = nodes[5]["links"][0][0].clone();
+        nodes[3]["links"][0]
+            .as_array_mut()
+            .unwrap()
+            .push(999.into());
+        nodes[4]["links"][0].as_array_mut().unwrap().push(4.into());
+        nodes[5]["links"][0].as_array_mut().unwrap().push(first);
+        nodes[6]["links"] = serde_json::json!([]);
+        nodes[7]["ext_id"] = 8.into();
+        let bytes = serde_json::to_vec(&json).unwrap();
+
+        let report = Hnsw::<Cosine>::audit_snapshot(&bytes).unwrap();
+        assert_eq!(
+            (
+                report.dangling_edges,
+                report.self_edges,
+                report.duplicate_edges,
+                report.layerless_nodes,
+                report.duplicate_ext_ids
+            ),
+            (1, 1, 1, 1, 1)
+        );
+        assert!(
+            report.cross_layer_edges > 0,
+            "edges into node 6 lost their layer"
+        );
+
+        let snap: SerIndex<u64> = serde_json::from_slice(&bytes).unwrap();
+        let mut raw: Hnsw<Cosine> = load_unrepaired(snap).unwrap();
+        assert_eq!(raw.audit(), report);
+        assert_eq!(
+            raw.graph.sanitize(),
+            (report.edges_dropped(), report.layerless_nodes)
+        );
+    }
+
+    #[test]
+    fn import_jsonl_streams_records_and_reports_lines() {
+        let mut h = HnswBuilder::<Cosine>::default().dims(3).build().unwrap();
+        let input = concat!(
+            "{\"id\": 1, \"vec\": [1.0, 0.0, 0.0]}\n",
+            "\n",
+            "{\"id\": 2, \"vec\": [0.0, 1.0, 0.0]}\n",
+            "{\"vec\": [0.0, 0.0, 1.0], \"id\": 3}",
+        );
+        assert_eq!(h.import_jsonl(input.as_bytes()).unwrap(), 3);
+        assert_eq!(h.search([0.0, 0.0, 1.0], 1).unwrap()[0].0, 3);
+
+        let bad = "{\"id\": 4, \"vec\": [1.0, 1.0, 0.0]}\n{\"id\": 5, \"vec\": [1.0,]}\n";
+        match h.import_jsonl(bad.as_bytes()) {
+            Err(VcalError::Serialize(msg)) => assert!(msg.starts_with("line 2:"), "{}", msg),
+            other => panic!("unexpected: {:?}", other.map(|_| ())),
+        }
+        assert!(h.contains(4) && !h.contains(5));
+
+        let short = "{\"id\": 6, \"vec\": [1.0]}\n";
+        assert!(matches!(
+            h.import_jsonl(short.as_bytes()),
+            Err(VcalError::DimensionMismatchAt {
+                index: 0,
+                found: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn query_flags_survive_snapshots() {
+        let mut h = HnswBuilder::<Cosine>::default()
+            .dims(4)
+            .recent_ties(true)
+            .normalize_query(true)
+            .flush_subnormals(true)
+            .empty_returns_ok(true)
+            .track_access(false)
+            .exact_below(50)
+            .build()
+            .unwrap();
+        h.insert(vec![1.0, 0.0, 0.0, 0.0], 1).unwrap();
+
+        let bytes = h.to_bytes().unwrap();
+        let h2 = Hnsw::<Cosine>::from_slice(&bytes).unwrap();
+        assert!(h2.recent_ties && h2.normalize_query && h2.flush_subnormals);
+        assert!(h2.empty_returns_ok && !h2.track_access);
+        assert_eq!(h2.exact_below, 50);
+
+        // Snapshots written before the flags were stored load with defaults.
+        let mut json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let obj = json.as_object_mut().unwrap();
+        for key in &[
+            "recent_ties",
+            "normalize_query",
+            "flush_subnormals",
+            "empty_returns_ok",
+            "track_access",
+            "exact_below",
+        ] {
+            assert!(obj.remove(*key).is_some(), "{}", key);
+        }
+        let old = Hnsw::<Cosine>::from_slice(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert!(!old.recent_ties && !old.normalize_query && !old.flush_subnormals);
+        assert!(!old.empty_returns_ok && old.track_access);
+        assert_eq!(old.exact_below, 0);
+    }
 }
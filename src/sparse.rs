@@ -0,0 +1,202 @@
+//! sparse.rs — HNSW over sparse vectors with [`SparseCosine`].
+//!
+//! Vectors are given as `(index, value)` pairs and stored in the existing
+//! `f32` graph in `SparseCosine`'s lane encoding (`|v|²`, then the pairs), so
+//! memory and distance time scale with non-zeros rather than with the
+//! logical dimensionality. Like `BinaryHnsw`, there is no snapshot support:
+//! index lanes hold raw bits that JSON cannot carry.
+
+use crate::{
+    math::SparseCosine, node::now_millis, ExternalId, Hnsw, HnswBuilder, IdType, Result, SearchHit,
+    VcalError,
+};
+
+/// Index over sparse vectors of logical dimensionality `dims`.
+pub struct SparseHnsw<Id: IdType = ExternalId> {
+    inner: Hnsw<SparseCosine, Id>,
+}
+
+impl SparseHnsw<ExternalId> {
+    /// Index for sparse vectors with indices in `0..dims`, default parameters.
+    pub fn new(dims: usize) -> Result<Self> {
+        Self::with_builder(dims, HnswBuilder::new(SparseCosine))
+    }
+}
+
+impl<Id: IdType> SparseHnsw<Id> {
+    /// Like `new`, using `builder`'s graph parameters (`m`, `ef_*`, `seed`,
+    /// ...); its `dims` is overridden.
+    pub fn with_builder(dims: usize, builder: HnswBuilder<SparseCosine>) -> Result<Self> {
+        if dims > u32::MAX as usize + 1 {
+            return Err(VcalError::InvalidDimensions { found: dims });
+        }
+        Ok(Self {
+            inner: builder.dims(dims).build_with_ids()?,
+        })
+    }
+
+    /// Logical dimensionality.
+    #[inline]
+    pub fn dims(&self) -> usize {
+        self.inner.dims
+    }
+
+    /// Validate and encode: indices must be unique and `< dims`; pairs may
+    /// come in any order.
+    fn encode(&self, pairs: &[(u32, f32)]) -> Result<Vec<f32>> {
+        let mut sorted = pairs.to_vec();
+        sorted.sort_unstable_by_key(|&(i, _)| i);
+        if let Some(&(i, _)) = sorted.last() {
+            if i as usize >= self.inner.dims {
+                return Err(VcalError::DimensionMismatch {
+                    expected: self.inner.dims,
+                    found: i as usize + 1,
+                });
+            }
+        }
+        if sorted.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(VcalError::InvalidParameter("duplicate sparse index"));
+        }
+        Ok(SparseCosine::encode(&sorted))
+    }
+
+    /// Insert (or upsert) the sparse vector `pairs` under `ext_id`.
+    pub fn insert(&mut self, pairs: &[(u32, f32)], ext_id: Id) -> Result<()> {
+        let lanes = self.encode(pairs)?;
+        let h = &mut self.inner;
+        let efc0 = h.efc0();
+        h.graph
+            .add(lanes.into(), ext_id, &h.metric, h.m, h.m_max0, h.efc, efc0);
+        Ok(())
+    }
+
+    /// Up to `k` nearest vectors by cosine distance, nearest first.
+    pub fn search(&self, pairs: &[(u32, f32)], k: usize) -> Result<Vec<SearchHit<Id>>> {
+        let query = self.encode(pairs)?;
+        let h = &self.inner;
+        if h.is_empty() {
+            return if h.empty_returns_ok {
+                Ok(Vec::new())
+            } else {
+                Err(VcalError::EmptyIndex)
+            };
+        }
+        let hits = h
+            .graph
+            .knn(&query, k, &h.metric, h.ef.max(k), h.recent_ties);
+        if h.track_access {
+            let ids: Vec<Id> = hits.iter().map(|h| h.0).collect();
+            h.graph.touch_many(&ids, now_millis());
+        }
+        Ok(hits)
+    }
+
+    /// Idempotent delete; true if something was removed.
+    pub fn delete(&mut self, ext_id: Id) -> bool {
+        self.inner.delete(ext_id)
+    }
+
+    #[inline]
+    pub fn contains(&self, ext_id: Id) -> bool {
+        self.inner.contains(ext_id)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// `(vector_count, approx_bytes)`; bytes count stored non-zeros only.
+    pub fn stats(&self) -> (usize, usize) {
+        self.inner.stats()
+    }
+
+    /// Set query-time ef.
+    #[inline]
+    pub fn set_ef(&mut self, ef: usize) {
+        self.inner.set_ef(ef);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cosine, Metric};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const DIMS: usize = 50_000;
+
+    fn random_sparse(rng: &mut StdRng, nnz: usize) -> Vec<(u32, f32)> {
+        // A shared low band guarantees overlap, the rest is spread out.
+        (0..nnz)
+            .map(|j| {
+                let i = if j < 4 {
+                    j as u32 * 7
+                } else {
+                    rng.random_range(0..DIMS as u32)
+                };
+                (i, rng.random::<f32>() - 0.5)
+            })
+            .collect()
+    }
+
+    fn dense(pairs: &[(u32, f32)]) -> Vec<f32> {
+        let mut d = vec![0.0; DIMS];
+        for &(i, v) in pairs {
+            d[i as usize] += v;
+        }
+        d
+    }
+
+    #[test]
+    fn sparse_search_agrees_with_dense_cosine() {
+        let mut rng = StdRng::seed_from_u64(157);
+        let mut data: Vec<Vec<(u32, f32)>> = Vec::new();
+        while data.len() < 300 {
+            let mut v = random_sparse(&mut rng, 20);
+            v.sort_unstable_by_key(|&(i, _)| i);
+            v.dedup_by_key(|p| p.0);
+            data.push(v);
+        }
+        let mut h =
+            SparseHnsw::with_builder(DIMS, HnswBuilder::new(SparseCosine).seed(157)).unwrap();
+        for (i, v) in data.iter().enumerate() {
+            h.insert(v, i as u64).unwrap();
+        }
+        // 300 vectors * ~20 non-zeros, far below 300 * 50_000 dense floats.
+        assert!(h.stats().1 < 300 * 50 * 4 + 300 * 64 * 8);
+
+        let q = &data[17];
+        let hits = h.search(q, 5).unwrap();
+        assert_eq!(hits[0].0, 17);
+        let dq = dense(q);
+        for &(id, d) in &hits {
+            let want = Cosine.distance(&dq, &dense(&data[id as usize]));
+            assert!(
+                (d - want).abs() < 1e-5,
+                "id {} sparse {} dense {}",
+                id,
+                d,
+                want
+            );
+        }
+
+        // Unsorted input is accepted; bad indices are not.
+        let mut shuffled = q.clone();
+        shuffled.reverse();
+        assert_eq!(h.search(&shuffled, 1).unwrap()[0].0, 17);
+        assert!(matches!(
+            h.insert(&[(DIMS as u32, 1.0)], 999),
+            Err(VcalError::DimensionMismatch { .. })
+        ));
+        assert!(matches!(
+            h.insert(&[(3, 1.0), (3, 2.0)], 999),
+            Err(VcalError::InvalidParameter(_))
+        ));
+    }
+}
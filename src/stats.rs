@@ -0,0 +1,33 @@
+//! stats.rs — summary structs returned by the index's introspection helpers.
+
+/// L2-norm distribution over live vectors (see `Hnsw::norm_stats`).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NormStats {
+    /// Number of live vectors included.
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+/// Named counters and parameters for monitoring (see `Hnsw::stats_detailed`).
+/// Non-exhaustive so fields can be added without breaking callers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IndexStats {
+    /// Live (searchable) vectors.
+    pub active: usize,
+    /// Deleted nodes still occupying a slot.
+    pub tombstones: usize,
+    /// All node slots, live or deleted (`active + tombstones`).
+    pub node_count: usize,
+    /// Approximate bytes held by live vectors and links.
+    pub total_bytes: usize,
+    pub dims: usize,
+    pub m: usize,
+    pub m_max0: usize,
+    pub ef: usize,
+    pub efc: usize,
+    /// Highest layer in the graph.
+    pub max_level: usize,
+}
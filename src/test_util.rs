@@ -0,0 +1,11 @@
+//! test_util.rs — fixtures shared by the unit-test modules.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// `n` seeded random vectors of `dims` components, each in `[-0.5, 0.5)`.
+pub(crate) fn rand_vecs(n: usize, dims: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| (0..dims).map(|_| rng.random::<f32>() - 0.5).collect())
+        .collect()
+}